@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::detector::FileScan;
+use crate::gitignore;
+
+/// Directory of the scan cache, relative to the scanned directory
+pub const CACHE_DIR: &str = ".enveil/cache";
+
+/// File holding the cached results inside `CACHE_DIR`
+const CACHE_FILE: &str = "scan.json";
+
+/// Per-file scan results from a previous run, so that unchanged files are
+/// not scanned again.
+///
+/// A file is unchanged when its size and modification time match, or when
+/// its content hash does (e.g. after a checkout that only touched mtimes).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    /// Scanner settings the results were produced with
+    settings: String,
+    files: HashMap<String, CachedFile>,
+    /// Results of the files seen by the current run, written by `save`
+    #[serde(skip)]
+    current: HashMap<String, CachedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    size: u64,
+    /// Modification time in nanoseconds since the epoch
+    modified: u128,
+    hash: String,
+    scan: FileScan,
+}
+
+impl ScanCache {
+    /// Load the cache of a directory. Results from other scanner settings,
+    /// or an unreadable cache, give an empty cache.
+    pub fn load(root: &Path, settings: &str) -> Self {
+        let cached = fs::read_to_string(root.join(CACHE_DIR).join(CACHE_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<ScanCache>(&content).ok())
+            .filter(|cache| cache.settings == settings);
+
+        cached.unwrap_or_else(|| Self { settings: settings.to_string(), ..Self::default() })
+    }
+
+    /// Write the results of the files seen by this run
+    pub fn save(&self, root: &Path) -> Result<(), String> {
+        let dir = root.join(CACHE_DIR);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        // The cache holds previews of the secrets found
+        gitignore::ignore_artifacts(&dir, &[CACHE_FILE])?;
+
+        let cache = ScanCache {
+            settings: self.settings.clone(),
+            files: self.current.clone(),
+            current: HashMap::new(),
+        };
+        let json = serde_json::to_string(&cache)
+            .map_err(|e| format!("Failed to serialize scan cache: {}", e))?;
+
+        fs::write(dir.join(CACHE_FILE), json)
+            .map_err(|e| format!("Failed to write scan cache: {}", e))
+    }
+
+    /// Delete the cache of a directory. Returns whether there was one.
+    pub fn clear(root: &Path) -> Result<bool, String> {
+        let dir = root.join(CACHE_DIR);
+        if !dir.exists() {
+            return Ok(false);
+        }
+
+        fs::remove_dir_all(&dir)
+            .map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))?;
+        Ok(true)
+    }
+
+    /// Cached result of a file whose size and modification time are unchanged
+    pub fn get(&mut self, path: &Path, metadata: &Metadata) -> Option<FileScan> {
+        let key = path.to_string_lossy().to_string();
+        let cached = self
+            .files
+            .get(&key)
+            .filter(|c| c.size == metadata.len() && Some(c.modified) == modified(metadata))?
+            .clone();
+
+        let scan = cached.scan.clone();
+        self.current.insert(key, cached);
+        Some(scan)
+    }
+
+    /// Cached result of a file whose content is unchanged
+    pub fn get_by_hash(&mut self, path: &Path, metadata: &Metadata, hash: &str) -> Option<FileScan> {
+        let cached = self.files.get(path.to_string_lossy().as_ref()).filter(|c| c.hash == hash)?;
+        let scan = cached.scan.clone();
+
+        self.insert(path, metadata, hash, scan.clone());
+        Some(scan)
+    }
+
+    /// Record the result of a file scanned by this run
+    pub fn insert(&mut self, path: &Path, metadata: &Metadata, hash: &str, scan: FileScan) {
+        let cached = CachedFile {
+            size: metadata.len(),
+            modified: modified(metadata).unwrap_or_default(),
+            hash: hash.to_string(),
+            scan,
+        };
+        self.current.insert(path.to_string_lossy().to_string(), cached);
+    }
+}
+
+fn modified(metadata: &Metadata) -> Option<u128> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("app.py");
+        fs::write(&file, "x = 1\n").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+
+        let mut cache = ScanCache::load(temp_dir.path(), "v1");
+        assert!(cache.get(&file, &metadata).is_none());
+        cache.insert(&file, &metadata, "hash", FileScan { suppressed: 2, ..FileScan::default() });
+        cache.save(temp_dir.path()).unwrap();
+
+        let mut cache = ScanCache::load(temp_dir.path(), "v1");
+        assert_eq!(cache.get(&file, &metadata).unwrap().suppressed, 2);
+        assert!(cache.get_by_hash(&file, &metadata, "other").is_none());
+        assert!(cache.get_by_hash(&file, &metadata, "hash").is_some());
+
+        // Other settings invalidate everything
+        let mut cache = ScanCache::load(temp_dir.path(), "v2");
+        assert!(cache.get(&file, &metadata).is_none());
+
+        // The cache never shows up as untracked
+        let ignore = fs::read_to_string(temp_dir.path().join(CACHE_DIR).join(".gitignore")).unwrap();
+        assert!(ignore.lines().any(|l| l == ".gitignore") && ignore.lines().any(|l| l == CACHE_FILE));
+
+        assert!(ScanCache::clear(temp_dir.path()).unwrap());
+        assert!(!ScanCache::clear(temp_dir.path()).unwrap());
+    }
+}
@@ -0,0 +1,516 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::detector::{self, Allowlist, CustomRule};
+use crate::extends;
+use crate::init::CONFIG_FILE;
+use crate::notifier::NotifyConfig;
+use crate::policy::Policy;
+use crate::sensitivity;
+
+/// Levels accepted by `--fail-on` and `scan.fail_on`
+pub const FAIL_ON_LEVELS: &[&str] = &["none", "low", "medium", "high", "critical"];
+
+/// Project configuration loaded from `.enveil.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Shared config this one builds on: an `https://` URL or a git URL
+    /// (`git@host:org/security.git#enveil.toml`)
+    pub extends: Option<String>,
+    /// SHA-256 the extended config must have
+    pub extends_sha256: Option<String>,
+    /// Glob patterns of paths skipped by both scan and protect
+    pub exclude: Vec<String>,
+    pub scan: ScanConfig,
+    pub protect: ProtectConfig,
+    pub env: EnvConfig,
+    /// Named environments (`[environments.prod]`), each with its own files,
+    /// secure directory and key
+    pub environments: HashMap<String, EnvironmentConfig>,
+    /// Custom detection rules
+    pub rules: Vec<CustomRule>,
+    /// Values never reported as secrets, on top of the built-in dummy values
+    pub allowlist: Allowlist,
+    /// Risk levels keyed by file type, over the built-in table: override
+    /// (".log" = "high"), add (".kdbx" = "high") or drop (".bak" = "none") types
+    pub severity: HashMap<String, String>,
+    /// Conditions `enveil scan --enforce` fails on (`[[policy]]`)
+    pub policy: Vec<Policy>,
+    /// Webhooks scan and watch POST findings to (`[[notify]]`)
+    pub notify: Vec<NotifyConfig>,
+}
+
+/// `[scan]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanConfig {
+    /// Default output format
+    pub format: Option<String>,
+    /// Extra file extensions treated as risky
+    pub extra_extensions: Vec<String>,
+    /// Glob patterns of paths excluded from scanning only
+    pub exclude: Vec<String>,
+    /// Lowest severity that makes a scan fail (none/low/medium/high/critical)
+    pub fail_on: Option<String>,
+    /// Findings below this severity are not reported (low/medium/high/critical)
+    pub min_severity: Option<String>,
+    /// Findings below this confidence are not reported (low/medium/high)
+    pub min_confidence: Option<String>,
+    /// Only run these rules, by id or name (all rules when empty)
+    pub enable_rules: Vec<String>,
+    /// Rules never run, by id or name (e.g. `GEN002` or `SECRET`)
+    pub disable_rules: Vec<String>,
+    /// Keep full confidence for findings in tests, fixtures, examples and docs
+    pub include_tests: bool,
+    /// Scan the files tracked by git instead of walking the directory
+    pub tracked_only: bool,
+    /// Files larger than this are skipped (e.g. `500KB`, `10MB`)
+    pub max_file_size: Option<String>,
+    /// Scan the printable strings of binary files instead of skipping them
+    pub scan_binary: bool,
+    /// Walk every hidden directory, not only `.github`, `.vscode`...
+    pub hidden: bool,
+}
+
+/// `[protect]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtectConfig {
+    /// Directory where protected files are stored
+    pub secure_dir: Option<String>,
+    /// Default protection action
+    pub action: Option<String>,
+    /// Store encryption keys in the OS keychain
+    pub keychain: bool,
+    /// Overwrite encrypted originals before deleting them
+    pub shred: bool,
+    /// Public keys encrypted files are encrypted to, instead of a shared key
+    pub recipients: Vec<String>,
+    /// Where protected files are stored (`local` or `vault`)
+    pub backend: Option<String>,
+    /// KV version 2 mount of the Vault backend
+    pub vault_mount: Option<String>,
+    /// Path prefix of the project's files in the Vault mount
+    pub vault_path: Option<String>,
+}
+
+/// `[env]` section: the variables `enveil env check` expects in .env
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvConfig {
+    /// Variables that must be set to a non-empty value; when set, .env is
+    /// checked against this list instead of .env.example
+    pub required: Vec<String>,
+    /// Variables that may be left out or empty
+    pub optional: Vec<String>,
+}
+
+/// `[environments.<name>]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvironmentConfig {
+    /// Files of the environment, relative to the project [default: .env.<name>]
+    pub files: Vec<String>,
+    /// File holding the base64 key of the environment
+    pub key_file: Option<String>,
+    /// Secure directory of the environment [default: <secure_dir>/<name>]
+    pub secure_dir: Option<String>,
+}
+
+/// A named environment with its defaults filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Environment {
+    pub name: String,
+    /// Files relative to the project
+    pub files: Vec<String>,
+    pub key_file: Option<String>,
+    pub secure_dir: String,
+}
+
+/// Standalone rules file passed with `--rules`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RulesFile {
+    rules: Vec<CustomRule>,
+}
+
+impl Config {
+    /// Load `.enveil.toml` from a project directory (defaults if missing)
+    pub fn load(project_path: &Path) -> Result<Self, String> {
+        let path = project_path.join(CONFIG_FILE);
+
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Self::from_file(&path)
+    }
+
+    /// Load configuration from an explicit file
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+
+        let config: Config = toml::from_str(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+
+        config.validate()?;
+        config.resolve_extends(&extends::cache_dir(), extends::refresh())
+    }
+
+    /// Merge the extended config, if any, under this one
+    fn resolve_extends(self, cache_dir: &Path, refresh: bool) -> Result<Self, String> {
+        let source = match &self.extends {
+            Some(source) => source,
+            None => return Ok(self),
+        };
+        let content = extends::load(source, self.extends_sha256.as_deref(), cache_dir, refresh)?;
+        let base: Config = toml::from_str(&content)
+            .map_err(|e| format!("Invalid extended config {}: {}", source, e))?;
+        if base.extends.is_some() {
+            return Err(format!("Extended config {} cannot extend another config", source));
+        }
+        base.validate()?;
+        Ok(base.merge(self))
+    }
+
+    /// This config with `local` on top: lists are joined, local settings
+    /// win, and local rules replace shared rules of the same name
+    fn merge(self, local: Config) -> Config {
+        let mut rules: Vec<CustomRule> = self.rules
+            .into_iter()
+            .filter(|rule| !local.rules.iter().any(|own| own.name == rule.name))
+            .collect();
+        rules.extend(local.rules);
+        let mut environments = self.environments;
+        environments.extend(local.environments);
+        let mut severity = self.severity;
+        severity.extend(local.severity);
+
+        Config {
+            extends: local.extends,
+            extends_sha256: local.extends_sha256,
+            exclude: [self.exclude, local.exclude].concat(),
+            scan: ScanConfig {
+                format: local.scan.format.or(self.scan.format),
+                extra_extensions: [self.scan.extra_extensions, local.scan.extra_extensions].concat(),
+                exclude: [self.scan.exclude, local.scan.exclude].concat(),
+                fail_on: local.scan.fail_on.or(self.scan.fail_on),
+                min_severity: local.scan.min_severity.or(self.scan.min_severity),
+                min_confidence: local.scan.min_confidence.or(self.scan.min_confidence),
+                enable_rules: match local.scan.enable_rules.is_empty() {
+                    true => self.scan.enable_rules,
+                    false => local.scan.enable_rules,
+                },
+                disable_rules: [self.scan.disable_rules, local.scan.disable_rules].concat(),
+                include_tests: self.scan.include_tests || local.scan.include_tests,
+                tracked_only: self.scan.tracked_only || local.scan.tracked_only,
+                max_file_size: local.scan.max_file_size.or(self.scan.max_file_size),
+                scan_binary: self.scan.scan_binary || local.scan.scan_binary,
+                hidden: self.scan.hidden || local.scan.hidden,
+            },
+            protect: ProtectConfig {
+                secure_dir: local.protect.secure_dir.or(self.protect.secure_dir),
+                action: local.protect.action.or(self.protect.action),
+                keychain: self.protect.keychain || local.protect.keychain,
+                shred: self.protect.shred || local.protect.shred,
+                recipients: [self.protect.recipients, local.protect.recipients].concat(),
+                backend: local.protect.backend.or(self.protect.backend),
+                vault_mount: local.protect.vault_mount.or(self.protect.vault_mount),
+                vault_path: local.protect.vault_path.or(self.protect.vault_path),
+            },
+            env: EnvConfig {
+                required: [self.env.required, local.env.required].concat(),
+                optional: [self.env.optional, local.env.optional].concat(),
+            },
+            environments,
+            rules,
+            allowlist: Allowlist {
+                values: [self.allowlist.values, local.allowlist.values].concat(),
+                regexes: [self.allowlist.regexes, local.allowlist.regexes].concat(),
+            },
+            severity,
+            policy: [self.policy, local.policy].concat(),
+            notify: [self.notify, local.notify].concat(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(fail_on) = &self.scan.fail_on {
+            if !FAIL_ON_LEVELS.contains(&fail_on.as_str()) {
+                return Err(format!(
+                    "Invalid fail_on: {} (expected none, low, medium, high or critical)",
+                    fail_on
+                ));
+            }
+        }
+
+        if let Some(level) = &self.scan.min_severity {
+            if !detector::SEVERITIES.contains(&level.as_str()) {
+                return Err(format!(
+                    "Invalid min_severity: {} (expected critical, high, medium or low)",
+                    level
+                ));
+            }
+        }
+
+        if let Some(level) = &self.scan.min_confidence {
+            if !detector::CONFIDENCES.contains(&level.as_str()) {
+                return Err(format!("Invalid min_confidence: {} (expected high, medium or low)", level));
+            }
+        }
+
+        sensitivity::validate(&self.severity)?;
+
+        for policy in &self.policy {
+            policy.validate()?;
+        }
+
+        for notifier in &self.notify {
+            notifier.validate()?;
+        }
+
+        if let Some(pin) = &self.extends_sha256 {
+            if self.extends.is_none() {
+                return Err("extends_sha256 is set without extends".to_string());
+            }
+            if pin.len() != 64 || !pin.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid extends_sha256: {} (expected 64 hex digits)", pin));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Settings of a named environment, the defaults for one the config
+    /// does not declare. `secure_dir` is the secure directory of the project.
+    pub fn environment(&self, name: &str, secure_dir: &str) -> Result<Environment, String> {
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+        if !valid {
+            return Err(format!("Invalid environment name: {} (expected letters, digits, - and _)", name));
+        }
+
+        let declared = self.environments.get(name).cloned().unwrap_or_default();
+        let files = match declared.files.is_empty() {
+            true => vec![format!(".env.{}", name)],
+            false => declared.files,
+        };
+        Ok(Environment {
+            name: name.to_string(),
+            files,
+            key_file: declared.key_file,
+            secure_dir: declared.secure_dir.unwrap_or_else(|| format!("{}/{}", secure_dir.trim_end_matches('/'), name)),
+        })
+    }
+
+    /// Exclude patterns that apply to scanning
+    pub fn scan_excludes(&self) -> Vec<String> {
+        self.exclude.iter().chain(&self.scan.exclude).cloned().collect()
+    }
+}
+
+/// Load `[[rules]]` entries from a standalone TOML rules file
+pub fn load_rules_file(path: &Path) -> Result<Vec<CustomRule>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read rules file {}: {}", path.display(), e))?;
+
+    let file: RulesFile = toml::from_str(&content)
+        .map_err(|e| format!("Invalid rules file {}: {}", path.display(), e))?;
+
+    Ok(file.rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_missing_config_is_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::load(temp_dir.path()).unwrap();
+        assert!(config.scan.format.is_none());
+        assert!(config.rules.is_empty());
+    }
+
+    #[test]
+    fn test_load_config() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join(CONFIG_FILE),
+            r#"
+exclude = ["testdata/**"]
+
+[scan]
+format = "json"
+extra_extensions = [".properties"]
+exclude = ["fixtures"]
+
+[protect]
+secure_dir = "vault"
+keychain = true
+
+[env]
+required = ["DATABASE_URL"]
+
+[[rules]]
+name = "INTERNAL_TOKEN"
+regex = "itk_[a-z0-9]{16}"
+severity = "high"
+
+[severity]
+".log" = "high"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(temp_dir.path()).unwrap();
+        assert_eq!(config.scan.format.as_deref(), Some("json"));
+        assert_eq!(config.scan.extra_extensions, vec![".properties"]);
+        assert_eq!(config.protect.secure_dir.as_deref(), Some("vault"));
+        assert!(config.protect.keychain);
+        assert_eq!(config.env.required, vec!["DATABASE_URL"]);
+        assert_eq!(config.rules[0].name, "INTERNAL_TOKEN");
+        assert_eq!(config.rules[0].severity.as_deref(), Some("high"));
+        assert_eq!(config.severity.get(".log").map(String::as_str), Some("high"));
+        assert_eq!(config.scan_excludes(), vec!["testdata/**", "fixtures"]);
+    }
+
+    #[test]
+    fn test_environment() {
+        let config: Config = toml::from_str(
+            "[environments.prod]\nfiles = [\"deploy/.env.prod\"]\nkey_file = \"prod.key\"\n",
+        )
+        .unwrap();
+
+        let prod = config.environment("prod", "enveil_secure").unwrap();
+        assert_eq!(prod.files, vec!["deploy/.env.prod"]);
+        assert_eq!(prod.key_file.as_deref(), Some("prod.key"));
+        assert_eq!(prod.secure_dir, "enveil_secure/prod");
+
+        let staging = config.environment("staging", "vault/").unwrap();
+        assert_eq!(staging.files, vec![".env.staging"]);
+        assert_eq!(staging.secure_dir, "vault/staging");
+
+        assert!(config.environment("../prod", "enveil_secure").is_err());
+    }
+
+    #[test]
+    fn test_invalid_severity() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(CONFIG_FILE), "[severity]\n\".log\" = \"urgent\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join(CONFIG_FILE),
+            "[[policy]]\nname = \"no committed .env\"\ncheck = \"committed_files\"\npaths = [\"**/.env\"]\n",
+        )
+        .unwrap();
+        let config = Config::load(temp_dir.path()).unwrap();
+        assert_eq!(config.policy.len(), 1);
+        assert_eq!(config.policy[0].max, 0);
+
+        fs::write(temp_dir.path().join(CONFIG_FILE), "[[policy]]\nname = \"x\"\nmin_severity = \"urgent\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_extends() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = temp_dir.path().join("cache");
+        let source = "https://security.example.com/enveil-org.toml";
+        let shared = r#"
+exclude = ["vendor/**"]
+
+[scan]
+fail_on = "medium"
+disable_rules = ["GEN002"]
+
+[[rules]]
+name = "ACME_KEY"
+regex = "acme_[0-9]{8}"
+
+[[rules]]
+name = "ACME_SESSION"
+regex = "acs_[0-9]{8}"
+
+[allowlist]
+values = ["acme_00000000"]
+
+[[policy]]
+name = "no critical findings"
+min_severity = "critical"
+"#;
+        // Cached copies are used without fetching
+        fs::create_dir_all(&cache).unwrap();
+        fs::write(cache.join(format!("{}.toml", detector::fingerprint(source))), shared).unwrap();
+
+        let local: Config = toml::from_str(&format!(
+            "extends = \"{}\"\nextends_sha256 = \"{}\"\nexclude = [\"build\"]\n\n[scan]\nfail_on = \"high\"\n\n[[rules]]\nname = \"ACME_KEY\"\nregex = \"acme_[0-9]{{12}}\"\n",
+            source,
+            extends::checksum(shared)
+        ))
+        .unwrap();
+        local.validate().unwrap();
+        let config = local.clone().resolve_extends(&cache, false).unwrap();
+        assert_eq!(config.exclude, vec!["vendor/**", "build"]);
+        assert_eq!(config.scan.fail_on.as_deref(), Some("high"));
+        assert_eq!(config.scan.disable_rules, vec!["GEN002"]);
+        let rules: Vec<(&str, &str)> = config.rules.iter().map(|r| (r.name.as_str(), r.regex.as_str())).collect();
+        assert_eq!(rules, vec![("ACME_SESSION", "acs_[0-9]{8}"), ("ACME_KEY", "acme_[0-9]{12}")]);
+        assert_eq!(config.allowlist.values, vec!["acme_00000000"]);
+        assert_eq!(config.policy[0].name, "no critical findings");
+
+        // A config that is neither cached nor reachable is an error
+        let mut unreachable = local;
+        unreachable.extends = Some("https://127.0.0.1:1/enveil-org.toml".to_string());
+        assert!(unreachable.resolve_extends(&cache, false).unwrap_err().contains("Failed to fetch"));
+    }
+
+    #[test]
+    fn test_invalid_extends() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(CONFIG_FILE), "extends_sha256 = \"abc\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).is_err());
+        fs::write(temp_dir.path().join(CONFIG_FILE), "extends = \"https://example.com/a.toml\"\nextends_sha256 = \"abc\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).unwrap_err().contains("64 hex digits"));
+        fs::write(temp_dir.path().join(CONFIG_FILE), "extends = \"../org.toml\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).unwrap_err().contains("Unsupported extends"));
+    }
+
+    #[test]
+    fn test_invalid_fail_on() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(CONFIG_FILE), "[scan]\nfail_on = \"always\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_rules_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("rules.toml");
+        fs::write(
+            &path,
+            "[[rules]]\nname = \"ACME_KEY\"\nregex = \"acme_[0-9]{8}\"\ndescription = \"ACME API key\"\n",
+        )
+        .unwrap();
+
+        let rules = load_rules_file(&path).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].description.as_deref(), Some("ACME API key"));
+    }
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(CONFIG_FILE), "[scan]\nformt = \"json\"\n").unwrap();
+        assert!(Config::load(temp_dir.path()).is_err());
+    }
+}
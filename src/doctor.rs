@@ -0,0 +1,319 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::Config;
+use crate::git;
+use crate::git_hooks::{Framework, GitHooks, Hook};
+use crate::keychain;
+use crate::restorer::{DecryptionKey, FileRestorer};
+
+/// Outcome of a setup check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Ok,
+    /// Works, but is likely not what the user wants
+    Warning,
+    Failed,
+}
+
+/// One verified aspect of the setup, with how to fix it when it is not ok
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self { name, status: Status::Ok, message: message.into(), fix: None }
+    }
+
+    fn warning(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Warning, message: message.into(), fix: Some(fix.into()) }
+    }
+
+    fn failed(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Failed, message: message.into(), fix: Some(fix.into()) }
+    }
+}
+
+/// Verifies that Enveil is set up correctly in a project
+pub struct Doctor {
+    project_path: PathBuf,
+    config_path: PathBuf,
+    default_secure_dir: String,
+    passphrase: Option<String>,
+}
+
+impl Doctor {
+    /// Check the project at `project_path`, configured by `config_path`.
+    /// Encrypted files are tried with the key in the OS keychain and the
+    /// passphrase, when given.
+    pub fn new(project_path: &Path, config_path: &Path, default_secure_dir: &str, passphrase: Option<String>) -> Self {
+        Self {
+            project_path: project_path.to_path_buf(),
+            config_path: config_path.to_path_buf(),
+            default_secure_dir: default_secure_dir.to_string(),
+            passphrase,
+        }
+    }
+
+    /// Run every check, in order
+    pub fn run(&self) -> Vec<Check> {
+        let (config, config_check) = self.check_config();
+        let secure_dir = self.project_path.join(
+            config
+                .as_ref()
+                .and_then(|config| config.protect.secure_dir.clone())
+                .unwrap_or_else(|| self.default_secure_dir.clone()),
+        );
+
+        let mut checks = vec![self.check_repository()];
+        if checks[0].status == Status::Ok {
+            checks.push(self.check_hooks());
+        }
+        checks.push(self.check_binary());
+        checks.push(config_check);
+        if checks[0].status == Status::Ok {
+            checks.push(self.check_secure_dir_ignored(&secure_dir));
+        }
+        checks.push(self.check_decryptable(&secure_dir));
+        checks
+    }
+
+    fn check_repository(&self) -> Check {
+        if git::is_work_tree(&self.project_path) {
+            Check::ok("Git repository", format!("{} is a git work tree", self.project_path.display()))
+        } else {
+            Check::failed(
+                "Git repository",
+                format!("{} is not a git repository", self.project_path.display()),
+                "Run `git init`, or run doctor from the repository",
+            )
+        }
+    }
+
+    fn check_hooks(&self) -> Check {
+        let hooks = GitHooks::new(&self.project_path);
+        let installed: Vec<&str> = Hook::ALL
+            .into_iter()
+            .filter(|hook| {
+                hooks.is_installed(*hook)
+                    || Framework::Husky.is_installed(&self.project_path, *hook)
+                    || Framework::PreCommit.is_installed(&self.project_path, *hook)
+            })
+            .map(|hook| hook.name())
+            .collect();
+
+        if installed.is_empty() {
+            Check::warning("Git hooks", "No Enveil hooks installed", "Run `enveil install`")
+        } else {
+            Check::ok("Git hooks", format!("Installed: {}", installed.join(", ")))
+        }
+    }
+
+    /// The hooks run `enveil` from PATH
+    fn check_binary(&self) -> Check {
+        let binary = match find_on_path("enveil") {
+            Some(binary) => binary,
+            None => {
+                return Check::failed(
+                    "enveil on PATH",
+                    "The hooks run `enveil`, which is not on PATH",
+                    "Install it with `cargo install --path .` or add its directory to PATH",
+                )
+            }
+        };
+
+        let version = Command::new(&binary)
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+        let expected = format!("enveil {}", env!("CARGO_PKG_VERSION"));
+
+        match version {
+            Some(version) if version == expected => Check::ok("enveil on PATH", format!("{} ({})", binary.display(), version)),
+            Some(version) => Check::warning(
+                "enveil on PATH",
+                format!("{} is {}, this is {}", binary.display(), version, expected),
+                "Reinstall enveil so the hooks run the same version",
+            ),
+            None => Check::failed(
+                "enveil on PATH",
+                format!("{} does not run", binary.display()),
+                "Reinstall enveil",
+            ),
+        }
+    }
+
+    fn check_config(&self) -> (Option<Config>, Check) {
+        if !self.config_path.is_file() {
+            let check = Check::warning(
+                "Config file",
+                format!("No {}, using the defaults", self.config_path.display()),
+                "Run `enveil init` to write a starter config",
+            );
+            return (Some(Config::default()), check);
+        }
+
+        match Config::from_file(&self.config_path) {
+            Ok(config) => (Some(config), Check::ok("Config file", format!("{} is valid", self.config_path.display()))),
+            Err(e) => (
+                None,
+                Check::failed("Config file", e, "Fix the error, or write a new config with `enveil init --force`"),
+            ),
+        }
+    }
+
+    fn check_secure_dir_ignored(&self, secure_dir: &Path) -> Check {
+        let relative = secure_dir.strip_prefix(&self.project_path).unwrap_or(secure_dir);
+        let pattern = format!("{}/", relative.display());
+
+        let tracked = git::tracked_files(&self.project_path)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file| Path::new(file).starts_with(relative))
+            .count();
+        if tracked > 0 {
+            return Check::failed(
+                "Secure directory ignored",
+                format!("{} file(s) of {} are tracked by git", tracked, pattern),
+                format!("Run `git rm -r --cached {}` and add {} to .gitignore", pattern, pattern),
+            );
+        }
+
+        if git::is_ignored(&self.project_path, &pattern) {
+            Check::ok("Secure directory ignored", format!("{} is in .gitignore", pattern))
+        } else {
+            Check::failed(
+                "Secure directory ignored",
+                format!("{} is not ignored, protected files could be committed", pattern),
+                format!("Add {} to .gitignore (`enveil init` does it)", pattern),
+            )
+        }
+    }
+
+    fn check_decryptable(&self, secure_dir: &Path) -> Check {
+        let restorer = FileRestorer::new(secure_dir.to_path_buf());
+        let files = restorer.find_encrypted_files();
+        if files.is_empty() {
+            return Check::ok("Protected files decryptable", "No encrypted files");
+        }
+
+        let mut keys = Vec::new();
+        if let Some(passphrase) = &self.passphrase {
+            keys.push(DecryptionKey::Passphrase(passphrase.clone()));
+        }
+        // A keychain error leaves only the passphrase to try
+        if let Ok(Some(key)) = keychain::load_key(&self.project_path) {
+            keys.push(DecryptionKey::Raw(key));
+        }
+        if keys.is_empty() {
+            return Check::warning(
+                "Protected files decryptable",
+                format!("{} encrypted file(s), but no key in the OS keychain or passphrase to check them with", files.len()),
+                "Set ENVEIL_PASSPHRASE, or keep the key with `enveil protect --action encrypt --keychain`",
+            );
+        }
+
+        let undecryptable: Vec<String> = files
+            .iter()
+            .filter(|file| {
+                !keys.iter().any(|key| {
+                    fs::File::open(file)
+                        .map_err(|e| e.to_string())
+                        .and_then(|input| key.decrypt_stream(io::BufReader::new(input), io::sink()))
+                        .is_ok()
+                })
+            })
+            .map(|file| file.display().to_string())
+            .collect();
+
+        if undecryptable.is_empty() {
+            Check::ok("Protected files decryptable", format!("{} encrypted file(s) decrypt", files.len()))
+        } else {
+            Check::failed(
+                "Protected files decryptable",
+                format!("Cannot decrypt with the configured key: {}", undecryptable.join(", ")),
+                "Decrypt them with the key printed by protect (`enveil decrypt --key <key>`)",
+            )
+        }
+    }
+}
+
+/// Executable of a command on PATH
+fn find_on_path(command: &str) -> Option<PathBuf> {
+    let name = format!("{}{}", command, std::env::consts::EXE_SUFFIX);
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(&name))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::{self, FileProtector, ProtectOption};
+    use tempfile::TempDir;
+
+    fn check<'a>(checks: &'a [Check], name: &str) -> &'a Check {
+        checks.iter().find(|check| check.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_outside_a_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let doctor = Doctor::new(temp_dir.path(), &temp_dir.path().join(".enveil.toml"), "enveil_secure", None);
+        let checks = doctor.run();
+
+        assert_eq!(check(&checks, "Git repository").status, Status::Failed);
+        assert!(checks.iter().all(|check| check.name != "Git hooks"));
+        assert_eq!(check(&checks, "Config file").status, Status::Warning);
+        assert_eq!(check(&checks, "Protected files decryptable").status, Status::Ok);
+    }
+
+    #[test]
+    fn test_repository_setup() {
+        let temp_dir = TempDir::new().unwrap();
+        Command::new("git").args(["init", "-q"]).current_dir(temp_dir.path()).status().unwrap();
+        fs::write(temp_dir.path().join(".enveil.toml"), "[scan]\nfail_on = \"severe\"\n").unwrap();
+
+        let doctor = Doctor::new(temp_dir.path(), &temp_dir.path().join(".enveil.toml"), "enveil_secure", None);
+        let checks = doctor.run();
+        assert_eq!(check(&checks, "Git hooks").status, Status::Warning);
+        assert_eq!(check(&checks, "Config file").status, Status::Failed);
+        let ignored = check(&checks, "Secure directory ignored");
+        assert_eq!(ignored.status, Status::Failed);
+        assert!(ignored.fix.as_ref().unwrap().contains("Add enveil_secure/ to .gitignore"));
+
+        fs::write(temp_dir.path().join(".enveil.toml"), "[protect]\nsecure_dir = \"vault\"\n").unwrap();
+        fs::write(temp_dir.path().join(".gitignore"), "vault/\n").unwrap();
+        let checks = doctor.run();
+        assert_eq!(check(&checks, "Config file").status, Status::Ok);
+        assert_eq!(check(&checks, "Secure directory ignored").status, Status::Ok);
+    }
+
+    #[test]
+    fn test_encrypted_files_decrypt_with_the_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let salt = protector::generate_salt();
+        let key = protector::derive_key("hunter2", &salt).unwrap();
+        FileProtector::new(secure_dir)
+            .with_salt(salt)
+            .protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+
+        let config = temp_dir.path().join(".enveil.toml");
+        let checks = Doctor::new(temp_dir.path(), &config, "enveil_secure", Some("hunter2".to_string())).run();
+        assert_eq!(check(&checks, "Protected files decryptable").status, Status::Ok);
+    }
+}
@@ -0,0 +1,263 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use base64::Engine;
+use rand::Rng;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::env;
+use crate::protector;
+use crate::stream;
+
+/// Encrypted bundle of environments, meant to be committed
+pub const VAULT_FILE: &str = ".env.vault";
+
+/// Keys of the environments of the bundle, never committed
+pub const KEYS_FILE: &str = ".env.keys";
+
+/// Environment variable holding the key(s) dotenv tooling decrypts with
+pub const KEY_ENV: &str = "DOTENV_KEY";
+
+const VAULT_PREFIX: &str = "DOTENV_VAULT_";
+const KEY_PREFIX: &str = "DOTENV_KEY_";
+const NONCE_LEN: usize = 12;
+
+const VAULT_HEADER: &str = "#/-------------------.env.vault---------------------/
+#/         cloud-agnostic vaulting standard         /
+#/   [how it works](https://dotenv.org/env-vault)   /
+#/--------------------------------------------------/
+";
+
+const KEYS_HEADER: &str = "#/!!!!!!!!!!!!!!!!!!!.env.keys!!!!!!!!!!!!!!!!!!!!!!/
+#/   DOTENV_KEYs. DO NOT commit to source control   /
+#/   [how it works](https://dotenv.org/env-keys)    /
+#/--------------------------------------------------/
+";
+
+/// Key of one environment, written as
+/// `dotenv://:key_<hex>@dotenv.local/vault/.env.vault?environment=<name>`
+#[derive(Clone, PartialEq)]
+pub struct DotenvKey {
+    pub key: [u8; 32],
+    pub environment: String,
+}
+
+impl DotenvKey {
+    /// New random key for an environment
+    pub fn generate(environment: &str) -> Self {
+        Self { key: protector::generate_key(), environment: environment.to_lowercase() }
+    }
+
+    /// Parse the keys of a `DOTENV_KEY` value, which may list several
+    /// separated by commas
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        value.split(',').map(|key| key.trim().parse()).collect()
+    }
+}
+
+impl fmt::Display for DotenvKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex: String = self.key.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(f, "dotenv://:key_{}@dotenv.local/vault/{}?environment={}", hex, VAULT_FILE, self.environment)
+    }
+}
+
+impl FromStr for DotenvKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid {} ({}): expected dotenv://:key_<hex>@...?environment=<name>", KEY_ENV, reason);
+
+        let rest = s.strip_prefix("dotenv://").ok_or_else(|| invalid("not a dotenv:// URI"))?;
+        let (credentials, location) = rest.split_once('@').ok_or_else(|| invalid("no key"))?;
+        let password = credentials.rsplit(':').next().unwrap_or_default();
+        // dotenv reads the key from the last 64 hex digits of the password
+        let hex = password.get(password.len().saturating_sub(64)..).unwrap_or_default();
+        let key: [u8; 32] = stream::decode_hex(hex)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("the key is not 64 hex digits"))?;
+
+        let query = location.split_once('?').map(|(_, query)| query).unwrap_or_default();
+        let environment = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("environment="))
+            .filter(|environment| !environment.is_empty())
+            .ok_or_else(|| invalid("no environment"))?;
+
+        Ok(Self { key, environment: environment.to_lowercase() })
+    }
+}
+
+/// The environments of a `.env.vault` file, each a .env file encrypted
+/// with the key of the environment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvVault {
+    /// Environment names and their base64 ciphertext, in file order
+    pub environments: Vec<(String, String)>,
+}
+
+impl EnvVault {
+    pub fn parse(content: &str) -> Self {
+        let environments = env::parse(content)
+            .into_iter()
+            .filter_map(|var| {
+                let environment = var.key.strip_prefix(VAULT_PREFIX)?.to_lowercase();
+                Some((environment, var.value))
+            })
+            .collect();
+        Self { environments }
+    }
+
+    /// Encrypt the content of a .env file as the environment of `key`,
+    /// replacing the environment if the vault has it
+    pub fn seal(&mut self, content: &str, key: &DotenvKey) -> Result<(), String> {
+        let ciphertext = encrypt(content, &key.key)?;
+        match self.environments.iter_mut().find(|(environment, _)| *environment == key.environment) {
+            Some(existing) => existing.1 = ciphertext,
+            None => self.environments.push((key.environment.clone(), ciphertext)),
+        }
+        Ok(())
+    }
+
+    /// Decrypt the environment of a key
+    pub fn open(&self, key: &DotenvKey) -> Result<String, String> {
+        let ciphertext = self
+            .environments
+            .iter()
+            .find(|(environment, _)| *environment == key.environment)
+            .map(|(_, ciphertext)| ciphertext)
+            .ok_or_else(|| format!("No {}{} in {}", VAULT_PREFIX, key.environment.to_uppercase(), VAULT_FILE))?;
+        decrypt(ciphertext, &key.key)
+            .map_err(|e| format!("{} environment: {}", key.environment, e))
+    }
+
+    /// Content of the `.env.vault` file
+    pub fn render(&self) -> String {
+        let mut content = VAULT_HEADER.to_string();
+        for (environment, ciphertext) in &self.environments {
+            content.push_str(&format!("\n# {}\n{}{}=\"{}\"\n", environment, VAULT_PREFIX, environment.to_uppercase(), ciphertext));
+        }
+        content
+    }
+}
+
+/// Keys listed in a `.env.keys` file
+pub fn parse_keys(content: &str) -> Result<Vec<DotenvKey>, String> {
+    env::parse(content)
+        .into_iter()
+        .filter(|var| var.key.starts_with(KEY_PREFIX))
+        .map(|var| var.value.parse())
+        .collect()
+}
+
+/// Content of a `.env.keys` file
+pub fn render_keys(keys: &[DotenvKey]) -> String {
+    let mut content = KEYS_HEADER.to_string();
+    for key in keys {
+        content.push_str(&format!("{}{}=\"{}\"\n", KEY_PREFIX, key.environment.to_uppercase(), key));
+    }
+    content
+}
+
+/// Environment a .env file is bundled as, following dotenv-vault:
+/// `.env` is `development`, `.env.production` is `production`
+pub fn environment_of(file: &Path) -> String {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    match name.strip_prefix(".env.") {
+        Some(environment) if !environment.is_empty() => environment.to_lowercase(),
+        _ => "development".to_string(),
+    }
+}
+
+/// Encrypt to base64 of `nonce || ciphertext || tag`
+fn encrypt(plaintext: &str, key: &[u8; 32]) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| format!("Failed to create cipher: {}", e))?;
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill(&mut nonce);
+
+    let sealed = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode([nonce.as_slice(), sealed.as_slice()].concat()))
+}
+
+fn decrypt(ciphertext: &str, key: &[u8; 32]) -> Result<String, String> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(ciphertext.trim())
+        .map_err(|_| "Invalid ciphertext (not base64)".to_string())?;
+    if data.len() < NONCE_LEN {
+        return Err("Ciphertext is truncated".to_string());
+    }
+
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| format!("Failed to create cipher: {}", e))?;
+    let (nonce, sealed) = data.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), sealed)
+        .map_err(|_| "Decryption failed (wrong key or corrupted vault)".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "Decrypted content is not text".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_round_trip() {
+        let key = DotenvKey::generate("Production");
+        let encoded = key.to_string();
+        assert!(encoded.starts_with("dotenv://:key_"));
+        assert!(encoded.ends_with("@dotenv.local/vault/.env.vault?environment=production"));
+        assert!(encoded.parse::<DotenvKey>().unwrap() == key);
+
+        let keys = DotenvKey::parse_list(&format!("{}, {}", encoded, DotenvKey::generate("ci"))).unwrap();
+        assert_eq!(keys[1].environment, "ci");
+
+        assert!("dotenv://:key_abc@dotenv.local/vault/.env.vault?environment=ci".parse::<DotenvKey>().is_err());
+        assert!(format!("dotenv://:key_{}@dotenv.local/vault/.env.vault", "a".repeat(64)).parse::<DotenvKey>().is_err());
+    }
+
+    #[test]
+    fn test_decrypts_dotenv_vault_ciphertext() {
+        // Encrypted the way the dotenv libraries do: AES-256-GCM, base64 of nonce, ciphertext and tag
+        let key: DotenvKey = format!("dotenv://:key_{}@dotenv.org/vault/.env.vault?environment=production", "11".repeat(32))
+            .parse()
+            .unwrap();
+        let cipher = Aes256Gcm::new_from_slice(&key.key).unwrap();
+        let nonce = [3u8; NONCE_LEN];
+        let sealed = cipher.encrypt(Nonce::from_slice(&nonce), b"API_KEY=abc\n".as_slice()).unwrap();
+        let ciphertext = base64::engine::general_purpose::STANDARD.encode([nonce.as_slice(), &sealed].concat());
+
+        let vault = EnvVault::parse(&format!("{}\n# production\nDOTENV_VAULT_PRODUCTION=\"{}\"\n", VAULT_HEADER, ciphertext));
+        assert_eq!(vault.open(&key).unwrap(), "API_KEY=abc\n");
+        assert!(vault.open(&DotenvKey::generate("production")).is_err());
+        assert!(vault.open(&DotenvKey::generate("staging")).is_err());
+    }
+
+    #[test]
+    fn test_seal_and_render() {
+        let development = DotenvKey::generate("development");
+        let production = DotenvKey::generate("production");
+        let mut vault = EnvVault::default();
+        vault.seal("PORT=3000\n", &development).unwrap();
+        vault.seal("PORT=80\n", &production).unwrap();
+        vault.seal("PORT=8080\n", &development).unwrap();
+
+        let reparsed = EnvVault::parse(&vault.render());
+        assert_eq!(reparsed, vault);
+        assert_eq!(reparsed.environments[0].0, "development");
+        assert_eq!(reparsed.open(&development).unwrap(), "PORT=8080\n");
+        assert_eq!(reparsed.open(&production).unwrap(), "PORT=80\n");
+
+        let keys = parse_keys(&render_keys(&[development.clone(), production])).unwrap();
+        assert!(keys[0] == development);
+    }
+
+    #[test]
+    fn test_environment_of() {
+        assert_eq!(environment_of(Path::new(".env")), "development");
+        assert_eq!(environment_of(Path::new("app/.env.Production")), "production");
+    }
+}
@@ -0,0 +1,75 @@
+/// Name the environment of the enveil process is reported under
+pub const CURRENT_NAME: &str = "<environment>";
+
+/// Variables of the environment enveil runs in (the exported variables of
+/// the calling shell), sorted by name
+pub fn current() -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = std::env::vars_os()
+        .map(|(name, value)| (name.to_string_lossy().to_string(), value.to_string_lossy().to_string()))
+        .collect();
+    vars.sort();
+    vars
+}
+
+/// Path the environment of a process is read from
+pub fn process_path(pid: u32) -> String {
+    format!("/proc/{}/environ", pid)
+}
+
+/// Variables a process was started with, read from `/proc/<pid>/environ`.
+/// Reading the environment of another user's process needs privileges.
+#[cfg(target_os = "linux")]
+pub fn of_process(pid: u32) -> Result<Vec<(String, String)>, String> {
+    let path = process_path(pid);
+    let content = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut vars = parse(&content);
+    vars.sort();
+    Ok(vars)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn of_process(pid: u32) -> Result<Vec<(String, String)>, String> {
+    let _ = pid;
+    Err("Reading the environment of another process is only supported on Linux".to_string())
+}
+
+/// Parse NUL separated `NAME=value` entries, as in `/proc/<pid>/environ`
+pub fn parse(content: &[u8]) -> Vec<(String, String)> {
+    content
+        .split(|b| *b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (name, value) = entry.split_once('=')?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+/// Variables as `NAME=value` lines, the way the detector reads .env files
+pub fn render(vars: &[(String, String)]) -> String {
+    vars.iter().map(|(name, value)| format!("{}={}\n", name, value)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        let vars = parse(b"PATH=/usr/bin\0TOKEN=a=b\0=C:=C:\\\0broken\0");
+        assert_eq!(vars, vec![
+            ("PATH".to_string(), "/usr/bin".to_string()),
+            ("TOKEN".to_string(), "a=b".to_string()),
+        ]);
+        assert_eq!(render(&vars), "PATH=/usr/bin\nTOKEN=a=b\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_of_process() {
+        let vars = of_process(std::process::id()).unwrap();
+        assert!(vars.iter().any(|(name, _)| name == "PATH"));
+        assert!(of_process(u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::detector;
+use crate::git;
+use crate::home;
+use crate::init::CONFIG_FILE;
+
+const USER_AGENT: &str = concat!("enveil/", env!("CARGO_PKG_VERSION"));
+
+static REFRESH: OnceLock<bool> = OnceLock::new();
+
+/// Fetch extended configs again instead of reading the cache, once at
+/// startup (`--refresh-config`)
+pub fn set_refresh(refresh: bool) {
+    let _ = REFRESH.set(refresh);
+}
+
+pub(crate) fn refresh() -> bool {
+    REFRESH.get().copied().unwrap_or(false)
+}
+
+/// Where an `extends` config is fetched from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// Downloaded over HTTPS (or HTTP when pinned)
+    Url(String),
+    /// A file of a git repository, cloned at its default branch
+    Git { url: String, file: String },
+}
+
+impl Source {
+    /// Parse an `extends` value: an `https://` URL, or a git URL with the
+    /// config file after `#` (`git@host:org/security.git#enveil.toml`).
+    /// URLs are git when they start with `git+`, `git@` or `ssh://`, or end in `.git`.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (location, file) = match source.split_once('#') {
+            Some((location, file)) => (location, Some(file)),
+            None => (source, None),
+        };
+
+        let git_url = match location.strip_prefix("git+") {
+            Some(url) => Some(url),
+            None if location.starts_with("git@") || location.starts_with("ssh://") || location.ends_with(".git") => Some(location),
+            None => None,
+        };
+        if let Some(url) = git_url {
+            let file = file.filter(|f| !f.is_empty()).unwrap_or(CONFIG_FILE);
+            let inside = Path::new(file).components().all(|c| matches!(c, Component::Normal(_)));
+            if !inside {
+                return Err(format!("Invalid extends file {} (expected a path inside the repository)", file));
+            }
+            return Ok(Self::Git { url: url.to_string(), file: file.to_string() });
+        }
+
+        if file.is_some() {
+            return Err(format!("Invalid extends {}: #<file> only applies to git URLs", source));
+        }
+        if location.starts_with("https://") || location.starts_with("http://") {
+            return Ok(Self::Url(location.to_string()));
+        }
+        Err(format!("Unsupported extends {} (expected an https:// URL or a git URL)", source))
+    }
+
+    fn fetch(&self) -> Result<String, String> {
+        match self {
+            Self::Url(url) => {
+                let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build();
+                agent
+                    .get(url)
+                    .set("User-Agent", USER_AGENT)
+                    .call()
+                    .map_err(|e| format!("Failed to fetch extended config {}: {}", url, e))?
+                    .into_string()
+                    .map_err(|e| format!("Failed to read extended config {}: {}", url, e))
+            }
+            Self::Git { url, file } => {
+                let temp_dir = tempfile::TempDir::new()
+                    .map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+                let checkout = temp_dir.path().join("repo");
+                git::clone_shallow(url, &checkout, None, None)
+                    .map_err(|e| format!("Failed to fetch extended config {}: {}", url, e))?;
+                fs::read_to_string(checkout.join(file))
+                    .map_err(|e| format!("Failed to read {} from {}: {}", file, url, e))
+            }
+        }
+    }
+}
+
+/// Lowercase hex SHA-256 of a config, as pinned with `extends_sha256`
+pub fn checksum(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Directory of the fetched configs: `$XDG_CACHE_HOME/enveil/extends`, or
+/// `~/.cache/enveil/extends`
+pub fn cache_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home::home_dir().map(|home| home.join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("enveil").join("extends")
+}
+
+/// Content of an extended config. The cached copy is used unless `refresh`
+/// is set or it does not match `sha256`; a fetched config must match it.
+pub fn load(source: &str, sha256: Option<&str>, cache_dir: &Path, refresh: bool) -> Result<String, String> {
+    let parsed = Source::parse(source)?;
+    if matches!(&parsed, Source::Url(url) if url.starts_with("http://")) && sha256.is_none() {
+        return Err(format!("Insecure extends {}: use https:// or pin it with extends_sha256", source));
+    }
+
+    let cached = cache_dir.join(format!("{}.toml", detector::fingerprint(source)));
+    let matches = |content: &str| sha256.is_none_or(|pin| pin.eq_ignore_ascii_case(&checksum(content)));
+    if !refresh {
+        if let Some(content) = fs::read_to_string(&cached).ok().filter(|c| matches(c)) {
+            tracing::debug!(source, cache = %cached.display(), "extended config read from cache");
+            return Ok(content);
+        }
+    }
+
+    let content = parsed.fetch()?;
+    if !matches(&content) {
+        return Err(format!(
+            "Checksum mismatch for extended config {}: expected {}, got {}",
+            source,
+            sha256.unwrap_or_default(),
+            checksum(&content)
+        ));
+    }
+    tracing::info!(source, "extended config fetched");
+
+    let saved = fs::create_dir_all(cache_dir).and_then(|_| fs::write(&cached, &content));
+    if let Err(e) = saved {
+        tracing::warn!(cache = %cached.display(), error = %e, "failed to cache extended config");
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_mock;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(Source::parse("https://example.com/org.toml").unwrap(), Source::Url("https://example.com/org.toml".to_string()));
+        assert_eq!(
+            Source::parse("git@github.com:acme/security.git#enveil/org.toml").unwrap(),
+            Source::Git { url: "git@github.com:acme/security.git".to_string(), file: "enveil/org.toml".to_string() }
+        );
+        assert_eq!(
+            Source::parse("git+https://example.com/acme/security").unwrap(),
+            Source::Git { url: "https://example.com/acme/security".to_string(), file: CONFIG_FILE.to_string() }
+        );
+        assert!(Source::parse("https://example.com/org.git#../../etc/passwd").is_err());
+        assert!(Source::parse("https://example.com/org.toml#x").is_err());
+        assert!(Source::parse("../org.toml").is_err());
+    }
+
+    #[test]
+    fn test_load_url_with_cache_and_pin() {
+        let temp_dir = TempDir::new().unwrap();
+        let body = "exclude = [\"vendor\"]\n";
+        let (addr, _) = http_mock::serve(vec![(200, body.to_string()); 2]);
+        let url = format!("{}/enveil-org.toml", addr);
+
+        assert!(load(&url, None, temp_dir.path(), false).unwrap_err().contains("Insecure extends"));
+        let pin = checksum(body);
+        assert_eq!(load(&url, Some(&pin), temp_dir.path(), false).unwrap(), body);
+        // Served from the cache: the server only answers once more
+        assert_eq!(load(&url, Some(&pin.to_uppercase()), temp_dir.path(), false).unwrap(), body);
+        assert_eq!(load(&url, Some(&pin), temp_dir.path(), true).unwrap(), body);
+
+        // A pin the cache does not match fetches again, and the server is gone
+        let wrong = checksum("other");
+        assert!(load(&url, Some(&wrong), temp_dir.path(), false).is_err());
+    }
+
+    #[test]
+    fn test_load_git() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("security");
+        let git = |args: &[&str]| {
+            assert!(Command::new("git").args(args).current_dir(&repo).output().unwrap().status.success());
+        };
+        fs::create_dir(&repo).unwrap();
+        git(&["init", "-q"]);
+        git(&["config", "user.email", "dev@example.com"]);
+        git(&["config", "user.name", "dev"]);
+        fs::write(repo.join("org.toml"), "[scan]\nfail_on = \"medium\"\n").unwrap();
+        git(&["add", "org.toml"]);
+        git(&["commit", "-q", "-m", "org config"]);
+
+        let cache = temp_dir.path().join("cache");
+        let source = format!("git+file://{}#org.toml", repo.display());
+        assert_eq!(load(&source, None, &cache, false).unwrap(), "[scan]\nfail_on = \"medium\"\n");
+
+        fs::write(repo.join("org.toml"), "[scan]\nfail_on = \"low\"\n").unwrap();
+        git(&["commit", "-q", "-am", "stricter"]);
+        assert!(load(&source, None, &cache, false).unwrap().contains("medium"));
+        assert!(load(&source, None, &cache, true).unwrap().contains("low"));
+
+        let missing = format!("git+file://{}#missing.toml", repo.display());
+        assert!(load(&missing, None, &cache, false).unwrap_err().contains("missing.toml"));
+    }
+}
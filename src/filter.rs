@@ -0,0 +1,158 @@
+use base64::Engine;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::git;
+use crate::restorer::DecryptionKey;
+use crate::stream::{self, FileHeader};
+
+/// Name of the git filter (`filter=enveil` in .gitattributes)
+pub const FILTER_NAME: &str = "enveil";
+
+/// Encrypt a file for the repository, as git's clean filter. The nonce
+/// prefix comes from the keyed checksum of the content, so the same
+/// content always gives the same blob and unchanged files do not show as
+/// modified. Content already encrypted is passed through.
+pub fn clean<R: Read, W: Write>(mut reader: R, mut writer: W, key: &[u8; 32], file_name: &str) -> Result<(), String> {
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+
+    if plaintext.starts_with(stream::MAGIC) {
+        return writer
+            .write_all(&plaintext)
+            .map_err(|e| format!("Failed to write file: {}", e));
+    }
+
+    let checksum = stream::checksum(key, plaintext.as_slice())?;
+    let prefix: Vec<u8> = (0..stream::PREFIX_LEN)
+        .map(|i| u8::from_str_radix(&checksum[i * 2..i * 2 + 2], 16).unwrap_or(0))
+        .collect();
+    let mut header = FileHeader::new(file_name, None, checksum);
+    header.nonce_prefix = base64::engine::general_purpose::STANDARD.encode(prefix);
+
+    stream::encrypt(plaintext.as_slice(), writer, key, &header)
+}
+
+/// Decrypt a file from the repository, as git's smudge filter. Content that
+/// is not encrypted (committed before the filter was set up) is passed through.
+pub fn smudge<R: Read, W: Write>(mut reader: R, mut writer: W, key: &[u8; 32]) -> Result<(), String> {
+    let mut magic = [0u8; 7];
+    let len = stream::read_full(&mut reader, &mut magic).map_err(|e| format!("Failed to read file: {}", e))?;
+
+    if &magic[..len] == stream::MAGIC.as_slice() {
+        return DecryptionKey::Raw(*key).decrypt_stream(magic.as_slice().chain(reader), writer);
+    }
+
+    writer
+        .write_all(&magic[..len])
+        .and_then(|_| std::io::copy(&mut reader, &mut writer).map(|_| ()))
+        .map_err(|e| format!("Failed to write file: {}", e))
+}
+
+/// Set up the filter in a repository: the clean and smudge commands in its
+/// git config, run with `--key-file` when given, and a
+/// `<pattern> filter=enveil` line in .gitattributes for each pattern
+pub fn install(repo_path: &Path, patterns: &[String], key_file: Option<&Path>) -> Result<(), String> {
+    if !git::is_work_tree(repo_path) {
+        return Err(format!("Not a git repository: {}", repo_path.display()));
+    }
+
+    let key_arg = match key_file {
+        Some(path) => {
+            let absolute = fs::canonicalize(path)
+                .map_err(|e| format!("Failed to read key file {}: {}", path.display(), e))?;
+            format!(" --key-file '{}'", absolute.display().to_string().replace('\'', r"'\''"))
+        }
+        None => String::new(),
+    };
+    let section = format!("filter.{}", FILTER_NAME);
+    git::set_config(repo_path, &format!("{}.clean", section), &format!("enveil filter clean{} %f", key_arg))?;
+    git::set_config(repo_path, &format!("{}.smudge", section), &format!("enveil filter smudge{} %f", key_arg))?;
+    // Never commit plaintext because the filter failed
+    git::set_config(repo_path, &format!("{}.required", section), "true")?;
+
+    let attributes = attributes_path(repo_path);
+    let existing = fs::read_to_string(&attributes).unwrap_or_default();
+    let missing: Vec<String> = patterns
+        .iter()
+        .map(|pattern| format!("{} filter={}", pattern, FILTER_NAME))
+        .filter(|line| !existing.lines().any(|l| l.trim() == line))
+        .collect();
+
+    if !missing.is_empty() {
+        let mut content = existing;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&missing.join("\n"));
+        content.push('\n');
+        fs::write(&attributes, content).map_err(|e| format!("Failed to write .gitattributes: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Path of the .gitattributes file routing files to the filter
+pub fn attributes_path(repo_path: &Path) -> PathBuf {
+    repo_path.join(".gitattributes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_clean_is_deterministic() {
+        let key = [7u8; 32];
+        let encrypt = |content: &[u8]| {
+            let mut out = Vec::new();
+            clean(content, &mut out, &key, "secrets.yaml").unwrap();
+            out
+        };
+
+        let blob = encrypt(b"token: abc\n");
+        assert!(blob.starts_with(stream::MAGIC));
+        assert_eq!(encrypt(b"token: abc\n"), blob);
+        assert_ne!(encrypt(b"token: abd\n"), blob);
+        // Cleaning an encrypted blob again changes nothing
+        assert_eq!(encrypt(&blob), blob);
+
+        let mut plaintext = Vec::new();
+        smudge(blob.as_slice(), &mut plaintext, &key).unwrap();
+        assert_eq!(plaintext, b"token: abc\n");
+        assert!(smudge(blob.as_slice(), &mut Vec::new(), &[8u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_smudge_passes_plaintext_through() {
+        let mut out = Vec::new();
+        smudge(b"plain".as_slice(), &mut out, &[7u8; 32]).unwrap();
+        assert_eq!(out, b"plain");
+    }
+
+    #[test]
+    fn test_install() {
+        let temp_dir = TempDir::new().unwrap();
+        Command::new("git").args(["init", "-q"]).current_dir(temp_dir.path()).status().unwrap();
+        let patterns = vec!["secrets/*.yaml".to_string()];
+
+        install(temp_dir.path(), &patterns, None).unwrap();
+        install(temp_dir.path(), &patterns, None).unwrap();
+
+        let attributes = fs::read_to_string(attributes_path(temp_dir.path())).unwrap();
+        assert_eq!(attributes, "secrets/*.yaml filter=enveil\n");
+        let output = Command::new("git")
+            .args(["config", "filter.enveil.clean"])
+            .current_dir(temp_dir.path())
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "enveil filter clean %f");
+
+        assert!(install(&temp_dir.path().join("missing"), &patterns, None).is_err());
+    }
+}
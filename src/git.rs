@@ -0,0 +1,339 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Run a git command in `repo_path` and return its raw stdout
+fn run_git(repo_path: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    run_git_with_env(repo_path, args, &[])
+}
+
+/// Like `run_git`, with extra environment variables
+fn run_git_with_env(repo_path: &Path, args: &[&str], env: &[(&str, &str)]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        // Fail instead of prompting for credentials
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .envs(env.iter().copied())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Like `run_git`, writing `input` to the command's stdin
+fn run_git_with_input(repo_path: &Path, args: &[&str], input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    // Written from another thread so a full stdout pipe cannot block git
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let output = std::thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(input));
+        child.wait_with_output()
+    })
+    .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Split NUL-separated git output into paths
+fn split_paths(output: &[u8]) -> Vec<String> {
+    output
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).to_string())
+        .collect()
+}
+
+/// Clone the latest commit of a repository (of `branch` when given) into
+/// `dest`. `http_header` is sent to HTTPS remotes, e.g. to authenticate.
+pub fn clone_shallow(url: &str, dest: &Path, branch: Option<&str>, http_header: Option<&str>) -> Result<(), String> {
+    let dest = dest.to_string_lossy();
+    let mut args = vec!["clone", "--quiet", "--depth", "1", "--no-tags"];
+    if let Some(branch) = branch {
+        args.extend(["--branch", branch]);
+    }
+    args.extend(["--", url, dest.as_ref()]);
+
+    // Passed through the environment, so the header is not visible in the process list
+    let env = match http_header {
+        Some(header) => vec![
+            ("GIT_CONFIG_COUNT", "1"),
+            ("GIT_CONFIG_KEY_0", "http.extraHeader"),
+            ("GIT_CONFIG_VALUE_0", header),
+        ],
+        None => Vec::new(),
+    };
+    run_git_with_env(Path::new("."), &args, &env).map(|_| ())
+}
+
+/// Whether `dir_path` is inside a git work tree
+pub fn is_work_tree(dir_path: &Path) -> bool {
+    run_git(dir_path, &["rev-parse", "--is-inside-work-tree"])
+        .is_ok_and(|output| output.starts_with(b"true"))
+}
+
+/// Whether git ignores `path`, relative to `dir_path`
+pub fn is_ignored(dir_path: &Path, path: &str) -> bool {
+    // check-ignore exits 1 for paths that are not ignored
+    run_git(dir_path, &["check-ignore", "-q", "--no-index", "--", path]).is_ok()
+}
+
+/// Files added, copied or modified in the index, relative to the repo root
+pub fn staged_files(repo_path: &Path) -> Result<Vec<String>, String> {
+    let output = run_git(
+        repo_path,
+        &["diff", "--cached", "--name-only", "--diff-filter=ACM", "-z"],
+    )?;
+    Ok(split_paths(&output))
+}
+
+/// Files tracked in the index below `dir_path`, relative to it
+pub fn tracked_files(dir_path: &Path) -> Result<Vec<String>, String> {
+    let output = run_git(dir_path, &["ls-files", "-z"])?;
+    Ok(split_paths(&output))
+}
+
+/// Files below `dir_path` present in any commit reachable from a ref,
+/// relative to it (deleted files included)
+pub fn committed_files(dir_path: &Path) -> Result<Vec<String>, String> {
+    let output = run_git(
+        dir_path,
+        &["log", "--all", "--pretty=format:", "--name-only", "--relative", "-z"],
+    )?;
+    Ok(split_paths(&output))
+}
+
+/// Commit where the current branch forked from `rev`
+pub fn merge_base(dir_path: &Path, rev: &str) -> Result<String, String> {
+    let output = run_git(dir_path, &["merge-base", rev, "HEAD"])?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Files below `dir_path` added, copied, modified or renamed in the working
+/// tree since `base`, relative to `dir_path`
+pub fn changed_files(dir_path: &Path, base: &str) -> Result<Vec<String>, String> {
+    let output = run_git(
+        dir_path,
+        &["diff", "--name-only", "--relative", "--diff-filter=ACMR", "-z", base],
+    )?;
+    Ok(split_paths(&output))
+}
+
+/// Content of a file, relative to `dir_path`, at a revision (`None` if it
+/// did not exist there)
+pub fn content_at(dir_path: &Path, rev: &str, file: &str) -> Option<Vec<u8>> {
+    run_git(dir_path, &["show", &format!("{}:./{}", rev, file)]).ok()
+}
+
+/// A file blob in the object database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blob {
+    pub id: String,
+    /// Path of the file the blob was first seen at
+    pub path: String,
+    pub size: u64,
+}
+
+/// Blobs reachable from `rev` but from no ref, e.g. the blobs of a push
+/// being received. Each blob is listed once.
+pub fn new_blobs(repo_path: &Path, rev: &str) -> Result<Vec<Blob>, String> {
+    list_blobs(repo_path, &["rev-list", "--objects", rev, "--not", "--all"])
+}
+
+/// Blobs reachable from any ref: every file version in the history
+pub fn history_blobs(repo_path: &Path) -> Result<Vec<Blob>, String> {
+    list_blobs(repo_path, &["rev-list", "--objects", "--all"])
+}
+
+/// Blobs among the objects listed by a `rev-list --objects` command
+fn list_blobs(repo_path: &Path, rev_list: &[&str]) -> Result<Vec<Blob>, String> {
+    let objects = run_git(repo_path, rev_list)?;
+    let objects = String::from_utf8_lossy(&objects);
+
+    // Commits are listed without a path, trees and blobs with one
+    let paths: Vec<(&str, &str)> = objects
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .collect();
+    let ids: String = paths.iter().map(|(id, _)| format!("{}\n", id)).collect();
+    let types = run_git_with_input(
+        repo_path,
+        &["cat-file", "--batch-check=%(objecttype) %(objectsize)"],
+        ids.as_bytes(),
+    )?;
+
+    let blobs = paths
+        .iter()
+        .zip(String::from_utf8_lossy(&types).lines())
+        .filter_map(|((id, path), info)| {
+            let size = info.strip_prefix("blob ")?.parse().ok()?;
+            Some(Blob {
+                id: id.to_string(),
+                path: path.to_string(),
+                size,
+            })
+        })
+        .collect();
+    Ok(blobs)
+}
+
+/// Content of a blob
+pub fn blob_content(repo_path: &Path, id: &str) -> Result<Vec<u8>, String> {
+    run_git(repo_path, &["cat-file", "blob", id])
+}
+
+/// Commits of any ref that add or remove an object
+pub fn commits_with_object(repo_path: &Path, id: &str) -> Result<Vec<String>, String> {
+    let output = run_git(repo_path, &["log", "--all", "--format=%H", &format!("--find-object={}", id)])?;
+    Ok(String::from_utf8_lossy(&output).lines().map(str::to_string).collect())
+}
+
+/// Absolute path of the `.git` directory of a repository
+pub fn git_dir(repo_path: &Path) -> Result<PathBuf, String> {
+    let output = run_git(repo_path, &["rev-parse", "--absolute-git-dir"])?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&output).trim()))
+}
+
+/// Git identity of the user, `Name <email>`
+pub fn user_identity(repo_path: &Path) -> Option<String> {
+    let config = |key: &str| {
+        run_git(repo_path, &["config", key])
+            .ok()
+            .map(|value| String::from_utf8_lossy(&value).trim().to_string())
+    };
+    match (config("user.name"), config("user.email")) {
+        (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+        (Some(name), None) => Some(name),
+        (None, Some(email)) => Some(format!("<{}>", email)),
+        (None, None) => None,
+    }
+}
+
+/// Stage a file, e.g. from a pre-commit hook so it joins the commit
+pub fn stage(repo_path: &Path, file: &str) -> Result<(), String> {
+    run_git(repo_path, &["add", "--", file]).map(|_| ())
+}
+
+/// Set a value in the repository's git config
+pub fn set_config(repo_path: &Path, key: &str, value: &str) -> Result<(), String> {
+    run_git(repo_path, &["config", key, value]).map(|_| ())
+}
+
+/// Content of a file as staged in the index
+pub fn staged_content(repo_path: &Path, file: &str) -> Result<Vec<u8>, String> {
+    run_git(repo_path, &["show", &format!(":{}", file)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_staged_content_reads_index_not_worktree() {
+        let temp_dir = TempDir::new().unwrap();
+        run_git(temp_dir.path(), &["init", "-q"]).unwrap();
+        fs::write(temp_dir.path().join("app.txt"), "staged\n").unwrap();
+        run_git(temp_dir.path(), &["add", "app.txt"]).unwrap();
+        fs::write(temp_dir.path().join("app.txt"), "modified\n").unwrap();
+
+        assert_eq!(staged_files(temp_dir.path()).unwrap(), vec!["app.txt"]);
+        assert_eq!(staged_content(temp_dir.path(), "app.txt").unwrap(), b"staged\n");
+    }
+
+    #[test]
+    fn test_tracked_files_are_relative_to_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        run_git(temp_dir.path(), &["init", "-q"]).unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/app.txt"), "tracked\n").unwrap();
+        fs::write(temp_dir.path().join("src/build.log"), "untracked\n").unwrap();
+        fs::write(temp_dir.path().join("root.txt"), "tracked\n").unwrap();
+        run_git(temp_dir.path(), &["add", "src/app.txt", "root.txt"]).unwrap();
+
+        assert_eq!(tracked_files(temp_dir.path()).unwrap(), vec!["root.txt", "src/app.txt"]);
+        assert_eq!(tracked_files(&temp_dir.path().join("src")).unwrap(), vec!["app.txt"]);
+    }
+
+    #[test]
+    fn test_changed_files_since_merge_base() {
+        let temp_dir = TempDir::new().unwrap();
+        let git = |args: &[&str]| run_git(temp_dir.path(), args).unwrap();
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.email", "dev@example.com"]);
+        git(&["config", "user.name", "dev"]);
+        fs::write(temp_dir.path().join("old.txt"), "v1\n").unwrap();
+        fs::write(temp_dir.path().join("same.txt"), "v1\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "base"]);
+
+        git(&["checkout", "-q", "-b", "feature"]);
+        fs::write(temp_dir.path().join("old.txt"), "v2\n").unwrap();
+        fs::write(temp_dir.path().join("new.txt"), "v1\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "change"]);
+
+        let base = merge_base(temp_dir.path(), "main").unwrap();
+        assert_eq!(changed_files(temp_dir.path(), &base).unwrap(), vec!["new.txt", "old.txt"]);
+        assert_eq!(content_at(temp_dir.path(), &base, "old.txt").unwrap(), b"v1\n");
+        assert!(content_at(temp_dir.path(), &base, "new.txt").is_none());
+        assert!(merge_base(temp_dir.path(), "missing").is_err());
+    }
+
+    #[test]
+    fn test_new_blobs_excludes_blobs_reachable_from_refs() {
+        let temp_dir = TempDir::new().unwrap();
+        let git = |args: &[&str]| run_git(temp_dir.path(), args).unwrap();
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.email", "dev@example.com"]);
+        git(&["config", "user.name", "dev"]);
+        fs::write(temp_dir.path().join("old.txt"), "v1\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "base"]);
+        
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/new file.txt"), "new\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "change"]);
+        // An unreferenced commit, as during a push before the refs are updated
+        let pushed = String::from_utf8(git(&["rev-parse", "HEAD"])).unwrap();
+        git(&["reset", "-q", "--hard", "HEAD~1"]);
+        
+        let blobs = new_blobs(temp_dir.path(), pushed.trim()).unwrap();
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].path, "src/new file.txt");
+        assert_eq!(blobs[0].size, 4);
+        assert_eq!(blob_content(temp_dir.path(), &blobs[0].id).unwrap(), b"new\n");
+    }
+
+    #[test]
+    fn test_not_a_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(staged_files(temp_dir.path()).is_err());
+    }
+}
@@ -1,28 +1,61 @@
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::str::FromStr;
+
+use crate::emit;
+
+/// Suffix of a hook Enveil replaced and chains to
+const BACKUP_SUFFIX: &str = ".pre-enveil";
 
 /// Git hooks manager for Enveil
 pub struct GitHooks {
-    project_path: PathBuf,
+    git_dir: PathBuf,
     hooks_dir: PathBuf,
+    bare: bool,
+    allow_bypass: bool,
 }
 
 impl GitHooks {
     pub fn new<P: AsRef<Path>>(project_path: P) -> Self {
-        let project_path = project_path.as_ref().to_path_buf();
-        let hooks_dir = project_path.join(".git").join("hooks");
+        let git_dir = project_path.as_ref().join(".git");
+        let hooks_dir = git_dir.join("hooks");
+        
+        Self {
+            git_dir,
+            hooks_dir,
+            bare: false,
+            allow_bypass: false,
+        }
+    }
+    
+    /// Hooks of a bare repository, as hosted by a git server
+    pub fn bare<P: AsRef<Path>>(repo_path: P) -> Self {
+        let git_dir = repo_path.as_ref().to_path_buf();
+        let hooks_dir = git_dir.join("hooks");
         
         Self {
-            project_path,
+            git_dir,
             hooks_dir,
+            bare: true,
+            allow_bypass: false,
         }
     }
     
-    /// Install git hooks for the project
-    pub fn install(&self, force: bool) -> Result<(), String> {
+    /// Let `ENVEIL_SKIP=1` bypass the installed hooks, recording each bypass
+    /// in the audit log
+    pub fn allow_bypass(mut self, allow_bypass: bool) -> Self {
+        self.allow_bypass = allow_bypass;
+        self
+    }
+    
+    /// Install the given git hooks for the project
+    pub fn install(&self, hooks: &[Hook], force: bool) -> Result<(), String> {
+        if self.bare && !self.git_dir.join("objects").is_dir() {
+            return Err(format!("{} is not a bare git repository", self.git_dir.display()));
+        }
+        
         // Check if .git directory exists
-        if !self.project_path.join(".git").exists() {
+        if !self.git_dir.exists() {
             return Err("Not a git repository. Initialize with 'git init' first.".to_string());
         }
         
@@ -32,317 +65,542 @@ impl GitHooks {
                 .map_err(|e| format!("Failed to create hooks directory: {}", e))?;
         }
         
-        // Create pre-commit hook
-        self.create_pre_commit_hook(force)?;
-        
-        // Create pre-push hook
-        self.create_pre_push_hook(force)?;
+        for hook in hooks {
+            self.create_hook(*hook, force)?;
+        }
         
-        println!("✅ Git hooks installed successfully!");
-        println!("   - Pre-commit hook: {}", self.hooks_dir.join("pre-commit").display());
-        println!("   - Pre-push hook: {}", self.hooks_dir.join("pre-push").display());
+        emit!(stdout, "✅ Git hooks installed successfully!");
+        for hook in hooks {
+            emit!(stdout, "   - {} hook: {}", hook.name(), self.hook_path(*hook).display());
+        }
         
         Ok(())
     }
     
-    /// Uninstall git hooks
-    pub fn uninstall(&self) -> Result<(), String> {
-        let pre_commit = self.hooks_dir.join("pre-commit");
-        let pre_push = self.hooks_dir.join("pre-push");
-        
+    /// Remove the given hooks, leaving hooks Enveil did not install alone
+    pub fn uninstall(&self, hooks: &[Hook]) -> Result<(), String> {
         let mut removed = 0;
         
-        if pre_commit.exists() {
-            if let Some(content) = fs::read_to_string(&pre_commit).ok() {
-                if content.contains("enveil") {
-                    fs::remove_file(&pre_commit)
-                        .map_err(|e| format!("Failed to remove pre-commit: {}", e))?;
-                    removed += 1;
-                }
-            }
-        }
-        
-        if pre_push.exists() {
-            if let Some(content) = fs::read_to_string(&pre_push).ok() {
-                if content.contains("enveil") {
-                    fs::remove_file(&pre_push)
-                        .map_err(|e| format!("Failed to remove pre-push: {}", e))?;
-                    removed += 1;
+        for hook in hooks {
+            if self.is_installed(*hook) {
+                fs::remove_file(self.hook_path(*hook))
+                    .map_err(|e| format!("Failed to remove {}: {}", hook.name(), e))?;
+                removed += 1;
+                
+                // Put back the hook Enveil chained to
+                if self.backup_path(*hook).exists() {
+                    fs::rename(self.backup_path(*hook), self.hook_path(*hook))
+                        .map_err(|e| format!("Failed to restore {}: {}", hook.name(), e))?;
+                    emit!(stdout, "🔗 Restored the previous {} hook", hook.name());
                 }
             }
         }
         
         if removed > 0 {
-            println!("✅ Removed {} hook(s)", removed);
+            emit!(stdout, "✅ Removed {} hook(s)", removed);
         } else {
-            println!("ℹ️  No Enveil hooks found to remove");
+            emit!(stdout, "ℹ️  No Enveil hooks found to remove");
         }
         
         Ok(())
     }
     
-    /// Check if hooks are installed
-    pub fn is_installed(&self) -> bool {
-        let pre_commit = self.hooks_dir.join("pre-commit");
-        let pre_push = self.hooks_dir.join("pre-push");
-        
-        let pre_commit_ok = pre_commit.exists() && 
-            fs::read_to_string(&pre_commit).map(|c| c.contains("enveil")).unwrap_or(false);
-        let pre_push_ok = pre_push.exists() && 
-            fs::read_to_string(&pre_push).map(|c| c.contains("enveil")).unwrap_or(false);
-        
-        pre_commit_ok || pre_push_ok
+    /// Check if an Enveil hook is installed
+    pub fn is_installed(&self, hook: Hook) -> bool {
+        fs::read_to_string(self.hook_path(hook))
+            .map(|content| content.contains("enveil"))
+            .unwrap_or(false)
+    }
+    
+    fn hook_path(&self, hook: Hook) -> PathBuf {
+        self.hooks_dir.join(hook.name())
     }
     
-    /// Create pre-commit hook
-    fn create_pre_commit_hook(&self, force: bool) -> Result<(), String> {
-        let hook_path = self.hooks_dir.join("pre-commit");
+    /// Where a hook Enveil replaced is kept, to be run first
+    fn backup_path(&self, hook: Hook) -> PathBuf {
+        self.hooks_dir.join(format!("{}{}", hook.name(), BACKUP_SUFFIX))
+    }
+    
+    /// Write the script of a hook, replacing hooks from older Enveil versions.
+    /// Another existing hook is kept and chained, or overwritten with `force`.
+    fn create_hook(&self, hook: Hook, force: bool) -> Result<(), String> {
+        let hook_path = self.hook_path(hook);
+        let backup_path = self.backup_path(hook);
         
-        // Check if hook already exists
         if hook_path.exists() && !force {
             let content = fs::read_to_string(&hook_path)
                 .map_err(|e| format!("Failed to read hook: {}", e))?;
             
-            if content.contains("enveil") {
-                println!("ℹ️  Pre-commit hook already installed");
+            if content == hook.script(backup_path.exists(), self.allow_bypass) {
+                emit!(stdout, "ℹ️  {} hook already installed", hook.name());
                 return Ok(());
             }
             
-            return Err("Pre-commit hook already exists. Use --force to overwrite.".to_string());
+            if !content.contains("enveil") {
+                if backup_path.exists() {
+                    return Err(format!(
+                        "{} hook already exists and {} is taken. Use --force to overwrite.",
+                        hook.name(),
+                        backup_path.display()
+                    ));
+                }
+                fs::rename(&hook_path, &backup_path)
+                    .map_err(|e| format!("Failed to keep the existing {} hook: {}", hook.name(), e))?;
+                emit!(stdout, "🔗 Existing {} hook kept as {} and run first", hook.name(), backup_path.display());
+            }
         }
         
-        let hook_content = self.generate_pre_commit_hook();
-        
-        fs::write(&hook_path, hook_content)
+        let script = hook.script(backup_path.exists(), self.allow_bypass);
+        fs::write(&hook_path, script)
             .map_err(|e| format!("Failed to write hook: {}", e))?;
         
-        // Make executable
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&hook_path)
-                .map_err(|e| format!("Failed to get permissions: {}", e))?
-                .permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&hook_path, perms)
-                .map_err(|e| format!("Failed to set permissions: {}", e))?;
+        make_executable(&hook_path)
+    }
+}
+
+/// Give a hook script the executable bit git requires
+fn make_executable(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(path)
+            .map_err(|e| format!("Failed to get permissions: {}", e))?
+            .permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(path, perms)
+            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    
+    Ok(())
+}
+
+/// Git hooks Enveil installs. The hook scripts only call `enveil hook run`,
+/// which does the scanning, so they work wherever git runs hooks (Git for
+/// Windows runs them with its bundled sh).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    /// Scans the staged content before a commit
+    PreCommit,
+    /// Scans the project before a push
+    PrePush,
+    /// Scans the commit message
+    CommitMsg,
+    /// Scans the blobs of a push on the server, for bare repositories
+    PreReceive,
+}
+
+impl Hook {
+    /// Every hook Enveil can manage in a working copy
+    pub const ALL: [Hook; 3] = [Hook::PreCommit, Hook::PrePush, Hook::CommitMsg];
+    
+    /// Hooks installed when none are chosen
+    pub const DEFAULT: [Hook; 2] = [Hook::PreCommit, Hook::PrePush];
+    
+    /// Hooks of bare repositories on a git server
+    pub const SERVER: [Hook; 1] = [Hook::PreReceive];
+    
+
+    /// File name of the hook in `.git/hooks`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::PreCommit => "pre-commit",
+            Hook::PrePush => "pre-push",
+            Hook::CommitMsg => "commit-msg",
+            Hook::PreReceive => "pre-receive",
         }
-        
-        Ok(())
     }
     
-    /// Create pre-push hook
-    fn create_pre_push_hook(&self, force: bool) -> Result<(), String> {
-        let hook_path = self.hooks_dir.join("pre-push");
-        
-        // Check if hook already exists
-        if hook_path.exists() && !force {
-            let content = fs::read_to_string(&hook_path)
-                .map_err(|e| format!("Failed to read hook: {}", e))?;
-            
-            if content.contains("enveil") {
-                println!("ℹ️  Pre-push hook already installed");
-                return Ok(());
-            }
-            
-            return Err("Pre-push hook already exists. Use --force to overwrite.".to_string());
+    /// What the hook scans, for messages
+    pub fn target(&self) -> &'static str {
+        match self {
+            Hook::PreCommit => "staged files",
+            Hook::PrePush => "project",
+            Hook::CommitMsg => "commit message",
+            Hook::PreReceive => "pushed commits",
         }
-        
-        let hook_content = self.generate_pre_push_hook();
-        
-        fs::write(&hook_path, hook_content)
-            .map_err(|e| format!("Failed to write hook: {}", e))?;
-        
-        // Make executable
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&hook_path)
-                .map_err(|e| format!("Failed to get permissions: {}", e))?
-                .permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&hook_path, perms)
-                .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+    
+    /// Git operation the hook can abort, for messages
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Hook::PreCommit => "commit",
+            Hook::PrePush => "push",
+            Hook::CommitMsg => "commit",
+            Hook::PreReceive => "push",
         }
-        
-        Ok(())
     }
     
-    /// Generate pre-commit hook script
-    fn generate_pre_commit_hook(&self) -> String {
-        let project_path = self.project_path.display().to_string();
+    /// Whether git writes the hook's input to its stdin
+    fn reads_stdin(&self) -> bool {
+        matches!(self, Hook::PrePush | Hook::PreReceive)
+    }
+    
+    /// Content of the hook script. A `chained` script first runs the hook
+    /// it replaced, kept next to it, and stops if that one fails.
+    pub fn script(&self, chained: bool, allow_bypass: bool) -> String {
+        let name = if allow_bypass {
+            format!("--allow-bypass {}", self.name())
+        } else {
+            self.name().to_string()
+        };
+        let mut script = format!("#!/bin/sh\n# Enveil {} hook, installed by `enveil install`\n", self.name());
         
-        format!(r#"#!/bin/bash
-# Enveil pre-commit hook
-# Scans staged files for secrets before commit
-
-# Check if enfveil binary exists
-if ! command -v enfveil &> /dev/null; then
-    # Try to find it in common locations
-    if [ -f "./target/release/enveil" ]; then
-        ENVEIL="./target/release/enveil"
-    elif [ -f "./target/debug/enveil" ]; then
-        ENVEIL="./target/debug/enveil"
-    else
-        echo "⚠️  Enveil not found. Skipping secret scan."
-        exit 0
-    fi
-else
-    ENVEIL="enveil"
-fi
-
-# Check for --force flag
-for arg in "$@"; do
-    if [ "$arg" = "--force" ] || [ "$arg" = "-n" ]; then
-        echo "ℹ️  Skipping Enveil scan (--force or dry-run detected)"
-        exit 0
-    fi
-done
-
-# Get staged files
-STAGED_FILES=$(git diff --cached --name-only --diff-filter=ACM)
-
-if [ -z "$STAGED_FILES" ]; then
-    echo "ℹ️  No staged files to scan"
-    exit 0
-fi
-
-echo "🔍 Scanning staged files for secrets..."
-
-# Create temp file for scanning
-TEMP_DIR=$(mktemp -d)
-trap "rm -rf $TEMP_DIR" EXIT
-
-# Copy staged files to temp directory
-echo "$STAGED_FILES" | while read file; do
-    if [ -f "$file" ]; then
-        mkdir -p "$(dirname "$TEMP_DIR/$file")"
-        git show ":$file" > "$TEMP_DIR/$file" 2>/dev/null
-    fi
-done
+        if !chained {
+            script.push_str(&format!("exec enveil hook run {} \"$@\"\n", name));
+            return script;
+        }
+        
+        script.push_str(&format!("# Runs the hook it replaced first, kept as {}{}\n", self.name(), BACKUP_SUFFIX));
+        script.push_str(&format!("previous=\"$(dirname \"$0\")/{}{}\"\n", self.name(), BACKUP_SUFFIX));
+        if self.reads_stdin() {
+            // Both hooks need the refs git writes to stdin
+            script.push_str("input=$(cat)\n");
+            script.push_str("if [ -x \"$previous\" ]; then\n");
+            script.push_str("    printf '%s\\n' \"$input\" | \"$previous\" \"$@\" || exit $?\n");
+            script.push_str("fi\n");
+            script.push_str(&format!("printf '%s\\n' \"$input\" | enveil hook run {} \"$@\"\n", name));
+        } else {
+            script.push_str("if [ -x \"$previous\" ]; then\n");
+            script.push_str("    \"$previous\" \"$@\" || exit $?\n");
+            script.push_str("fi\n");
+            script.push_str(&format!("exec enveil hook run {} \"$@\"\n", name));
+        }
+        script
+    }
+}
 
-# Scan for secrets
-SCAN_RESULT=$("$ENVEIL" scan "$TEMP_DIR" 2>&1)
-SCAN_EXIT=$?
+impl FromStr for Hook {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pre-commit" => Ok(Hook::PreCommit),
+            "pre-push" => Ok(Hook::PrePush),
+            "commit-msg" => Ok(Hook::CommitMsg),
+            "pre-receive" => Ok(Hook::PreReceive),
+            _ => Err(format!("Unknown hook: {} (expected pre-commit, pre-push, commit-msg or pre-receive)", s)),
+        }
+    }
+}
 
-if [ $SCAN_EXIT -ne 0 ]; then
-    echo "❌ Error running Enveil scan"
-    echo "$SCAN_RESULT"
-    exit 1
-fi
+/// Hook managers Enveil registers with instead of writing `.git/hooks`,
+/// leaving the team's other hooks in place
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Framework {
+    /// Adds a line to the `.husky/<hook>` scripts
+    Husky,
+    /// Adds a local repo block to `.pre-commit-config.yaml`
+    PreCommit,
+}
 
-# Check if secrets were found
-if echo "$SCAN_RESULT" | grep -q "secrets found"; then
-    COUNT=$(echo "$SCAN_RESULT" | grep -oP '\d+(?= secrets found)' || echo "0")
-    if [ "$COUNT" -gt 0 ]; then
-        echo "❌ ABORTING COMMIT: $COUNT secret(s) detected in staged files!"
-        echo ""
-        echo "$SCAN_RESULT"
-        echo ""
-        echo "To commit anyway, use: git commit --no-verify"
-        echo "Or fix the secrets and commit again"
-        exit 1
-    fi
-fi
+/// Markers around the block Enveil manages in `.pre-commit-config.yaml`
+const PRE_COMMIT_BEGIN: &str = "# enveil:begin (managed by `enveil install --framework pre-commit`)";
+const PRE_COMMIT_END: &str = "# enveil:end";
 
-echo "✅ No secrets detected in staged files"
-exit 0
-"#
-        )
+impl Framework {
+    /// Register the hooks of a project with the framework
+    pub fn install(&self, project_path: &Path, hooks: &[Hook]) -> Result<(), String> {
+        if hooks.contains(&Hook::PreReceive) {
+            return Err("pre-receive is a server hook, install it with --server".to_string());
+        }
+        
+        match self {
+            Framework::Husky => {
+                let husky_dir = project_path.join(".husky");
+                if !husky_dir.is_dir() {
+                    return Err("Husky is not set up (no .husky directory). Run `npx husky init` first.".to_string());
+                }
+                
+                for hook in hooks {
+                    let path = husky_dir.join(hook.name());
+                    let mut content = fs::read_to_string(&path).unwrap_or_default();
+                    if content.lines().any(|line| line.trim() == husky_line(*hook)) {
+                        emit!(stdout, "ℹ️  .husky/{} already runs enveil", hook.name());
+                        continue;
+                    }
+                    
+                    if !content.is_empty() && !content.ends_with('\n') {
+                        content.push('\n');
+                    }
+                    content.push_str(&husky_line(*hook));
+                    content.push('\n');
+                    fs::write(&path, content)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    make_executable(&path)?;
+                    emit!(stdout, "✅ Added enveil to .husky/{}", hook.name());
+                }
+            }
+            Framework::PreCommit => {
+                let mut installed: Vec<Hook> = Hook::ALL
+                    .into_iter()
+                    .filter(|hook| self.is_installed(project_path, *hook))
+                    .collect();
+                let missing: Vec<Hook> = hooks.iter().copied().filter(|hook| !installed.contains(hook)).collect();
+                if missing.is_empty() {
+                    emit!(stdout, "ℹ️  .pre-commit-config.yaml already runs enveil");
+                    return Ok(());
+                }
+                
+                installed.extend(missing);
+                write_pre_commit_block(project_path, &installed)?;
+                emit!(stdout, "✅ Added enveil to .pre-commit-config.yaml");
+                emit!(stdout, "   Run `pre-commit install` (with --hook-type for pre-push or commit-msg) to activate it");
+            }
+        }
+        Ok(())
     }
     
-    /// Generate pre-push hook script
-    fn generate_pre_push_hook(&self) -> String {
-        let project_path = self.project_path.display().to_string();
+    /// Remove the hooks Enveil registered, leaving the rest of the config alone
+    pub fn uninstall(&self, project_path: &Path, hooks: &[Hook]) -> Result<(), String> {
+        let installed: Vec<Hook> = Hook::ALL
+            .into_iter()
+            .filter(|hook| self.is_installed(project_path, *hook))
+            .collect();
+        let removed = installed.iter().filter(|hook| hooks.contains(hook)).count();
         
-        format!(r#"#!/bin/bash
-# Enveil pre-push hook
-# Scans all files for secrets before push
-
-# Get remote and URL
-REMOTE="$1"
-URL="$2"
-
-# Check if enfveil binary exists
-if ! command -v enfveil &> /dev/null; then
-    # Try to find it in common locations
-    if [ -f "./target/release/enveil" ]; then
-        ENVEIL="./target/release/enveil"
-    elif [ -f "./target/debug/enveil" ]; then
-        ENVEIL="./target/debug/enveil"
-    else
-        echo "⚠️  Enveil not found. Skipping secret scan."
-        exit 0
-    fi
-else
-    ENVEIL="enveil"
-fi
-
-# Check for --force flag
-for arg in "$@"; do
-    if [ "$arg" = "--force" ]; then
-        echo "ℹ️  Skipping Enveil scan (--force detected)"
-        exit 0
-    fi
-done
-
-echo "🔍 Scanning entire project for secrets..."
-
-# Scan for secrets
-SCAN_RESULT=$("$ENVEIL" scan . 2>&1)
-SCAN_EXIT=$?
-
-if [ $SCAN_EXIT -ne 0 ]; then
-    echo "❌ Error running Enveil scan"
-    echo "$SCAN_RESULT"
-    exit 1
-fi
-
-# Check if secrets were found
-if echo "$SCAN_RESULT" | grep -q "secrets found"; then
-    COUNT=$(echo "$SCAN_RESULT" | grep -oP '\d+(?= secrets found)' || echo "0")
-    if [ "$COUNT" -gt 0 ]; then
-        echo "❌ ABORTING PUSH: $COUNT secret(s) detected in project!"
-        echo ""
-        echo "$SCAN_RESULT"
-        echo ""
-        echo "To push anyway, use: git push --no-verify"
-        echo "Or fix the secrets and push again"
-        exit 1
-    fi
-fi
+        match self {
+            Framework::Husky => {
+                for hook in installed.iter().filter(|hook| hooks.contains(hook)) {
+                    let path = project_path.join(".husky").join(hook.name());
+                    let content = fs::read_to_string(&path)
+                        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                    let kept: String = content
+                        .lines()
+                        .filter(|line| line.trim() != husky_line(*hook))
+                        .map(|line| format!("{}\n", line))
+                        .collect();
+                    fs::write(&path, kept)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                }
+            }
+            Framework::PreCommit => {
+                let kept: Vec<Hook> = installed.into_iter().filter(|hook| !hooks.contains(hook)).collect();
+                write_pre_commit_block(project_path, &kept)?;
+            }
+        }
+        
+        if removed > 0 {
+            emit!(stdout, "✅ Removed {} hook(s)", removed);
+        } else {
+            emit!(stdout, "ℹ️  No Enveil hooks found to remove");
+        }
+        Ok(())
+    }
+    
+    /// Check if the framework runs an Enveil hook
+    pub fn is_installed(&self, project_path: &Path, hook: Hook) -> bool {
+        match self {
+            Framework::Husky => fs::read_to_string(project_path.join(".husky").join(hook.name()))
+                .map(|content| content.lines().any(|line| line.trim() == husky_line(hook)))
+                .unwrap_or(false),
+            Framework::PreCommit => fs::read_to_string(project_path.join(".pre-commit-config.yaml"))
+                .map(|content| content.contains(&format!("entry: enveil hook run {}\n", hook.name())))
+                .unwrap_or(false),
+        }
+    }
+}
 
-echo "✅ No secrets detected in project"
-exit 0
-"#
-        )
+impl FromStr for Framework {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "husky" => Ok(Framework::Husky),
+            "pre-commit" => Ok(Framework::PreCommit),
+            _ => Err(format!("Unknown hook framework: {} (expected husky or pre-commit)", s)),
+        }
     }
 }
 
-/// Check if current directory is a git repository
-pub fn is_git_repo() -> bool {
-    Path::new(".git").exists()
+/// Line running a hook from a husky script, which gets git's arguments
+fn husky_line(hook: Hook) -> String {
+    format!("enveil hook run {} \"$@\"", hook.name())
 }
 
-/// Run git hook manually (for testing)
-pub fn run_hook(hook_type: &str, force: bool) -> Result<(), String> {
-    if !is_git_repo() {
-        return Err("Not a git repository".to_string());
+/// Replace the Enveil block of `.pre-commit-config.yaml` with one running
+/// `hooks`, or drop it when there are none. The block goes first in `repos:`.
+fn write_pre_commit_block(project_path: &Path, hooks: &[Hook]) -> Result<(), String> {
+    let path = project_path.join(".pre-commit-config.yaml");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) if hooks.is_empty() => return Ok(()),
+        Err(_) => "repos:\n".to_string(),
+    };
+    
+    // Drop the current block
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        match line.trim() {
+            PRE_COMMIT_BEGIN => in_block = true,
+            PRE_COMMIT_END => in_block = false,
+            _ if !in_block => lines.push(line),
+            _ => {}
+        }
     }
     
-    let hooks = GitHooks::new(".");
+    let repos = match lines.iter().position(|line| line.trim_end() == "repos:" || line.trim_end() == "repos: []") {
+        Some(repos) => repos,
+        None => return Err(format!("No top-level repos: list in {}", path.display())),
+    };
+    // Indent like the existing entries of the list
+    let indent = lines[repos + 1..]
+        .iter()
+        .find(|line| line.trim_start().starts_with("- "))
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .unwrap_or("  ")
+        .to_string();
     
-    match hook_type {
-        "install" => hooks.install(force),
-        "uninstall" => hooks.uninstall(),
-        "status" => {
-            if hooks.is_installed() {
-                println!("✅ Git hooks are installed");
-            } else {
-                println!("ℹ️  Git hooks are not installed");
-            }
-            Ok(())
+    let mut block = Vec::new();
+    if !hooks.is_empty() {
+        block.push(format!("{}{}", indent, PRE_COMMIT_BEGIN));
+        block.push(format!("{}- repo: local", indent));
+        block.push(format!("{}  hooks:", indent));
+        for hook in hooks {
+            block.push(format!("{}    - id: enveil-{}", indent, hook.name()));
+            block.push(format!("{}      name: enveil ({})", indent, hook.name()));
+            block.push(format!("{}      entry: enveil hook run {}", indent, hook.name()));
+            block.push(format!("{}      language: system", indent));
+            // commit-msg gets the message file
+            block.push(format!("{}      pass_filenames: {}", indent, *hook == Hook::CommitMsg));
+            block.push(format!("{}      always_run: true", indent));
+            block.push(format!("{}      stages: [{}]", indent, hook.name()));
         }
-        _ => Err(format!("Unknown hook command: {}", hook_type)),
+        block.push(format!("{}{}", indent, PRE_COMMIT_END));
+    }
+    
+    let mut out: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    out[repos] = "repos:".to_string();
+    out.splice(repos + 1..repos + 1, block);
+    fs::write(&path, out.join("\n") + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_install_writes_thin_hooks() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join(".git")).unwrap();
+        let hooks = GitHooks::new(temp_dir.path());
+        
+        hooks.install(&Hook::DEFAULT, false).unwrap();
+        let script = fs::read_to_string(temp_dir.path().join(".git/hooks/pre-commit")).unwrap();
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("exec enveil hook run pre-commit \"$@\""));
+        assert!(!script.contains("enfveil"));
+        assert!(hooks.is_installed(Hook::PrePush));
+        assert!(!hooks.is_installed(Hook::CommitMsg));
+        
+        hooks.uninstall(&[Hook::PreCommit]).unwrap();
+        assert!(!hooks.is_installed(Hook::PreCommit));
+        assert!(hooks.is_installed(Hook::PrePush));
+    }
+    
+    #[test]
+    fn test_install_upgrades_enveil_hooks_and_chains_others() {
+        let temp_dir = TempDir::new().unwrap();
+        let hooks_dir = temp_dir.path().join(".git/hooks");
+        fs::create_dir_all(&hooks_dir).unwrap();
+        fs::write(hooks_dir.join("pre-commit"), "#!/bin/bash\n# Enveil pre-commit hook\nenveil scan --staged\n").unwrap();
+        fs::write(hooks_dir.join("pre-push"), "#!/bin/sh\nmake test\n").unwrap();
+        
+        let hooks = GitHooks::new(temp_dir.path());
+        hooks.install(&Hook::DEFAULT, false).unwrap();
+        assert_eq!(fs::read_to_string(hooks_dir.join("pre-commit")).unwrap(), Hook::PreCommit.script(false, false));
+        assert_eq!(fs::read_to_string(hooks_dir.join("pre-push")).unwrap(), Hook::PrePush.script(true, false));
+        assert_eq!(fs::read_to_string(hooks_dir.join("pre-push.pre-enveil")).unwrap(), "#!/bin/sh\nmake test\n");
+        
+        // Installing again keeps the chain
+        hooks.install(&Hook::DEFAULT, false).unwrap();
+        assert_eq!(fs::read_to_string(hooks_dir.join("pre-push")).unwrap(), Hook::PrePush.script(true, false));
+        
+        hooks.uninstall(&Hook::ALL).unwrap();
+        assert!(!hooks_dir.join("pre-commit").exists());
+        assert_eq!(fs::read_to_string(hooks_dir.join("pre-push")).unwrap(), "#!/bin/sh\nmake test\n");
+        assert!(!hooks_dir.join("pre-push.pre-enveil").exists());
+    }
+    
+    #[test]
+    fn test_force_overwrites_without_chaining() {
+        let temp_dir = TempDir::new().unwrap();
+        let hooks_dir = temp_dir.path().join(".git/hooks");
+        fs::create_dir_all(&hooks_dir).unwrap();
+        fs::write(hooks_dir.join("pre-commit"), "#!/bin/sh\nmake lint\n").unwrap();
+        
+        GitHooks::new(temp_dir.path()).install(&[Hook::PreCommit], true).unwrap();
+        assert_eq!(fs::read_to_string(hooks_dir.join("pre-commit")).unwrap(), Hook::PreCommit.script(false, false));
+        assert!(!hooks_dir.join("pre-commit.pre-enveil").exists());
+    }
+    
+    #[test]
+    fn test_install_server_hook_in_bare_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        let hooks = GitHooks::bare(temp_dir.path());
+        assert!(hooks.install(&Hook::SERVER, false).unwrap_err().contains("not a bare git repository"));
+        
+        fs::create_dir(temp_dir.path().join("objects")).unwrap();
+        hooks.install(&Hook::SERVER, false).unwrap();
+        let script = fs::read_to_string(temp_dir.path().join("hooks/pre-receive")).unwrap();
+        assert!(script.contains("exec enveil hook run pre-receive"));
+        assert!(hooks.is_installed(Hook::PreReceive));
+    }
+    
+    #[test]
+    fn test_husky_line_is_added_once_and_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(Framework::Husky.install(temp_dir.path(), &[Hook::PreCommit]).unwrap_err().contains("npx husky init"));
+        
+        fs::create_dir(temp_dir.path().join(".husky")).unwrap();
+        fs::write(temp_dir.path().join(".husky/pre-commit"), "npm test").unwrap();
+        Framework::Husky.install(temp_dir.path(), &[Hook::PreCommit]).unwrap();
+        Framework::Husky.install(temp_dir.path(), &[Hook::PreCommit]).unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join(".husky/pre-commit")).unwrap(),
+            "npm test\nenveil hook run pre-commit \"$@\"\n"
+        );
+        assert!(Framework::Husky.is_installed(temp_dir.path(), Hook::PreCommit));
+        
+        Framework::Husky.uninstall(temp_dir.path(), &Hook::ALL).unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join(".husky/pre-commit")).unwrap(), "npm test\n");
+    }
+    
+    #[test]
+    fn test_pre_commit_config_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = temp_dir.path().join(".pre-commit-config.yaml");
+        let existing = "default_stages: [pre-commit]\nrepos:\n- repo: https://github.com/psf/black\n  rev: 24.1.0\n  hooks:\n  - id: black\n";
+        fs::write(&config, existing).unwrap();
+        
+        Framework::PreCommit.install(temp_dir.path(), &[Hook::PreCommit]).unwrap();
+        Framework::PreCommit.install(temp_dir.path(), &[Hook::CommitMsg]).unwrap();
+        let content = fs::read_to_string(&config).unwrap();
+        assert!(content.starts_with("default_stages: [pre-commit]\nrepos:\n# enveil:begin"));
+        assert!(content.contains("\n- repo: local\n  hooks:\n    - id: enveil-pre-commit\n"));
+        assert!(content.contains("      entry: enveil hook run commit-msg\n      language: system\n      pass_filenames: true\n"));
+        assert!(content.ends_with("# enveil:end\n- repo: https://github.com/psf/black\n  rev: 24.1.0\n  hooks:\n  - id: black\n"));
+        assert!(Framework::PreCommit.is_installed(temp_dir.path(), Hook::CommitMsg));
+        assert!(!Framework::PreCommit.is_installed(temp_dir.path(), Hook::PrePush));
+        
+        Framework::PreCommit.uninstall(temp_dir.path(), &Hook::ALL).unwrap();
+        assert_eq!(fs::read_to_string(&config).unwrap(), existing);
+    }
+    
+    #[test]
+    fn test_allow_bypass_is_passed_to_hook_run() {
+        assert!(Hook::PreCommit.script(false, true).contains("exec enveil hook run --allow-bypass pre-commit \"$@\""));
+        assert!(Hook::PrePush.script(true, true).contains("| enveil hook run --allow-bypass pre-push \"$@\""));
+    }
+    
+    #[test]
+    fn test_hook_names() {
+        assert_eq!("pre-push".parse::<Hook>().unwrap(), Hook::PrePush);
+        assert_eq!("commit-msg".parse::<Hook>().unwrap(), Hook::CommitMsg);
+        assert!("post-merge".parse::<Hook>().is_err());
     }
 }
@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::git;
+
+/// Pattern of files encrypted in place by `protect --action encrypt`
+pub const ENCRYPTED_PATTERN: &str = "*.enc";
+
+/// Header written above the entries Enveil appends
+const HEADER: &str = "# Enveil";
+
+/// The `.gitignore` at the root of a project
+pub struct Gitignore {
+    project_path: PathBuf,
+}
+
+impl Gitignore {
+    pub fn new<P: AsRef<Path>>(project_path: P) -> Self {
+        Self {
+            project_path: project_path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Append the entries that are not already lines of `.gitignore`,
+    /// returning the ones added
+    pub fn append<S: AsRef<str>>(&self, entries: &[S]) -> Result<Vec<String>, String> {
+        let path = self.project_path.join(".gitignore");
+        let existing = if path.exists() {
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read .gitignore: {}", e))?
+        } else {
+            String::new()
+        };
+
+        let present: Vec<&str> = existing.lines().map(|l| l.trim()).collect();
+        let mut missing: Vec<String> = Vec::new();
+        for entry in entries.iter().map(AsRef::as_ref) {
+            if !present.contains(&entry) && !missing.iter().any(|m| m == entry) {
+                missing.push(entry.to_string());
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(missing);
+        }
+
+        let has_header = present.contains(&HEADER);
+        let mut content = existing;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        if !has_header {
+            content.push_str(&format!("\n{}\n", HEADER));
+        }
+        for entry in &missing {
+            content.push_str(entry);
+            content.push('\n');
+        }
+
+        fs::write(&path, content).map_err(|e| format!("Failed to write .gitignore: {}", e))?;
+        Ok(missing)
+    }
+
+    /// Entry anchored at the project root for one of its files, or `None`
+    /// when the file is outside the project or already ignored
+    pub fn entry_for(&self, file: &Path) -> Option<String> {
+        let relative = file.strip_prefix(&self.project_path).ok()?;
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if relative.is_empty() || git::is_ignored(&self.project_path, &relative) {
+            return None;
+        }
+        Some(format!("/{}", relative))
+    }
+
+    /// Entries for the files plus the secure directory and `*.enc`
+    pub fn entries(&self, files: &[PathBuf], secure_dir: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = files.iter().filter_map(|file| self.entry_for(file)).collect();
+        let secure_dir = secure_dir.strip_prefix(&self.project_path).unwrap_or(secure_dir);
+        entries.push(format!("{}/", secure_dir.to_string_lossy().trim_end_matches('/')));
+        entries.push(ENCRYPTED_PATTERN.to_string());
+        entries
+    }
+
+    /// The files that git already tracks: ignoring them does not remove
+    /// them from the repository
+    pub fn tracked(&self, files: &[PathBuf]) -> Vec<PathBuf> {
+        let tracked = match git::tracked_files(&self.project_path) {
+            Ok(tracked) => tracked,
+            Err(_) => return Vec::new(),
+        };
+
+        files
+            .iter()
+            .filter(|file| match file.strip_prefix(&self.project_path) {
+                Ok(relative) => tracked.iter().any(|t| Path::new(t) == relative),
+                Err(_) => false,
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Keep local artifacts out of git with a `.gitignore` inside their own
+/// directory, ignoring itself too, so that they stay untracked even in
+/// projects that never ran `enveil init`
+pub fn ignore_artifacts(dir: &Path, entries: &[&str]) -> Result<(), String> {
+    let entries: Vec<&str> = std::iter::once(".gitignore").chain(entries.iter().copied()).collect();
+    Gitignore::new(dir).append(&entries).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_is_idempotent() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(".gitignore"), "target/\n*.enc").unwrap();
+
+        let gitignore = Gitignore::new(temp_dir.path());
+        let files = vec![temp_dir.path().join("config/.env"), temp_dir.path().join("id_rsa")];
+        let entries = gitignore.entries(&files, &temp_dir.path().join("enveil_secure"));
+        assert_eq!(entries, ["/config/.env", "/id_rsa", "enveil_secure/", "*.enc"]);
+
+        assert_eq!(gitignore.append(&entries).unwrap(), ["/config/.env", "/id_rsa", "enveil_secure/"]);
+        assert!(gitignore.append(&entries).unwrap().is_empty());
+
+        let content = fs::read_to_string(temp_dir.path().join(".gitignore")).unwrap();
+        assert_eq!(content, "target/\n*.enc\n\n# Enveil\n/config/.env\n/id_rsa\nenveil_secure/\n");
+    }
+
+    #[test]
+    fn test_tracked_and_ignored_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git").args(args).current_dir(temp_dir.path()).status().unwrap();
+        };
+        git(&["init", "-q"]);
+        fs::write(temp_dir.path().join(".env"), "API_KEY=abc\n").unwrap();
+        fs::write(temp_dir.path().join("key.pem"), "pem\n").unwrap();
+        fs::write(temp_dir.path().join(".gitignore"), "*.pem\n").unwrap();
+        git(&["add", ".env"]);
+
+        let gitignore = Gitignore::new(temp_dir.path());
+        let files = vec![temp_dir.path().join(".env"), temp_dir.path().join("key.pem")];
+        assert_eq!(gitignore.entry_for(&files[0]).as_deref(), Some("/.env"));
+        assert_eq!(gitignore.entry_for(&files[1]), None);
+        assert_eq!(gitignore.tracked(&files), [temp_dir.path().join(".env")]);
+    }
+}
@@ -0,0 +1,117 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+
+/// Glob patterns of paths, as given to `--exclude` and `--include`.
+///
+/// Patterns are matched against paths relative to the scanned directory.
+/// A pattern without `/` matches at any depth, like in `.gitignore`, and a
+/// matching directory matches everything below it.
+#[derive(Debug, Clone)]
+pub struct PathGlobs {
+    globs: GlobSet,
+    /// Leading literal directories of each pattern, used to prune walks
+    prefixes: Vec<PathBuf>,
+}
+
+impl PathGlobs {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        let mut prefixes = Vec::new();
+
+        for pattern in patterns {
+            let trimmed = pattern.trim_start_matches("./").trim_end_matches('/');
+            let anchored = if trimmed.contains('/') { trimmed.to_string() } else { format!("**/{}", trimmed) };
+
+            let glob = Glob::new(&anchored)
+                .map_err(|e| format!("Invalid glob pattern {}: {}", pattern, e))?;
+            builder.add(glob);
+            prefixes.push(literal_prefix(&anchored));
+        }
+
+        let globs = builder
+            .build()
+            .map_err(|e| format!("Invalid glob patterns: {}", e))?;
+        Ok(Self { globs, prefixes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    /// Whether a path below `root`, or one of its parent directories, matches
+    pub fn matches(&self, root: &Path, path: &Path) -> bool {
+        if self.globs.is_empty() {
+            return false;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        relative
+            .ancestors()
+            .take_while(|p| !p.as_os_str().is_empty())
+            .any(|p| self.globs.is_match(p))
+    }
+
+    /// Whether a directory below `root` may hold paths that match, so that
+    /// walks can skip the rest of the tree
+    pub fn may_contain(&self, root: &Path, dir: &Path) -> bool {
+        let relative = dir.strip_prefix(root).unwrap_or(dir);
+
+        self.prefixes
+            .iter()
+            .any(|prefix| relative.starts_with(prefix) || prefix.starts_with(relative))
+    }
+}
+
+impl Default for PathGlobs {
+    fn default() -> Self {
+        Self { globs: GlobSet::empty(), prefixes: Vec::new() }
+    }
+}
+
+/// Directories of a pattern before its first wildcard (`services/api/**` -> `services/api`)
+fn literal_prefix(pattern: &str) -> PathBuf {
+    pattern
+        .split('/')
+        .take_while(|part| !part.contains(['*', '?', '[', '{', '\\']))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(patterns: &[&str]) -> PathGlobs {
+        PathGlobs::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_glob_patterns() {
+        let root = Path::new("/repo");
+        let globs = globs(&["fixtures/**", "testdata", "*.generated.json", "./docs/"]);
+
+        assert!(globs.matches(root, Path::new("/repo/fixtures/a/.env")));
+        assert!(globs.matches(root, Path::new("/repo/pkg/testdata/key.pem")));
+        assert!(globs.matches(root, Path::new("/repo/api/schema.generated.json")));
+        assert!(globs.matches(root, Path::new("/repo/docs/setup.md")));
+        assert!(!globs.matches(root, Path::new("/repo/src/fixtures/.env")));
+        assert!(!globs.matches(root, Path::new("/repo/config.json")));
+
+        assert!(!PathGlobs::default().matches(root, Path::new("/repo/fixtures")));
+        assert!(PathGlobs::new(&["a[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_may_contain() {
+        let root = Path::new("/repo");
+        let globs = globs(&["services/api/**"]);
+
+        assert!(globs.may_contain(root, Path::new("/repo")));
+        assert!(globs.may_contain(root, Path::new("/repo/services")));
+        assert!(globs.may_contain(root, Path::new("/repo/services/api/src")));
+        assert!(!globs.may_contain(root, Path::new("/repo/services/web")));
+        assert!(!globs.may_contain(root, Path::new("/repo/node")));
+
+        // Patterns matching at any depth cannot prune anything
+        assert!(self::globs(&["*.yaml"]).may_contain(root, Path::new("/repo/node")));
+    }
+}
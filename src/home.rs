@@ -0,0 +1,65 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Dotfiles of a home directory that commonly end up holding credentials:
+/// shell history, cloud and registry logins, package manager tokens
+pub const CREDENTIAL_FILES: &[&str] = &[
+    ".bash_history",
+    ".zsh_history",
+    ".aws/credentials",
+    ".docker/config.json",
+    ".npmrc",
+    ".netrc",
+    ".kube/config",
+];
+
+/// Home directory of the current user
+pub fn home_dir() -> Option<PathBuf> {
+    ["HOME", "USERPROFILE"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Credential files present in a home directory, with the kubeconfig files
+/// listed in `kubeconfig` (the `KUBECONFIG` path list) when set
+pub fn credential_files(home: &Path, kubeconfig: Option<&OsStr>) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = CREDENTIAL_FILES.iter().map(|file| home.join(file)).collect();
+    if let Some(paths) = kubeconfig {
+        files.extend(std::env::split_paths(paths).filter(|path| !path.as_os_str().is_empty()));
+    }
+
+    let mut present: Vec<PathBuf> = Vec::new();
+    for file in files {
+        if file.is_file() && !present.contains(&file) {
+            present.push(file);
+        }
+    }
+    present
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_credential_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path();
+        fs::create_dir_all(home.join(".aws")).unwrap();
+        fs::write(home.join(".aws/credentials"), "[default]\n").unwrap();
+        fs::write(home.join(".npmrc"), "registry=https://registry.npmjs.org/\n").unwrap();
+        fs::write(home.join("work.kubeconfig"), "apiVersion: v1\n").unwrap();
+
+        assert_eq!(credential_files(home, None), vec![home.join(".aws/credentials"), home.join(".npmrc")]);
+
+        let kubeconfig = std::env::join_paths([home.join("work.kubeconfig"), home.join("missing"), home.join(".npmrc")]).unwrap();
+        assert_eq!(
+            credential_files(home, Some(&kubeconfig)),
+            vec![home.join(".aws/credentials"), home.join(".npmrc"), home.join("work.kubeconfig")]
+        );
+    }
+}
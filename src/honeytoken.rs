@@ -0,0 +1,238 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::detector::{self, SecretDetector};
+use crate::reporter::ScanReport;
+
+/// Registry of issued honeytokens, relative to the project root
+pub const REGISTRY_FILE: &str = ".enveil/honeytokens.json";
+
+/// Version written to new registry files
+const REGISTRY_VERSION: u32 = 1;
+
+const ALNUM: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const DIGITS: &[u8] = b"0123456789";
+/// Characters of AWS access key ids
+const AWS_ID: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const AWS_SECRET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789/+";
+
+/// Kind of fake credential
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HoneytokenType {
+    /// AWS access key id and secret key, as in ~/.aws/credentials
+    Aws,
+    /// GitHub personal access token
+    Github,
+    /// Slack bot token
+    Slack,
+    /// Stripe live secret key
+    Stripe,
+}
+
+impl FromStr for HoneytokenType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "aws" => Ok(Self::Aws),
+            "github" => Ok(Self::Github),
+            "slack" => Ok(Self::Slack),
+            "stripe" => Ok(Self::Stripe),
+            _ => Err(format!("Unknown honeytoken type: {} (expected aws, github, slack or stripe)", s)),
+        }
+    }
+}
+
+impl HoneytokenType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Aws => "aws",
+            Self::Github => "github",
+            Self::Slack => "slack",
+            Self::Stripe => "stripe",
+        }
+    }
+
+    /// Text holding a new fake credential, ready to paste into a file
+    fn snippet(&self) -> String {
+        match self {
+            Self::Aws => format!(
+                "[default]\naws_access_key_id = AKIA{}\naws_secret_access_key = {}\n",
+                random(AWS_ID, 16),
+                random(AWS_SECRET, 40)
+            ),
+            Self::Github => format!("GITHUB_TOKEN=ghp_{}\n", random(ALNUM, 36)),
+            Self::Slack => format!(
+                "SLACK_BOT_TOKEN=xoxb-{}-{}-{}\n",
+                random(DIGITS, 12),
+                random(DIGITS, 13),
+                random(ALNUM, 24)
+            ),
+            Self::Stripe => format!("STRIPE_SECRET_KEY=sk_live_{}\n", random(ALNUM, 24)),
+        }
+    }
+}
+
+/// An issued honeytoken. Only the fingerprints of its values are kept, so
+/// the registry itself holds nothing a scan would report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Honeytoken {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: HoneytokenType,
+    /// Unix timestamp (seconds) of the generation
+    pub created: u64,
+    /// Where the token was planted, as given with `--note`
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Fingerprints of the secret values, as in scan reports
+    pub fingerprints: Vec<String>,
+}
+
+/// Honeytokens issued for a project, stored in `.enveil/honeytokens.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registry {
+    pub version: u32,
+    pub tokens: Vec<Honeytoken>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self { version: REGISTRY_VERSION, tokens: Vec::new() }
+    }
+}
+
+/// A honeytoken found by a scan
+#[derive(Debug, Clone)]
+pub struct Sighting<'a> {
+    pub token: &'a Honeytoken,
+    pub path: String,
+    pub line_number: usize,
+    pub rule_id: String,
+}
+
+impl Registry {
+    /// Load a registry, empty when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read honeytoken registry {}: {}", path.display(), e))?;
+
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid honeytoken registry {}: {}", path.display(), e))
+    }
+
+    /// Write the registry, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize honeytoken registry: {}", e))?;
+        fs::write(path, format!("{}\n", json))
+            .map_err(|e| format!("Failed to write honeytoken registry {}: {}", path.display(), e))
+    }
+
+    /// Generate a honeytoken and record it. Returns the text to plant.
+    pub fn generate(&mut self, kind: HoneytokenType, note: Option<&str>) -> Result<(String, &Honeytoken), String> {
+        let snippet = kind.snippet();
+        let mut fingerprints: Vec<String> = SecretDetector::new()
+            .scan_content(&snippet)
+            .findings
+            .into_iter()
+            .map(|finding| finding.fingerprint)
+            .collect();
+        fingerprints.sort();
+        fingerprints.dedup();
+        if fingerprints.is_empty() {
+            return Err(format!("The generated {} honeytoken is not detected by the rules", kind.name()));
+        }
+
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.tokens.push(Honeytoken {
+            id: format!("ht-{}", &detector::fingerprint(&snippet)[..8]),
+            kind,
+            created,
+            note: note.map(str::to_string),
+            fingerprints,
+        });
+        Ok((snippet, &self.tokens[self.tokens.len() - 1]))
+    }
+
+    /// Findings of a scan report that are honeytokens of the registry
+    pub fn sightings(&self, report: &ScanReport) -> Vec<Sighting<'_>> {
+        report
+            .secrets
+            .iter()
+            .flat_map(|file| file.findings.iter().map(move |finding| (file, finding)))
+            .filter_map(|(file, finding)| {
+                let token = self.tokens.iter().find(|t| t.fingerprints.contains(&finding.fingerprint))?;
+                Some(Sighting {
+                    token,
+                    path: file.path.clone(),
+                    line_number: finding.line_number,
+                    rule_id: finding.rule_id.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn random(chars: &[u8], len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..len).map(|_| chars[rng.gen_range(0..chars.len())] as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_generate_and_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let registry_path = temp_dir.path().join(REGISTRY_FILE);
+        let mut registry = Registry::load(&registry_path).unwrap();
+
+        for kind in [HoneytokenType::Aws, HoneytokenType::Github, HoneytokenType::Slack, HoneytokenType::Stripe] {
+            let (snippet, token) = registry.generate(kind, Some("planted in ci.env")).unwrap();
+            assert!(token.id.starts_with("ht-"));
+            fs::write(temp_dir.path().join(format!("{}.env", kind.name())), snippet).unwrap();
+        }
+        assert!(registry.tokens[0].fingerprints.len() >= 2);
+        registry.save(&registry_path).unwrap();
+
+        let report = Scanner::builder().build().unwrap().scan(temp_dir.path()).unwrap();
+        let registry = Registry::load(&registry_path).unwrap();
+        let sightings = registry.sightings(&report);
+        for token in &registry.tokens {
+            assert!(sightings.iter().any(|s| s.token.id == token.id && s.path.ends_with(&format!("{}.env", token.kind.name()))));
+        }
+        assert_eq!(sightings[0].token.note.as_deref(), Some("planted in ci.env"));
+
+        // The registry keeps fingerprints only
+        let saved = fs::read_to_string(&registry_path).unwrap();
+        assert!(!saved.contains("AKIA"));
+        assert!(!saved.contains("sk_live_"));
+        assert!(Registry::default().sightings(&report).is_empty());
+    }
+
+    #[test]
+    fn test_honeytoken_type() {
+        assert_eq!("AWS".parse::<HoneytokenType>().unwrap(), HoneytokenType::Aws);
+        assert!("gcp".parse::<HoneytokenType>().is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::manifest::Manifest;
+use crate::protector::{self, ProtectAction};
+use crate::restorer::{self, RestoreResult};
+use crate::stub::Stub;
+
+/// Name of the journal file inside the secure directory
+pub const JOURNAL_FILE: &str = "journal.json";
+
+/// Progress of one operation of a protect run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum OpState {
+    /// Nothing written yet
+    Planned,
+    /// Protected copy written and recorded, original still in place
+    Written,
+    /// Original removed
+    Completed,
+}
+
+/// One file of a protect run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalEntry {
+    pub original_path: String,
+    pub protected_path: String,
+    pub action: ProtectAction,
+    pub state: OpState,
+}
+
+/// Operations of a directory protect run, written before any file is
+/// touched and removed once the run completes. A journal left behind
+/// means the run was interrupted and can be rolled back.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Journal {
+    /// Unix timestamp (seconds) of the run
+    pub started: u64,
+    pub operations: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub fn new(operations: Vec<JournalEntry>) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self { started, operations }
+    }
+
+    /// Path of the journal for a secure directory
+    pub fn path(secure_dir: &Path) -> PathBuf {
+        secure_dir.join(JOURNAL_FILE)
+    }
+
+    /// Load the journal of an unfinished run, if any
+    pub fn load(secure_dir: &Path) -> Result<Option<Self>, String> {
+        let path = Self::path(secure_dir);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read journal: {}", e))?;
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid journal {}: {}", path.display(), e))
+    }
+
+    /// Write the journal into the secure directory
+    pub fn save(&self, secure_dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize journal: {}", e))?;
+
+        let path = Self::path(secure_dir);
+        fs::write(&path, json + "\n")
+            .map_err(|e| format!("Failed to write journal: {}", e))?;
+        protector::restrict_permissions(&path, protector::SECURE_FILE_MODE)
+    }
+
+    /// Delete the journal once its run is complete or rolled back
+    pub fn remove(secure_dir: &Path) -> Result<(), String> {
+        match fs::remove_file(Self::path(secure_dir)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove journal: {}", e)),
+        }
+    }
+
+    /// Mark an operation and persist the journal
+    pub fn set_state(&mut self, secure_dir: &Path, index: usize, state: OpState) -> Result<(), String> {
+        self.operations[index].state = state;
+        self.save(secure_dir)
+    }
+
+    /// Undo the run: protected copies whose original is still in place are
+    /// deleted, moved files are put back. Encrypted files whose original is
+    /// gone are kept, they can only come back with the key. The journal is
+    /// removed afterwards.
+    pub fn rollback(&self, secure_dir: &Path) -> Result<Vec<RestoreResult>, String> {
+        let mut manifest = Manifest::load(secure_dir)?;
+        let mut results = Vec::new();
+
+        for op in self.operations.iter().rev() {
+            let protected = PathBuf::from(&op.protected_path);
+            let original = PathBuf::from(&op.original_path);
+            let result = |success: bool, message: String| RestoreResult {
+                encrypted_path: op.protected_path.clone(),
+                restored_path: op.original_path.clone(),
+                success,
+                message,
+            };
+
+            if !protected.exists() {
+                results.push(result(true, "Nothing written, nothing to undo".to_string()));
+                continue;
+            }
+
+            let entry = manifest
+                .entries
+                .iter()
+                .rposition(|e| e.location(secure_dir).as_deref() == Some(protected.as_path()));
+            let rolled_back = if original.exists() {
+                fs::remove_file(&protected)
+                    .map(|_| "Protected copy removed, original untouched".to_string())
+                    .map_err(|e| format!("Failed to remove protected copy: {}", e))
+            } else if op.action == ProtectAction::Moved {
+                let metadata = entry.and_then(|index| manifest.entries[index].metadata.clone());
+                fs::copy(&protected, &original)
+                    .and_then(|_| fs::remove_file(&protected))
+                    .map_err(|e| format!("Failed to move file back: {}", e))
+                    .map(|_| match metadata.map(|m| m.apply(&original)) {
+                        Some(Err(e)) => format!("File moved back (warning: {})", e),
+                        _ => "File moved back".to_string(),
+                    })
+            } else {
+                results.push(result(
+                    false,
+                    "Original already deleted, kept encrypted: restore it with `enveil decrypt --to-original`".to_string(),
+                ));
+                continue;
+            };
+
+            match rolled_back {
+                Ok(message) => {
+                    if let Some(index) = entry {
+                        manifest.entries.remove(index);
+                    }
+                    restorer::remove_empty_parents(secure_dir, &protected);
+                    Stub::remove(&original);
+                    results.push(result(true, message));
+                }
+                Err(e) => results.push(result(false, e)),
+            }
+        }
+
+        manifest.save(secure_dir)?;
+        Self::remove(secure_dir)?;
+        results.reverse();
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::{FileProtector, ProtectOption};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_load_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(Journal::load(temp_dir.path()).unwrap(), None);
+
+        let mut journal = Journal::new(vec![JournalEntry {
+            original_path: "/project/.env".to_string(),
+            protected_path: temp_dir.path().join(".env").to_string_lossy().to_string(),
+            action: ProtectAction::Moved,
+            state: OpState::Planned,
+        }]);
+        journal.save(temp_dir.path()).unwrap();
+        journal.set_state(temp_dir.path(), 0, OpState::Written).unwrap();
+
+        let loaded = Journal::load(temp_dir.path()).unwrap().unwrap();
+        assert_eq!(loaded.operations[0].state, OpState::Written);
+
+        Journal::remove(temp_dir.path()).unwrap();
+        assert_eq!(Journal::load(temp_dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rollback_interrupted_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let moved = temp_dir.path().join(".env");
+        let written = temp_dir.path().join("id_rsa");
+        fs::write(&moved, "API_KEY=abc\n").unwrap();
+        fs::write(&written, "key\n").unwrap();
+
+        // The run was cut off after removing the first original only
+        let protector = FileProtector::new(secure_dir.clone());
+        let first = protector.protect_file(&moved, &ProtectOption::Move, None);
+        fs::copy(&written, secure_dir.join("id_rsa")).unwrap();
+        let journal = Journal::new(vec![
+            JournalEntry {
+                original_path: first.original_path.clone(),
+                protected_path: first.protected_path.clone(),
+                action: ProtectAction::Moved,
+                state: OpState::Completed,
+            },
+            JournalEntry {
+                original_path: written.to_string_lossy().to_string(),
+                protected_path: secure_dir.join("id_rsa").to_string_lossy().to_string(),
+                action: ProtectAction::Moved,
+                state: OpState::Written,
+            },
+        ]);
+        journal.save(&secure_dir).unwrap();
+        assert!(!moved.exists());
+
+        let results = Journal::load(&secure_dir).unwrap().unwrap().rollback(&secure_dir).unwrap();
+        assert!(results.iter().all(|r| r.success));
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "API_KEY=abc\n");
+        assert_eq!(fs::read_to_string(&written).unwrap(), "key\n");
+        assert!(!secure_dir.join(".env").exists());
+        assert!(!secure_dir.join("id_rsa").exists());
+        assert!(Manifest::load(&secure_dir).unwrap().entries.is_empty());
+        assert_eq!(Journal::load(&secure_dir).unwrap(), None);
+    }
+}
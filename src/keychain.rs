@@ -0,0 +1,75 @@
+use keyring::Entry;
+use std::fs;
+use std::path::Path;
+
+use crate::protector;
+
+/// Service name under which Enveil stores keys
+const SERVICE: &str = "enveil";
+
+/// Keychain account name for a project, scoped by its absolute path
+pub fn account_name(project_path: &Path) -> String {
+    let absolute = fs::canonicalize(project_path).unwrap_or_else(|_| project_path.to_path_buf());
+    format!("project:{}", absolute.display())
+}
+
+/// Keychain account name for a named environment of a project
+pub fn environment_account_name(project_path: &Path, environment: &str) -> String {
+    format!("{}:env:{}", account_name(project_path), environment)
+}
+
+fn entry(account: &str) -> Result<Entry, String> {
+    Entry::new(SERVICE, account).map_err(|e| format!("Failed to access OS keychain: {}", e))
+}
+
+/// Store the encryption key of a project in the OS keychain
+pub fn store_key(project_path: &Path, key: &[u8; 32]) -> Result<(), String> {
+    store(&account_name(project_path), key)
+}
+
+/// Fetch the encryption key of a project from the OS keychain
+pub fn load_key(project_path: &Path) -> Result<Option<[u8; 32]>, String> {
+    load(&account_name(project_path))
+}
+
+/// Store the encryption key of an environment of a project
+pub fn store_environment_key(project_path: &Path, environment: &str, key: &[u8; 32]) -> Result<(), String> {
+    store(&environment_account_name(project_path, environment), key)
+}
+
+/// Fetch the encryption key of an environment of a project
+pub fn load_environment_key(project_path: &Path, environment: &str) -> Result<Option<[u8; 32]>, String> {
+    load(&environment_account_name(project_path, environment))
+}
+
+fn store(account: &str, key: &[u8; 32]) -> Result<(), String> {
+    entry(account)?
+        .set_password(&protector::encode_key(key))
+        .map_err(|e| format!("Failed to store key in OS keychain: {}", e))
+}
+
+fn load(account: &str) -> Result<Option<[u8; 32]>, String> {
+    match entry(account)?.get_password() {
+        Ok(encoded) => protector::decode_key(&encoded).map(Some),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read key from OS keychain: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_account_name_is_project_scoped() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+
+        let name = account_name(first.path());
+        assert!(name.starts_with("project:/"));
+        assert_eq!(name, account_name(&first.path().join(".")));
+        assert_ne!(name, account_name(second.path()));
+        assert_eq!(environment_account_name(first.path(), "prod"), format!("{}:env:prod", name));
+    }
+}
@@ -1,47 +1,125 @@
+//! Secret detection and protection.
+//!
+//! The `enveil` binary is a thin CLI over this crate. Other tools can embed
+//! scanning and protection directly:
+//!
+//! ```no_run
+//! use enveil::scanner::Scanner;
+//! use std::path::Path;
+//!
+//! let scanner = Scanner::builder()
+//!     .extra_extension(".properties")
+//!     .exclude("fixtures")
+//!     .build()?;
+//!
+//! // Full report, as printed by `enveil scan`
+//! let report = scanner.scan(Path::new("."))?;
+//! println!("{} secrets found", report.secrets_found);
+//!
+//! // Or stream findings one file at a time
+//! for found in scanner.findings(Path::new(".")) {
+//!     println!("{}:{} {}", found.path.display(), found.finding.line_number, found.finding.secret_type);
+//! }
+//! # Ok::<(), String>(())
+//! ```
+
+pub mod audit;
+pub mod baseline;
+pub mod cache;
+pub mod config;
+pub mod context;
+pub mod detector;
+pub mod diff;
+pub mod doctor;
+pub mod dotenv_vault;
+pub mod env;
+pub mod environ;
+pub mod extends;
+pub mod filter;
+pub mod forge;
+mod git;
+pub mod git_hooks;
+pub mod gitignore;
+mod globs;
+pub mod history;
+pub mod home;
+pub mod honeytoken;
+#[cfg(test)]
+mod http_mock;
+pub mod image;
+pub mod init;
+pub mod journal;
+pub mod keychain;
+pub mod lsp;
+pub mod manifest;
+pub mod notifier;
+mod patterns;
+pub mod policy;
+pub mod protector;
+pub mod purge;
+pub mod recipient;
+pub mod rekey;
+pub mod reporter;
+pub mod restorer;
+pub mod runner;
+pub mod scanner;
+pub mod selftest;
+pub mod sensitivity;
+pub mod server;
+pub mod shares;
+pub mod stream;
+mod structured;
+pub mod style;
+pub mod stub;
+pub mod vault;
+pub mod verify;
+pub mod watcher;
+
+pub use config::Config;
+pub use detector::{CustomRule, SecretDetector, SecretFinding};
+pub use protector::{FileProtector, ProtectOption};
+pub use reporter::ScanReport;
+pub use restorer::{DecryptionKey, FileRestorer};
+pub use scanner::{Finding, ScanOptions, Scanner};
+
 // Unit tests for Enveil
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
-    
+    use crate::sensitivity::{RiskTable, ENV_FILE, TEMPLATE};
+    use std::path::Path;
+
+    /// File type and risk level the default table gives a path
+    fn classify(path: &str) -> Option<(String, String)> {
+        RiskTable::default().classify(Path::new(path)).map(|c| (c.file_type, c.risk_level))
+    }
+
     #[test]
     fn test_risky_extensions() {
-        let mut extensions = std::collections::HashSet::new();
-        extensions.insert(".env");
-        extensions.insert(".pem");
-        extensions.insert(".key");
-        
-        assert!(extensions.contains(".env"));
-        assert!(extensions.contains(".pem"));
-        assert!(!extensions.contains(".txt"));
+        assert!(classify("/project/.env").is_some());
+        assert!(classify("/project/server.pem").is_some());
+        assert!(classify("/project/tls.key").is_some());
+        assert!(classify("/project/notes.txt").is_none());
     }
     
     #[test]
     fn test_file_risk_level() {
-        fn get_file_risk_level(extension: &str) -> &'static str {
-            match extension {
-                ".env" | ".pem" | ".key" | ".p12" | ".pfx" => "high",
-                ".json" | ".yaml" | ".yml" | ".toml" => "medium",
-                _ => "low",
-            }
-        }
+        let level = |path: &str| classify(path).map(|(_, level)| level);
         
-        assert_eq!(get_file_risk_level(".env"), "high");
-        assert_eq!(get_file_risk_level(".pem"), "high");
-        assert_eq!(get_file_risk_level(".json"), "medium");
-        assert_eq!(get_file_risk_level(".txt"), "low");
+        assert_eq!(level("/project/.env").as_deref(), Some("high"));
+        assert_eq!(level("/project/server.pem").as_deref(), Some("high"));
+        assert_eq!(level("/project/config.json").as_deref(), Some("medium"));
+        assert_eq!(level("/project/app.log").as_deref(), Some("low"));
     }
     
     #[test]
     fn test_env_file_detection() {
-        fn is_env_file(file_name: &str) -> bool {
-            file_name.starts_with(".env") || file_name == ".env"
-        }
+        let file_type = |path: &str| classify(path).map(|(file_type, _)| file_type);
         
-        assert!(is_env_file(".env"));
-        assert!(is_env_file(".env.local"));
-        assert!(is_env_file(".env.production"));
-        assert!(!is_env_file(".env.example"));
-        assert!(!is_env_file("config.json"));
+        assert_eq!(file_type("/project/.env").as_deref(), Some(ENV_FILE));
+        assert_eq!(file_type("/project/.env.local").as_deref(), Some(ENV_FILE));
+        assert_eq!(file_type("/project/.env.production").as_deref(), Some(ENV_FILE));
+        assert_eq!(file_type("/project/.env.example").as_deref(), Some(TEMPLATE));
+        assert_eq!(file_type("/project/config.json").as_deref(), Some(".json"));
     }
 }
@@ -1,17 +1,84 @@
-use clap::{Parser, Subcommand};
-mod git_hooks;
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::Level;
 
-use git_hooks::GitHooks;
+use enveil::audit;
+use enveil::baseline::Baseline;
+use enveil::cache::ScanCache;
+use enveil::config::{self, Config};
+use enveil::detector::{self, SecretDetector};
+use enveil::diff::{LocatedFinding, ScanDiff};
+use enveil::doctor::{Doctor, Status};
+use enveil::dotenv_vault::{self, DotenvKey, EnvVault};
+use enveil::emit;
+use enveil::env;
+use enveil::environ;
+use enveil::extends;
+use enveil::filter;
+use enveil::forge::{self, GitHub, GitLab, OrgReport};
+use enveil::git_hooks::{Framework, GitHooks, Hook};
+use enveil::gitignore::Gitignore;
+use enveil::history::{self, History};
+use enveil::home;
+use enveil::honeytoken::{self, HoneytokenType};
+use enveil::image;
+use enveil::init::{self, InitStep, ProjectInitializer};
+use enveil::journal::Journal;
+use enveil::keychain;
+use enveil::lsp::{self, LanguageServer};
+use enveil::notifier::{self, Located, Notifier};
+use enveil::policy::{self, Policy};
+use enveil::protector::{self, FileProtector, ProtectOption};
+use enveil::purge::PurgePlan;
+use enveil::recipient::{Identity, Recipient};
+use enveil::rekey::{self, NewKey};
+use enveil::reporter::{self, GroupBy, Output, OutputTarget, ScanReport, SecretFileReport};
+use enveil::restorer::{ConflictPolicy, DecryptionKey, FileRestorer, RestoreResult};
+use enveil::runner::{self, EnvSource};
+use enveil::scanner::{ScanOptions, Scanner};
+use enveil::selftest::{self, Outcome};
+use enveil::sensitivity::RiskTable;
+use enveil::server::{self, ScanServer};
+use enveil::shares::{self, Threshold};
+use enveil::stub::Stub;
+use enveil::style::{self, ColorChoice, Stream};
+use enveil::vault::{self, Backend, Vault};
+use enveil::verify::{self, VerifyStatus};
+use enveil::watcher::Watch;
 
-mod detector;
-mod git_hooks;
-mod protector;
+const VERSION: &str = "0.1.0";
 
-use detector::{SecretDetector, SecretFinding};
-use git_hooks::GitHooks;
-use protector::{FileProtector, ProtectOption, ProtectResult, SensitiveFiles};
+/// Exit code of a scan whose findings reach the --fail-on threshold
+const EXIT_FINDINGS: i32 = 1;
 
-const VERSION: &str = "0.1.0";
+/// Exit code of a scan that could not complete
+const EXIT_ERROR: i32 = 2;
+
+/// Path argument that makes scan read stdin
+const STDIN_PATH: &str = "-";
+
+/// Name findings read from stdin are reported under
+const STDIN_NAME: &str = "<stdin>";
+
+/// Environment variable read instead of prompting for a passphrase
+const PASSPHRASE_ENV: &str = "ENVEIL_PASSPHRASE";
+
+/// Environment variable read for the base64 encryption key
+const KEY_ENV: &str = "ENVEIL_KEY";
+
+/// Environment variable holding the new passphrase of `enveil rekey`
+const NEW_PASSPHRASE_ENV: &str = "ENVEIL_NEW_PASSPHRASE";
+
+/// Environment variable bypassing hooks installed with `--allow-bypass`
+const SKIP_ENV: &str = "ENVEIL_SKIP";
+/// Environment variables read when `scan-org` or `scan-pr` has no `--token`
+const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+const GITLAB_TOKEN_ENV: &str = "GITLAB_TOKEN";
 
 #[derive(Parser)]
 #[command(name = "enveil")]
@@ -20,270 +87,3933 @@ const VERSION: &str = "0.1.0";
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    
+    /// Config file (defaults to .enveil.toml in the project directory)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    
+    /// Fetch the config named by `extends` again instead of using the cached copy
+    #[arg(long, global = true)]
+    refresh_config: bool,
+    
+    /// Level of the logs printed on stderr; debug shows why each file is
+    /// scanned or skipped and which rules match [default: warn, info with --verbose]
+    #[arg(long, global = true, value_parser = ["error", "warn", "info", "debug", "trace"])]
+    log_level: Option<String>,
+    
+    /// Print the logs as JSON lines
+    #[arg(long, global = true)]
+    log_json: bool,
+    
+    /// Color output (auto/always/never); auto colors terminals unless NO_COLOR is set
+    #[arg(long, global = true, value_name = "WHEN", default_value = "auto", value_parser = ["auto", "always", "never"])]
+    color: String,
+    
+    /// Replace emoji with plain ASCII, for plain terminals and Windows consoles
+    #[arg(long, global = true)]
+    ascii: bool,
+}
+
+impl Commands {
+    /// Whether a scan command asked for verbose output
+    fn verbose(&self) -> bool {
+        matches!(
+            self,
+            Commands::Scan { verbose: true, .. }
+                | Commands::ScanImage { verbose: true, .. }
+                | Commands::ScanRemote { verbose: true, .. }
+                | Commands::ScanOrg { verbose: true, .. }
+                | Commands::ScanPr { verbose: true, .. }
+        )
+    }
 }
 
+// Parsed once per run, so the size of the scan variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Scan a directory for secrets
     Scan {
-        /// Path to scan
+        /// Directory or file to scan, or - to read stdin
         path: Option<String>,
         
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
         
-        /// Output format (text/json)
-        #[arg(short, long, default_value = "text")]
-        format: String,
+        /// Output format (text/json/ndjson/junit/markdown/html/sarif) [default: text]
+        #[arg(short, long)]
+        format: Option<String>,
+        
+        /// Write the formatted report to a file (a text summary is still printed)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// List the secrets of the text report by file, by rule or by directory
+        #[arg(long, value_name = "BY", value_parser = ["file", "rule", "dir"], default_value = "file")]
+        group_by: String,
+        
+        /// TOML file with extra [[rules]] merged into the built-in rules
+        #[arg(long)]
+        rules: Option<PathBuf>,
+        
+        /// Scan the content staged in the git index instead of the working tree
+        #[arg(long)]
+        staged: bool,
+        
+        /// Skip paths matching a glob pattern (repeatable, e.g. 'fixtures/**')
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+        
+        /// Only scan paths matching a glob pattern (repeatable, e.g. 'services/api/**')
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+        
+        /// Lowest secret severity that makes the scan exit with code 1 [default: high]
+        #[arg(long, value_parser = ["none", "low", "medium", "high", "critical"])]
+        fail_on: Option<String>,
+        
+        /// Only run these rules, by id or name (comma separated, e.g. AWS001,GH001)
+        #[arg(long, value_name = "RULES", value_delimiter = ',')]
+        enable_rules: Vec<String>,
+        
+        /// Never run these rules, by id or name (comma separated, e.g. GEN002,PASSWORD)
+        #[arg(long, value_name = "RULES", value_delimiter = ',')]
+        disable_rules: Vec<String>,
+        
+        /// Only report secrets at or above this severity
+        #[arg(long, value_parser = ["low", "medium", "high", "critical"])]
+        min_severity: Option<String>,
+        
+        /// Only report secrets at or above this confidence
+        #[arg(long, value_parser = ["low", "medium", "high"])]
+        min_confidence: Option<String>,
+        
+        /// Mask whole lines instead of showing the key and a preview of the secret
+        #[arg(long)]
+        redact_all: bool,
+        
+        /// Keep full confidence for secrets in tests, fixtures, examples and docs
+        #[arg(long)]
+        include_tests: bool,
+        
+        /// Baseline of known secrets to skip [default: .enveil/baseline.json if present]
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+        
+        /// Accept every secret found into the baseline instead of reporting them
+        #[arg(long)]
+        update_baseline: bool,
+        
+        /// Compare with a saved JSON scan and only fail on newly introduced secrets
+        #[arg(long, value_name = "FILE")]
+        compare_with: Option<PathBuf>,
+        
+        /// Scan every file again instead of reusing results from .enveil/cache
+        #[arg(long)]
+        no_cache: bool,
+        
+        /// Only scan files tracked by git, skipping untracked build output
+        #[arg(long, conflicts_with = "staged")]
+        tracked_only: bool,
+        
+        /// Only scan files changed since the branch forked from a revision and
+        /// fail on the secrets it introduced (e.g. origin/main)
+        #[arg(long, value_name = "REV", conflicts_with_all = ["staged", "tracked_only", "compare_with"])]
+        since: Option<String>,
+        
+        /// Skip files larger than this, with a warning (e.g. 500KB, 50MB) [default: 10MB]
+        #[arg(long, value_name = "SIZE")]
+        max_file_size: Option<String>,
+        
+        /// Scan the printable strings of binary files instead of skipping them
+        #[arg(long)]
+        scan_binary: bool,
+        
+        /// Walk every hidden directory (.github, .gitlab, .circleci, .buildkite,
+        /// .devcontainer and .vscode are always walked)
+        #[arg(long)]
+        hidden: bool,
+        
+        /// Scan the exported environment variables instead of files (catches
+        /// credentials exported by dotfiles or CI runners)
+        #[arg(long, conflicts_with_all = ["staged", "since", "tracked_only"])]
+        env_vars: bool,
+        
+        /// With --env-vars, also scan the environment of a running process from
+        /// /proc/<PID>/environ on Linux (repeatable)
+        #[arg(long, value_name = "PID", requires = "env_vars")]
+        pid: Vec<u32>,
+        
+        /// Audit the credential dotfiles of the home directory instead of a
+        /// project: shell history, ~/.aws/credentials, ~/.docker/config.json,
+        /// ~/.npmrc, ~/.netrc and kubeconfig
+        #[arg(long, conflicts_with_all = ["staged", "since", "tracked_only", "env_vars"])]
+        home: bool,
+        
+        /// Check the [[policy]] conditions of the config after the scan and
+        /// fail when any is violated
+        #[arg(long, conflicts_with = "update_baseline")]
+        enforce: bool,
+        
+        /// Do not record the scan in .enveil/history.db
+        #[arg(long)]
+        no_history: bool,
+        
+        /// POST the findings to a webhook when some reach --notify-on, as a
+        /// Slack message for hooks.slack.com and JSON otherwise (repeatable)
+        #[arg(long, value_name = "URL")]
+        notify: Vec<String>,
+        
+        /// Lowest severity that triggers --notify webhooks
+        #[arg(long, value_name = "LEVEL", default_value = notifier::DEFAULT_MIN_SEVERITY, value_parser = ["low", "medium", "high", "critical"])]
+        notify_on: String,
+    },
+    /// Scan a container image (`docker save` tarball or OCI layout directory)
+    /// for secrets and sensitive files baked into its layers
+    ScanImage {
+        /// Image tarball or OCI layout directory
+        image: PathBuf,
+        
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+        
+        /// Output format (text/json/ndjson/junit/markdown/html/sarif) [default: text]
+        #[arg(short, long)]
+        format: Option<String>,
+        
+        /// Write the formatted report to a file (a text summary is still printed)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Lowest secret severity that makes the scan exit with code 1 [default: high]
+        #[arg(long, value_parser = ["none", "low", "medium", "high", "critical"])]
+        fail_on: Option<String>,
+        
+        /// Skip files larger than this, with a warning (e.g. 500KB, 50MB) [default: 10MB]
+        #[arg(long, value_name = "SIZE")]
+        max_file_size: Option<String>,
+        
+        /// Scan the printable strings of binary files instead of skipping them
+        #[arg(long)]
+        scan_binary: bool,
+    },
+    /// Shallow clone a git repository and scan it, without keeping the clone
+    ScanRemote {
+        /// Repository URL (https, ssh or file)
+        url: String,
+        
+        /// Branch or tag to scan [default: the remote's default branch]
+        #[arg(long)]
+        branch: Option<String>,
+        
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+        
+        /// Output format (text/json/ndjson/junit/markdown/html/sarif) [default: text]
+        #[arg(short, long)]
+        format: Option<String>,
+        
+        /// Write the formatted report to a file (a text summary is still printed)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Lowest secret severity that makes the scan exit with code 1 [default: high]
+        #[arg(long, value_parser = ["none", "low", "medium", "high", "critical"])]
+        fail_on: Option<String>,
+    },
+    /// Audit every repository of a GitHub organization: list them through
+    /// the API, shallow clone and scan them concurrently, report per repo
+    ScanOrg {
+        /// GitHub organization name
+        org: String,
+        
+        /// Personal access token, for private repositories and higher rate limits [default: $GITHUB_TOKEN]
+        #[arg(long)]
+        token: Option<String>,
+        
+        /// API base URL, for GitHub Enterprise Server
+        #[arg(long, default_value = forge::GITHUB_API)]
+        api_url: String,
+        
+        /// Also scan forks
+        #[arg(long)]
+        include_forks: bool,
+        
+        /// Also scan archived repositories
+        #[arg(long)]
+        include_archived: bool,
+        
+        /// Repositories cloned and scanned at the same time
+        #[arg(short, long, default_value_t = 4)]
+        jobs: usize,
+        
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+        
+        /// Output format (text/json) [default: text]
+        #[arg(short, long, value_parser = ["text", "json"])]
+        format: Option<String>,
+        
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Lowest secret severity that makes the audit exit with code 1 [default: high]
+        #[arg(long, value_parser = ["none", "low", "medium", "high", "critical"])]
+        fail_on: Option<String>,
+    },
+    /// Scan the lines added by a GitHub pull request or GitLab merge request,
+    /// fetched through the API without cloning
+    ScanPr {
+        /// GitHub pull request, e.g. acme/api#123
+        #[arg(long, value_name = "OWNER/REPO#NUMBER", conflicts_with = "gitlab", required_unless_present = "gitlab")]
+        github: Option<String>,
+        
+        /// GitLab merge request, e.g. group/app!45
+        #[arg(long, value_name = "PROJECT!IID")]
+        gitlab: Option<String>,
+        
+        /// Access token [default: $GITHUB_TOKEN or $GITLAB_TOKEN]
+        #[arg(long)]
+        token: Option<String>,
+        
+        /// API base URL, for GitHub Enterprise Server or self-managed GitLab
+        #[arg(long)]
+        api_url: Option<String>,
+        
+        /// Post a comment listing the findings on the pull request (locations only)
+        #[arg(long)]
+        comment: bool,
+        
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+        
+        /// Output format (text/json/ndjson/junit/markdown/html/sarif) [default: text]
+        #[arg(short, long)]
+        format: Option<String>,
+        
+        /// Write the formatted report to a file (a text summary is still printed)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Lowest secret severity that makes the scan exit with code 1 [default: high]
+        #[arg(long, value_parser = ["none", "low", "medium", "high", "critical"])]
+        fail_on: Option<String>,
+    },
+    /// Render a saved JSON scan report again, in any format, without rescanning
+    Report {
+        /// JSON report written by `enveil scan --output <file>`
+        file: PathBuf,
+        
+        /// Output format (text/json/ndjson/junit/markdown/html/sarif) [default: text]
+        #[arg(short, long)]
+        format: Option<String>,
+        
+        /// Write the formatted report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// List the secrets of the text report by file, by rule or by directory
+        #[arg(long, value_name = "BY", value_parser = ["file", "rule", "dir"], default_value = "file")]
+        group_by: String,
+        
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Compare two saved JSON scans: new, fixed and persisting secrets
+    Diff {
+        /// Earlier JSON scan report
+        old: PathBuf,
+        
+        /// Later JSON scan report
+        new: PathBuf,
+        
+        /// Output format (text/json) [default: text]
+        #[arg(short, long, value_parser = ["text", "json"])]
+        format: Option<String>,
+        
+        /// Lowest severity of new secrets that makes diff exit with code 1 [default: high]
+        #[arg(long, value_parser = ["none", "low", "medium", "high", "critical"])]
+        fail_on: Option<String>,
     },
     /// Protect a project from secret exposure
     Protect {
         /// Path to protect
         path: Option<String>,
+        
+        /// Protection action (move/encrypt/both) [default: move]
+        #[arg(short, long)]
+        action: Option<String>,
+        
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+        
+        /// Base64 encryption key, refused from a terminal without
+        /// --insecure-key-arg (a new key is generated when omitted)
+        #[arg(short, long)]
+        key: Option<String>,
+        
+        /// Read the base64 encryption key from a file [default: ENVEIL_KEY]
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+        
+        /// Accept --key from a terminal, where it ends up in shell history and `ps`
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+        
+        /// Store the encryption key in the OS keychain instead of printing it
+        #[arg(long)]
+        keychain: bool,
+        
+        /// Derive the encryption key from a passphrase prompted without echo
+        #[arg(long, conflicts_with_all = ["key", "key_file", "keychain"])]
+        passphrase: bool,
+        
+        /// Encrypt to a teammate's public key from `enveil keygen --identity`
+        /// (repeatable); each file gets its own key, nothing is shared
+        #[arg(long, value_name = "PUBKEY", conflicts_with_all = ["key", "key_file", "keychain", "passphrase"])]
+        recipient: Vec<String>,
+        
+        /// Rebuild the key from shares printed by `enveil keygen --split`
+        #[arg(long, value_delimiter = ',', conflicts_with_all = ["key", "key_file", "keychain", "passphrase", "recipient"])]
+        shares: Vec<String>,
+        
+        /// Leave paths matching a glob pattern in place (repeatable, e.g. 'fixtures/**')
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+        
+        /// List the files that would be protected without touching them
+        #[arg(long)]
+        dry_run: bool,
+        
+        /// Undo a previous move, same as `enveil unprotect`
+        #[arg(long)]
+        undo: bool,
+        
+        /// Do not add the protected files and the secure directory to .gitignore
+        #[arg(long)]
+        no_gitignore: bool,
+        
+        /// Overwrite the originals of encrypted files before deleting them
+        /// (best effort: SSDs and copy-on-write filesystems may keep copies)
+        #[arg(long)]
+        shred: bool,
+        
+        /// Undo an interrupted protect run from its journal
+        #[arg(long, conflicts_with_all = ["undo", "dry_run"])]
+        rollback: bool,
+        
+        /// Do not leave a .enveil stub in place of moved files
+        #[arg(long)]
+        no_stubs: bool,
+        
+        /// Where protected files go: local (secure directory) or vault (a
+        /// Vault KV path, auth from VAULT_ADDR and VAULT_TOKEN or
+        /// VAULT_ROLE_ID/VAULT_SECRET_ID) [default: local]
+        #[arg(long)]
+        backend: Option<String>,
+        
+        /// KV version 2 mount of the Vault backend [default: secret]
+        #[arg(long)]
+        vault_mount: Option<String>,
+        
+        /// Path prefix of the files in the Vault mount [default: enveil/<project>]
+        #[arg(long)]
+        vault_path: Option<String>,
+        
+        /// Protect the files of a named environment (`[environments.<NAME>]`
+        /// in the config, by default .env.<NAME>, or the file given as path)
+        /// into its own secure directory with its own key
+        #[arg(long = "env", value_name = "NAME")]
+        environment: Option<String>,
+    },
+    /// Move protected files back to their original location
+    Unprotect {
+        /// Project path
+        path: Option<String>,
+        
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+        
+        /// Show what would be restored without touching any file
+        #[arg(long)]
+        dry_run: bool,
+        
+        /// What to do when the original location exists (skip/overwrite/rename)
+        #[arg(long, default_value = "skip")]
+        on_conflict: String,
+    },
+    /// Decrypt files protected with --action encrypt, or move back the file
+    /// of a .enveil stub
+    #[command(visible_alias = "restore")]
+    Decrypt {
+        /// Project path, or a .enveil stub left by protect to move its file back
+        path: Option<String>,
+        
+        /// Base64 encryption key printed by protect, refused from a terminal
+        /// without --insecure-key-arg [default: ENVEIL_KEY, then the OS keychain]
+        #[arg(short, long)]
+        key: Option<String>,
+        
+        /// Read the base64 encryption key from a file
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+        
+        /// Accept --key from a terminal, where it ends up in shell history and `ps`
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+        
+        /// Prompt for the passphrase the files were protected with
+        #[arg(long, conflicts_with_all = ["key", "key_file"])]
+        passphrase: bool,
+        
+        /// Read the identity (private key) the files were encrypted to
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["key", "key_file", "passphrase"])]
+        identity: Option<PathBuf>,
+        
+        /// Rebuild the key from shares printed by `enveil keygen --split`
+        #[arg(long, value_delimiter = ',', conflicts_with_all = ["key", "key_file", "passphrase", "identity"])]
+        shares: Vec<String>,
+        
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+        
+        /// Decrypt a single .enc file instead of the whole secure directory
+        #[arg(long)]
+        file: Option<PathBuf>,
+        
+        /// Write plaintext back to the original location recorded in the manifest
+        #[arg(long)]
+        to_original: bool,
+        
+        /// Overwrite existing files
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Pull the files protect stored in Vault back instead (vault), auth
+        /// from VAULT_ADDR and VAULT_TOKEN or VAULT_ROLE_ID/VAULT_SECRET_ID
+        #[arg(long)]
+        backend: Option<String>,
+    },
+    
+    /// Generate a base64 encryption key, or a recipient identity (key pair)
+    Keygen {
+        /// Write a new identity (private key) to this file and print its
+        /// public key, to give to whoever protects files for you
+        #[arg(long, value_name = "PATH")]
+        identity: Option<PathBuf>,
+        
+        /// Split the new key into Shamir shares, any k of which rebuild it
+        /// (e.g. 3-of-5); decrypt with `enveil decrypt --shares`
+        #[arg(long, value_name = "K-of-N", conflicts_with = "identity")]
+        split: Option<String>,
+    },
+    
+    /// Check every protected file against its recorded digest, and that
+    /// encrypted files decrypt when a key is available
+    Verify {
+        /// Project path
+        path: Option<String>,
+        
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+        
+        /// Base64 key to check decryption with, refused from a terminal without
+        /// --insecure-key-arg [default: ENVEIL_KEY, then the OS keychain, if any]
+        #[arg(short, long)]
+        key: Option<String>,
+        
+        /// Read the base64 key from a file
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+        
+        /// Accept --key from a terminal, where it ends up in shell history and `ps`
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+        
+        /// Prompt for the passphrase the files were protected with
+        #[arg(long, conflicts_with_all = ["key", "key_file"])]
+        passphrase: bool,
+        
+        /// Read the identity (private key) the files were encrypted to
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["key", "key_file", "passphrase"])]
+        identity: Option<PathBuf>,
+    },
+    
+    /// Re-encrypt every .enc file of the secure directory with a new key
+    Rekey {
+        /// Project path
+        path: Option<String>,
+        
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+        
+        /// Current base64 key, refused from a terminal without
+        /// --insecure-key-arg [default: ENVEIL_KEY, then the OS keychain]
+        #[arg(short, long)]
+        key: Option<String>,
+        
+        /// Read the current base64 key from a file
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+        
+        /// Accept --key from a terminal, where it ends up in shell history and `ps`
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+        
+        /// Prompt for the current passphrase
+        #[arg(long, conflicts_with_all = ["key", "key_file"])]
+        passphrase: bool,
+        
+        /// Read the identity (private key) the files are encrypted to
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["key", "key_file", "passphrase"])]
+        identity: Option<PathBuf>,
+        
+        /// Read the new base64 key from a file [default: generate one]
+        #[arg(long, value_name = "PATH")]
+        new_key_file: Option<PathBuf>,
+        
+        /// Derive the new key from a passphrase (prompted without echo, or ENVEIL_NEW_PASSPHRASE)
+        #[arg(long, conflicts_with = "new_key_file")]
+        new_passphrase: bool,
+        
+        /// Store the new key in the OS keychain instead of printing it
+        #[arg(long, conflicts_with = "new_passphrase")]
+        keychain: bool,
     },
     /// Install Git hooks
     Install {
         /// Path to install hooks
         path: Option<String>,
         
-        /// Force reinstall hooks
+        /// Overwrite existing hooks instead of keeping and chaining to them
         #[arg(short, long)]
         force: bool,
         
-        /// Uninstall hooks
+        /// Hooks to manage, comma separated (pre-commit, pre-push, commit-msg, pre-receive)
+        /// [default: pre-commit,pre-push; all with --uninstall; pre-receive with --server]
+        #[arg(long, value_delimiter = ',', value_name = "HOOKS")]
+        hooks: Vec<String>,
+        
+        /// Manage the pre-receive hook of a bare repository on a git server,
+        /// which rejects pushes containing secrets
         #[arg(long)]
+        server: bool,
+        
+        /// Register the hooks with a hook manager instead of writing .git/hooks
+        #[arg(long, value_parser = ["husky", "pre-commit"], conflicts_with = "server")]
+        framework: Option<String>,
+        
+        /// Let `ENVEIL_SKIP=1 git commit` through despite secrets, recording
+        /// who bypassed which findings in .enveil/audit.log
+        #[arg(long, conflicts_with_all = ["server", "framework"])]
+        allow_bypass: bool,
+        
+        /// Uninstall hooks
+        #[arg(long, conflicts_with_all = ["status", "force"])]
         uninstall: bool,
         
-        /// Check hook status
-        #[arg(long)]
+        /// Show which hooks are installed
+        #[arg(long, conflicts_with = "force")]
         status: bool,
     },
-}
+    /// Watch a directory and alert as soon as a secret is written to disk
+    Watch {
+        /// Path to watch
+        path: Option<String>,
 
-<<<<<<< HEAD
-#[derive(Serialize)]
-struct ScanResult {
-    path: String,
-    file_type: String,
-    risk_level: String,
-}
-
-#[derive(Serialize)]
-struct ScanReport {
-    total_files: usize,
-    risky_files: usize,
-    files: Vec<ScanResult>,
-}
-
-fn get_risky_extensions() -> HashSet<&'static str> {
-    let mut extensions = HashSet::new();
-    // Secrets & Config
-    extensions.insert(".env");
-    extensions.insert(".env.local");
-    extensions.insert(".env.prod");
-    extensions.insert(".env.dev");
-    extensions.insert(".env.example");
-    extensions.insert(".json");
-    extensions.insert(".yaml");
-    extensions.insert(".yml");
-    extensions.insert(".toml");
-    extensions.insert(".ini");
-    extensions.insert(".conf");
-    extensions.insert(".config");
-    // Keys & Credentials
-    extensions.insert(".pem");
-    extensions.insert(".key");
-    extensions.insert(".pub");
-    extensions.insert(".p12");
-    extensions.insert(".pfx");
-    extensions.insert(".crt");
-    extensions.insert(".cer");
-    // Sensitive
-    extensions.insert(".sql");
-    extensions.insert(".db");
-    extensions.insert(".sqlite");
-    extensions.insert(".log");
-    extensions.insert(".bak");
-    extensions.insert(".backup");
-    extensions
-}
-
-fn get_file_risk_level(extension: &str) -> &'static str {
-    match extension {
-        ".env" | ".env.local" | ".env.prod" | ".env.dev" | ".pem" | ".key" | ".p12" | ".pfx" => "high",
-        ".env.example" | ".json" | ".yaml" | ".yml" | ".toml" | ".sql" | ".db" => "medium",
-        _ => "low",
-    }
-}
-
-fn scan_directory(dir_path: &Path, verbose: bool) -> Result<ScanReport, String> {
-    let risky_extensions = get_risky_extensions();
-    let mut results: Vec<ScanResult> = Vec::new();
-    
-    if !dir_path.exists() {
-        return Err(format!("Path does not exist: {}", dir_path.display()));
-    }
-    
-    if !dir_path.is_dir() {
-        return Err(format!("Path is not a directory: {}", dir_path.display()));
-    }
-    
-    scan_recursive(dir_path, &risky_extensions, &mut results, verbose);
-    
-    let total_files = results.len();
-    let risky_files = results.len();
-    
-    Ok(ScanReport {
-        total_files,
-        risky_files,
-        files: results,
-    })
-}
+        /// Alert format (text/json, one JSON object per line) [default: text]
+        #[arg(short, long, value_parser = ["text", "json"])]
+        format: Option<String>,
 
-fn scan_recursive(dir_path: &Path, extensions: &HashSet<&str>, results: &mut Vec<ScanResult>, verbose: bool) {
-    // Skip hidden directories and common non-relevant dirs
-    let skip_dirs = [".git", "node_modules", "target", "dist", "build", "vendor"];
-    
-    if let Ok(entries) = fs::read_dir(dir_path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            
-            if path.is_dir() {
-                let dir_name = path.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("");
-                
-                if !dir_name.starts_with('.') && !skip_dirs.contains(&dir_name) {
-                    scan_recursive(&path, extensions, results, verbose);
-                }
-            } else if path.is_file() {
-                let extension = path.extension()
-                    .and_then(|e| e.to_str())
-                    .map(|e| format!(".{}", e))
-                    .unwrap_or_default();
-                
-                let file_name = path.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("");
-                
-                // Check for .env files by name
-                let is_env_file = file_name.starts_with(".env") || extension == ".env";
-                
-                if extensions.contains(&extension.as_str()) || is_env_file {
-                    let risk_level = if is_env_file {
-                        "high"
-                    } else {
-                        get_file_risk_level(&extension)
-                    };
-                    
-                    results.push(ScanResult {
-                        path: path.to_string_lossy().to_string(),
-                        file_type: if is_env_file { ".env".to_string() } else { extension },
-                        risk_level: risk_level.to_string(),
-                    });
-                    
-                    if verbose {
-                        eprintln!("[{}] Found: {}", risk_level.to_uppercase(), path.display());
-                    }
-                }
-            }
-        }
-    }
-}
+        /// Mask whole lines instead of showing the key and a preview of the secret
+        #[arg(long)]
+        redact_all: bool,
 
-fn print_text_report(report: ScanReport, verbose: bool) {
-    println!("\n📁 Enveil Scan Report\n");
-    println!("Total risky files found: {}\n", report.risky_files);
-    
-    if report.files.is_empty() {
-        println!("✅ No risky files detected!");
-        return;
-    }
-    
-    // Sort by risk level (high first)
-    let mut files = report.files;
-    files.sort_by(|a, b| {
-        let order = |r: &str| match r {
-            "high" => 0,
-            "medium" => 1,
-            _ => 2,
-        };
-        order(&a.risk_level).cmp(&order(&b.risk_level))
-    });
-    
-    for file in &files {
-        let icon = match file.risk_level.as_str() {
-            "high" => "🔴",
-            "medium" => "🟡",
-            _ => "🟢",
-        };
-        
-        println!("{} [{}] {}", icon, file.risk_level.to_uppercase(), file.path);
-        
-        if verbose {
-            println!("   Type: {}", file.file_type);
-        }
-    }
-}
+        /// POST each alert to a webhook when it has secrets reaching
+        /// --notify-on (Slack message for hooks.slack.com, JSON otherwise; repeatable)
+        #[arg(long, value_name = "URL")]
+        notify: Vec<String>,
 
-fn print_json_report(report: ScanReport) {
-    if let Ok(json) = serde_json::to_string_pretty(&report) {
-        println!("{}", json);
-    }
-}
+        /// Lowest severity that triggers --notify webhooks
+        #[arg(long, value_name = "LEVEL", default_value = notifier::DEFAULT_MIN_SEVERITY, value_parser = ["low", "medium", "high", "critical"])]
+        notify_on: String,
+    },
+
+    /// Replace secret values in a file or stdin with ****, e.g. before sharing logs
+    Mask {
+        /// File to mask [default: read stdin]
+        file: Option<PathBuf>,
+
+        /// Overwrite the file instead of printing to stdout
+        #[arg(short, long, requires = "file")]
+        write: bool,
+    },
+
+    /// List detection rules or try one against sample input
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+
+    /// Run the checks of an installed git hook (called by the hook scripts)
+    Hook {
+        #[command(subcommand)]
+        command: HookCommand,
+    },
+
+    /// Commit files encrypted while keeping them plaintext in the working
+    /// tree (git clean/smudge filter)
+    Filter {
+        #[command(subcommand)]
+        command: FilterCommand,
+    },
+
+    /// Run a command with the variables of a protected .env file in its
+    /// environment, without writing the plaintext to disk
+    Run {
+        /// Load the files of a named environment (`[environments.<NAME>]` in
+        /// the config, by default .env.<NAME>) from its secure directory
+        #[arg(long = "env", value_name = "NAME")]
+        environment: Option<String>,
+        
+        /// Project path
+        #[arg(long, default_value = ".")]
+        path: PathBuf,
+        
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+        
+        /// Base64 decryption key, refused from a terminal without
+        /// --insecure-key-arg [default: ENVEIL_KEY, then the OS keychain]
+        #[arg(short, long)]
+        key: Option<String>,
+        
+        /// Read the base64 key from a file
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+        
+        /// Accept --key from a terminal, where it ends up in shell history and `ps`
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+        
+        /// Prompt for the passphrase the file was protected with
+        #[arg(long, conflicts_with_all = ["key", "key_file"])]
+        passphrase: bool,
+        
+        /// Read the identity (private key) the file was encrypted to
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["key", "key_file", "passphrase"])]
+        identity: Option<PathBuf>,
+        
+        /// Command to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Work with .env files
+    Env {
+        #[command(subcommand)]
+        command: EnvCommand,
+    },
+
+    /// Manage the scan cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Set up Enveil in a project (config, baseline, .gitignore, hooks, first scan)
+    Init {
+        /// Project path
+        path: Option<String>,
+        
+        /// Overwrite existing config and baseline
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Do not install Git hooks
+        #[arg(long)]
+        no_hooks: bool,
+    },
+
+    /// Fix what a scan finds in the project setup
+    Fix {
+        /// Project path
+        path: Option<String>,
+
+        /// Add the high risk files, the secure directory and *.enc to .gitignore
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Directory where protected files are stored [default: enveil_secure]
+        #[arg(long)]
+        secure_dir: Option<String>,
+    },
+
+    /// Find secrets in the git history and plan how to rewrite it with
+    /// git-filter-repo
+    Purge {
+        /// Repository path
+        path: Option<String>,
+
+        /// Print the affected commits and the commands to run
+        #[arg(long, required_unless_present = "execute")]
+        plan: bool,
+
+        /// Print the plan, then rewrite the local history (nothing is pushed)
+        #[arg(long, conflicts_with = "plan")]
+        execute: bool,
+    },
+
+    /// Check the setup (repository, hooks, binary, config, .gitignore, keys)
+    /// and suggest a fix for every failed check
+    Doctor {
+        /// Project path
+        path: Option<String>,
+    },
+
+    /// Generate fake credentials to plant as tripwires, and detect them in scan reports
+    Honeytoken {
+        #[command(subcommand)]
+        command: HoneytokenCommand,
+    },
+
+    /// Scan a synthetic secret for every rule and check that each is
+    /// detected with the current config (exits 1 when a rule misses)
+    Selftest,
+
+    /// Show findings over time from the scans recorded in .enveil/history.db:
+    /// new and fixed findings per week and the longest-lived open findings
+    Trends {
+        /// Project directory [default: .]
+        path: Option<PathBuf>,
+        
+        /// Output format (text/json) [default: text]
+        #[arg(short, long, value_parser = ["text", "json"])]
+        format: Option<String>,
+        
+        /// Number of open findings listed
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// Serve a JSON HTTP API: GET /rules, and POST /scan with a tarball,
+    /// text/plain content, or JSON {"content": ..., "name": ...} or {"path": ...}.
+    /// Set ENVEIL_SERVE_TOKEN to require `Authorization: Bearer <token>`
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        bind: String,
+        
+        /// Directory JSON {"path": ...} scans are confined to (path scans are
+        /// refused without it); its .enveil.toml configures the scans
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
+        
+        /// Requests handled at once
+        #[arg(long, default_value_t = 4)]
+        jobs: usize,
+    },
+
+    /// Run a language server over stdio: editors show findings as you type,
+    /// with quick fixes to suppress them inline or allowlist their value
+    Lsp,
+
+    /// Print a tab completion script for a shell, e.g.
+    /// `enveil completions bash > ~/.local/share/bash-completion/completions/enveil`
+    Completions {
+        /// Shell to complete in
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// List built-in and custom rules with their id, severity and an example
+    List,
+
+    /// Run a single rule against a string or file to see whether it matches
+    Test {
+        /// Rule id or name (e.g. GEN003 or PASSWORD)
+        rule: String,
+
+        /// Text to test, or the path of a file to read it from
+        input: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvCommand {
+    /// Write a copy of a .env file that is safe to commit: the same keys and
+    /// comments, with the values blanked
+    Template {
+        /// .env file to read
+        #[arg(default_value = ".env")]
+        file: PathBuf,
+
+        /// File to write [default: <file>.example]; `-` prints to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Value written for every variable
+        #[arg(long, default_value = "")]
+        placeholder: String,
+
+        /// Overwrite an existing template
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Compare a .env file with its template or the `[env]` schema of
+    /// .enveil.toml: missing, empty and undeclared variables fail the check
+    Check {
+        /// .env file to check
+        #[arg(default_value = ".env")]
+        file: PathBuf,
+
+        /// Template to compare with [default: the `[env]` schema if set,
+        /// else <file>.example]
+        #[arg(long, value_name = "PATH")]
+        example: Option<PathBuf>,
+
+        /// Report variables missing from the template without failing
+        #[arg(long)]
+        allow_extra: bool,
+    },
+
+    /// Show the variables added, removed or changed between two .env files,
+    /// with values masked (exits with 1 when they differ)
+    Diff {
+        /// First .env file
+        old: PathBuf,
+
+        /// Second .env file
+        new: PathBuf,
+
+        /// Print the values instead of masking them
+        #[arg(long)]
+        show_values: bool,
+    },
+
+    /// Flag duplicate keys, unquoted values with spaces, trailing
+    /// whitespace, syntax errors and plaintext secrets in .env files
+    Lint {
+        /// .env files to lint
+        #[arg(default_value = ".env")]
+        files: Vec<PathBuf>,
+
+        /// Quote values with spaces and remove trailing whitespace in place
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Encrypt the values of some variables in place (`enc:v1:...`), leaving
+    /// the rest of the file readable
+    Encrypt {
+        /// .env file to edit
+        #[arg(default_value = ".env")]
+        file: PathBuf,
+
+        /// Variables to encrypt (comma-delimited)
+        #[arg(long, value_delimiter = ',', required = true)]
+        keys: Vec<String>,
+
+        /// Base64 encryption key (prefer --key-file or ENVEIL_KEY)
+        #[arg(short, long)]
+        key: Option<String>,
+
+        /// Read the base64 key from a file
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+
+        /// Allow --key from an interactive terminal
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+
+        /// Store a newly generated key in the OS keychain instead of printing it
+        #[arg(long)]
+        keychain: bool,
+    },
+
+    /// Read and write dotenv-vault `.env.vault` bundles
+    Vault {
+        #[command(subcommand)]
+        command: EnvVaultCommand,
+    },
+
+    /// Decrypt the encrypted values of a .env file in place, or print the
+    /// decrypted file
+    Decrypt {
+        /// .env file to decrypt
+        #[arg(default_value = ".env")]
+        file: PathBuf,
+
+        /// Print the decrypted file instead of editing it
+        #[arg(long)]
+        stdout: bool,
+
+        /// Base64 decryption key (prefer --key-file or ENVEIL_KEY)
+        #[arg(short, long)]
+        key: Option<String>,
+
+        /// Read the base64 key from a file
+        #[arg(long, value_name = "PATH", conflicts_with = "key")]
+        key_file: Option<PathBuf>,
+
+        /// Allow --key from an interactive terminal
+        #[arg(long, requires = "key")]
+        insecure_key_arg: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvVaultCommand {
+    /// Encrypt .env files into .env.vault, one environment per file (`.env`
+    /// is development, `.env.production` production), keeping the keys in
+    /// .env.keys
+    Build {
+        /// .env files to bundle
+        #[arg(default_value = ".env")]
+        files: Vec<PathBuf>,
+
+        /// Bundle to write [default: .env.vault next to the first file]
+        #[arg(long, value_name = "PATH")]
+        vault: Option<PathBuf>,
+
+        /// Keys file, reused for existing environments [default: .env.keys
+        /// next to the bundle]
+        #[arg(long, value_name = "PATH")]
+        keys_file: Option<PathBuf>,
+    },
+
+    /// Print an environment of a .env.vault, decrypted with DOTENV_KEY or
+    /// the key in .env.keys
+    Decrypt {
+        /// Environment to decrypt [default: the one of DOTENV_KEY, else development]
+        #[arg(short, long)]
+        environment: Option<String>,
+
+        /// Bundle to read
+        #[arg(long, value_name = "PATH", default_value = ".env.vault")]
+        vault: PathBuf,
+
+        /// Keys file read when DOTENV_KEY is not set [default: .env.keys next
+        /// to the bundle]
+        #[arg(long, value_name = "PATH")]
+        keys_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FilterCommand {
+    /// Configure the filter in this repository and route files to it
+    Install {
+        /// Files to encrypt, as .gitattributes patterns (e.g. 'secrets/*.yaml')
+        #[arg(required = true)]
+        patterns: Vec<String>,
+
+        /// Key file the filter reads [default: ENVEIL_KEY, then the OS keychain,
+        /// where a new key is stored if there is none]
+        #[arg(long, value_name = "PATH")]
+        key_file: Option<PathBuf>,
+    },
+
+    /// Encrypt stdin to stdout (run by git when staging)
+    Clean {
+        /// Path of the file, passed by git
+        file: Option<String>,
+
+        /// Read the base64 key from a file
+        #[arg(long, value_name = "PATH")]
+        key_file: Option<PathBuf>,
+    },
+
+    /// Decrypt stdin to stdout (run by git on checkout)
+    Smudge {
+        /// Path of the file, passed by git
+        file: Option<String>,
+
+        /// Read the base64 key from a file
+        #[arg(long, value_name = "PATH")]
+        key_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HookCommand {
+    /// Scan what a hook guards and exit non-zero to abort the git operation
+    Run {
+        /// Let ENVEIL_SKIP=1 bypass the hook, recording it in .enveil/audit.log
+        #[arg(long)]
+        allow_bypass: bool,
+
+        /// Hook name (pre-commit, pre-push, commit-msg or pre-receive)
+        hook: String,
+
+        /// Arguments git passes to the hook
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HoneytokenCommand {
+    /// Print a realistic fake credential to plant in a repository, and
+    /// record its fingerprints in the registry
+    Generate {
+        /// Kind of credential
+        #[arg(long = "type", value_name = "TYPE", value_parser = ["aws", "github", "slack", "stripe"])]
+        kind: String,
+        
+        /// Where the token is planted, shown when it is found
+        #[arg(long)]
+        note: Option<String>,
+        
+        /// Registry of issued honeytokens [default: .enveil/honeytokens.json]
+        #[arg(long)]
+        registry: Option<PathBuf>,
+    },
+    
+    /// Flag the honeytokens of the registry found in a saved JSON scan
+    /// report: the repository they were planted in leaked (exits 1)
+    Check {
+        /// JSON report written by `enveil scan --output <file>`
+        report: PathBuf,
+        
+        /// Registry of issued honeytokens [default: .enveil/honeytokens.json]
+        #[arg(long)]
+        registry: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Delete the cached scan results of a project
+    Clear {
+        /// Project path
+        path: Option<String>,
+    },
+}
+
+/// Build the list of outputs for a scan run.
+///
+/// Without `--output` the selected format goes to stdout. With it, the
+/// selected format is written to the file and a text summary is printed.
+fn build_outputs(format: &str, output: Option<&PathBuf>, verbose: bool, group_by: GroupBy) -> Result<Vec<Output>, String> {
+    let reporter = reporter::reporter_for(format, verbose, group_by)?;
+    
+    match output {
+        Some(file) => Ok(vec![
+            Output::new(reporter, OutputTarget::File(file.clone())),
+            Output::new(reporter::reporter_for("text", verbose, group_by)?, OutputTarget::Stdout),
+        ]),
+        None => Ok(vec![Output::new(reporter, OutputTarget::Stdout)]),
+    }
+}
+
+/// Re-render a saved scan report
+fn run_report(file: &Path, format: &str, output: Option<&PathBuf>, verbose: bool, group_by: GroupBy) -> Result<(), String> {
+    let report = ScanReport::load(file)?;
+    let target = match output {
+        Some(path) => OutputTarget::File(path.clone()),
+        None => OutputTarget::Stdout,
+    };
+
+    Output::new(reporter::reporter_for(format, verbose, group_by)?, target).emit(&report)
+}
+
+/// Read the config file given with --config, or the project's .enveil.toml
+fn try_load_config(explicit: Option<&PathBuf>, project_path: &Path) -> Result<Config, String> {
+    match explicit {
+        Some(file) => Config::from_file(file),
+        None => Config::load(project_path),
+    }
+}
+
+/// Load the config, exiting on error
+fn load_config(explicit: Option<&PathBuf>, project_path: &Path) -> Config {
+    match try_load_config(explicit, project_path) {
+        Ok(config) => config,
+        Err(e) => {
+            emit!(stderr, "❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Scan a project and add its high risk files to .gitignore
+fn run_fix_gitignore(project_path: &Path, secure_dir: Option<&str>, config_file: Option<&PathBuf>) -> Result<(), String> {
+    let config = try_load_config(config_file, project_path)?;
+    let secure_dir = secure_dir
+        .or(config.protect.secure_dir.as_deref())
+        .unwrap_or(protector::DEFAULT_SECURE_DIR);
+    let report = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .build()?
+        .scan(project_path)?;
+
+    let risky: Vec<PathBuf> = report.files.iter()
+        .filter(|file| file.risk_level == "high")
+        .map(|file| PathBuf::from(&file.path))
+        .collect();
+    update_gitignore(project_path, &risky, &project_path.join(secure_dir))
+}
+
+/// Append the files, the secure directory and *.enc to .gitignore, warning
+/// about the files git already tracks
+fn update_gitignore(project_path: &Path, files: &[PathBuf], secure_dir: &Path) -> Result<(), String> {
+    let gitignore = Gitignore::new(project_path);
+    let added = gitignore.append(&gitignore.entries(files, secure_dir))?;
+
+    if added.is_empty() {
+        emit!(stdout, "ℹ️  .gitignore already covers the sensitive files");
+    } else {
+        emit!(stdout, "✅ Added {} entries to .gitignore:", added.len());
+        for entry in &added {
+            emit!(stdout, "   {}", entry);
+        }
+    }
+
+    for file in gitignore.tracked(files) {
+        emit!(stdout, 
+            "⚠️  {} is already tracked by git, ignoring it is not enough: run `git rm --cached {}` and rotate its secrets",
+            file.display(),
+            file.display()
+        );
+    }
+    Ok(())
+}
+
+/// Print the purge plan of a repository and optionally run it. Returns
+/// whether secrets were left in the history.
+fn run_purge(repo_path: &Path, execute: bool, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let config = try_load_config(config_file, repo_path)?;
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .build()?;
+    
+    emit!(stdout, "🔍 Scanning the git history of {}...\n", repo_path.display());
+    let plan = PurgePlan::build(&scanner, repo_path)?;
+    print!("{}", style::decorate(&plan.render_text()?, Stream::Stdout));
+    if plan.leaks.is_empty() {
+        return Ok(false);
+    }
+    
+    if !execute {
+        if let Some(path) = plan.write_replacements()? {
+            emit!(stdout, "\n🔑 {} holds the raw secrets: delete it once the history is rewritten", path.display());
+        }
+        return Ok(true);
+    }
+    
+    emit!(stdout, "\n🧹 Rewriting the history...");
+    plan.execute()?;
+    emit!(stdout, "✅ History rewritten. Check it, then force push after warning your collaborators:");
+    emit!(stdout, "   git push --force --all && git push --force --tags");
+    Ok(false)
+}
+
+/// Print the doctor checks of a project, returning whether none failed
+fn run_doctor(project_path: &Path, config_file: Option<&PathBuf>) -> bool {
+    let config_path = match config_file {
+        Some(file) => file.clone(),
+        None => project_path.join(init::CONFIG_FILE),
+    };
+    let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+    let checks = Doctor::new(project_path, &config_path, protector::DEFAULT_SECURE_DIR, passphrase).run();
+
+    emit!(stdout, "🩺 Enveil Doctor: {}\n", project_path.display());
+    for check in &checks {
+        let icon = match check.status {
+            Status::Ok => "✅",
+            Status::Warning => "⚠️ ",
+            Status::Failed => "❌",
+        };
+        emit!(stdout, "{} {}: {}", icon, check.name, check.message);
+        if let Some(fix) = &check.fix {
+            emit!(stdout, "   → {}", fix);
+        }
+    }
+
+    let failed = checks.iter().filter(|check| check.status == Status::Failed).count();
+    if failed > 0 {
+        emit!(stdout, "\n❌ {} check(s) failed", failed);
+    } else {
+        emit!(stdout, "\n✅ Enveil is set up correctly");
+    }
+    failed == 0
+}
+
+/// Watch a project until interrupted, printing an alert per file that gains secrets
+fn run_watch(
+    path: &Path,
+    format: &str,
+    redact_all: bool,
+    notify_urls: &[String],
+    notify_on: &str,
+    config_file: Option<&PathBuf>,
+) -> Result<(), String> {
+    let config = try_load_config(config_file, path)?;
+    let notifiers = notifiers(&config, notify_urls, notify_on)?;
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .redact_all(redact_all)
+        .build()?;
+
+    let mut watch = Watch::new(path, scanner)?;
+    let existing = watch.prime();
+
+    emit!(stderr, "👀 Watching {} (Ctrl+C to stop)", watch.root().display());
+    let source = watch.root().display().to_string();
+    if existing > 0 {
+        emit!(stderr, "⚠️  {} secret(s) already present, run 'enveil scan' for details", existing);
+    }
+
+    watch.run(|alert| {
+        if format == "json" {
+            if let Ok(json) = serde_json::to_string(alert) {
+                emit!(stdout, "{}", json);
+            }
+        } else {
+            emit!(stdout, "🚨 {} new secret(s) written to {}", alert.findings.len(), alert.path);
+            for finding in &alert.findings {
+                emit!(stdout, "   {}", reporter::format_finding(&alert.path, finding));
+            }
+        }
+        
+        let findings: Vec<Located> = alert.findings.iter().map(|finding| (alert.path.as_str(), finding)).collect();
+        send_notifications(&notifiers, &source, &findings);
+    })
+}
+
+/// Detector with the built-in rules and the custom rules of the config
+fn load_detector(config_file: Option<&PathBuf>) -> Result<SecretDetector, String> {
+    let config = try_load_config(config_file, Path::new("."))?;
+    let mut detector = SecretDetector::with_custom_rules(&config.rules)?;
+    detector.allow(&config.allowlist)?;
+    Ok(detector)
+}
+
+/// Generate a honeytoken, record it and print it to stdout
+fn run_honeytoken_generate(kind: HoneytokenType, note: Option<&str>, registry_path: &Path) -> Result<(), String> {
+    let mut registry = honeytoken::Registry::load(registry_path)?;
+    let (snippet, token) = registry.generate(kind, note)?;
+    let id = token.id.clone();
+    registry.save(registry_path)?;
+    
+    // Only the credential goes to stdout, to redirect it into a file
+    print!("{}", snippet);
+    emit!(stderr, "🍯 Honeytoken {} recorded in {}", id, registry_path.display());
+    Ok(())
+}
+
+/// Look for the honeytokens of the registry in a saved scan report.
+/// Returns whether none was found.
+fn run_honeytoken_check(report_path: &Path, registry_path: &Path) -> Result<bool, String> {
+    let registry = honeytoken::Registry::load(registry_path)?;
+    if registry.tokens.is_empty() {
+        return Err(format!("No honeytoken in {}: generate some with `enveil honeytoken generate`", registry_path.display()));
+    }
+    let report = ScanReport::load(report_path)?;
+    let sightings = registry.sightings(&report);
+    
+    if sightings.is_empty() {
+        emit!(stdout, "✅ None of the {} honeytoken(s) found in {}", registry.tokens.len(), report_path.display());
+        return Ok(true);
+    }
+    
+    emit!(stdout, "🚨 {} honeytoken match(es) in {}: the places they were planted in leaked", sightings.len(), report_path.display());
+    for sighting in &sightings {
+        let note = sighting.token.note.as_deref().map(|note| format!(" ({})", note)).unwrap_or_default();
+        emit!(stdout, 
+            "   {}:{} [{}] {} {}{}",
+            sighting.path, sighting.line_number, sighting.rule_id, sighting.token.kind.name(), sighting.token.id, note
+        );
+    }
+    Ok(false)
+}
+
+/// Answer scan API requests until the process is stopped
+fn run_serve(bind: &str, root: Option<&Path>, jobs: usize, config_file: Option<&PathBuf>) -> Result<(), String> {
+    let config = try_load_config(config_file, root.unwrap_or(Path::new(".")))?;
+    let scanner = Scanner::builder().options(ScanOptions::from_config(&config)).build()?;
+    let token = std::env::var(server::TOKEN_ENV).ok().filter(|token| !token.is_empty());
+    let open = token.is_none();
+    let api = ScanServer::new(scanner, root, token)?;
+    
+    let listener = tiny_http::Server::http(bind).map_err(|e| format!("Failed to listen on {}: {}", bind, e))?;
+    let address = listener.server_addr().to_ip().map(|address| address.to_string()).unwrap_or_else(|| bind.to_string());
+    emit!(stderr, "🚀 Listening on http://{} (Ctrl+C to stop)", address);
+    if open {
+        emit!(stderr, "⚠️  {} is not set: the API accepts every client that can reach it", server::TOKEN_ENV);
+    }
+    api.serve(listener, jobs);
+    Ok(())
+}
+
+/// Serve the language server protocol over stdin and stdout
+fn run_lsp(config_file: Option<&Path>) -> Result<(), String> {
+    let mut server = LanguageServer::new(Path::new("."), config_file)?;
+    lsp::run(&mut server, &mut std::io::stdin().lock(), &mut std::io::stdout().lock())
+}
+
+/// Print the trends of the scans recorded for a project
+fn run_trends(project_path: &Path, format: &str, limit: usize) -> Result<(), String> {
+    let path = project_path.join(history::HISTORY_FILE);
+    if !path.exists() {
+        return Err(format!("No scan history at {}: run enveil scan first", path.display()));
+    }
+    let trends = History::open(&path)?.trends(limit, now())?;
+    if format == "json" {
+        let json = serde_json::to_string_pretty(&trends)
+            .map_err(|e| format!("Failed to serialize trends: {}", e))?;
+        emit!(stdout, "{}", json);
+    } else {
+        print!("{}", style::decorate(&trends.render_text(), Stream::Stdout));
+    }
+    Ok(())
+}
+
+/// Current Unix timestamp in seconds
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Check that every rule detects its synthetic secret with the options of
+/// the config. Returns whether none missed.
+fn run_selftest(config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let config = try_load_config(config_file, Path::new("."))?;
+    let checks = selftest::run(&ScanOptions::from_config(&config))?;
+
+    emit!(stdout, "🩺 Scanning a synthetic secret for each of {} rule(s)\n", checks.len());
+    for check in &checks {
+        match check.outcome {
+            Outcome::Detected => emit!(stdout, "   ✅ {} ({})", check.id, check.name),
+            Outcome::Missed => emit!(stdout, "   ❌ {} ({}) did not detect its sample", check.id, check.name),
+            Outcome::Disabled => emit!(stdout, "   ℹ️  {} ({}) disabled by the config", check.id, check.name),
+            Outcome::NoSample => emit!(stdout, "   ⚠️  {} ({}) not tested: add an example to the rule", check.id, check.name),
+        }
+    }
+
+    let count = |outcome: Outcome| checks.iter().filter(|check| check.outcome == outcome).count();
+    let missed = count(Outcome::Missed);
+    emit!(stdout);
+    if missed > 0 {
+        emit!(stdout, "❌ {} rule(s) missed their sample: check the allowlist, minimum severity and confidence, and custom rules overriding built-in ones", missed);
+    } else {
+        emit!(stdout, "✅ {} rule(s) detected their sample", count(Outcome::Detected));
+    }
+    Ok(missed == 0)
+}
+
+/// Print the completion script of a shell. The ids of the rules, custom
+/// rules of the config included, complete `--enable-rules` and `--disable-rules`.
+fn run_completions(shell: Shell, config_file: Option<&PathBuf>) {
+    let mut command = Cli::command();
+    match load_detector(config_file) {
+        Ok(detector) => {
+            let ids: Vec<PossibleValue> = detector
+                .rules()
+                .into_iter()
+                .map(|rule| PossibleValue::new(rule.id).help(rule.name))
+                .collect();
+            command = command.mut_subcommand("scan", |scan| {
+                scan.mut_arg("enable_rules", |arg| arg.value_parser(PossibleValuesParser::new(ids.clone())))
+                    .mut_arg("disable_rules", |arg| arg.value_parser(PossibleValuesParser::new(ids)))
+            });
+        }
+        Err(e) => emit!(stderr, "⚠️  Rule ids not completed: {}", e),
+    }
+
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+/// Print every rule the detector runs
+fn run_rules_list(config_file: Option<&PathBuf>) -> Result<(), String> {
+    let rules = load_detector(config_file)?.rules();
+
+    emit!(stdout, "{:<8} {:<30} {:<9} {:<11} EXAMPLE", "ID", "NAME", "SEVERITY", "CONFIDENCE");
+    for rule in &rules {
+        emit!(stdout, 
+            "{:<8} {:<30} {:<9} {:<11} {}",
+            rule.id,
+            rule.name,
+            rule.severity,
+            rule.confidence,
+            rule.example.as_deref().unwrap_or("-")
+        );
+    }
+    emit!(stdout, "\n📋 {} rule(s)", rules.len());
+    Ok(())
+}
+
+/// Run one rule against sample input. Returns whether it matched.
+fn run_rules_test(rule: &str, input: &str, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let mut detector = load_detector(config_file)?;
+    detector.select_rules(&[rule.to_string()], &[])?;
+
+    let path = Path::new(input);
+    let (name, scan, content) = if path.is_file() {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        (input, detector.scan_content_at(path, &content), content)
+    } else {
+        ("<input>", detector.scan_content(input), input.to_string())
+    };
+
+    let info = &detector.rules()[0];
+    let label = format!("{} ({})", info.id, info.name);
+
+    if scan.findings.is_empty() {
+        emit!(stdout, "❌ {} did not match", label);
+        if let Some(requires) = info.requires.as_ref().filter(|_| !detector.inactive_rules(&content).is_empty()) {
+            emit!(stdout, "   The rule only applies to content that also matches: {}", requires);
+        }
+    } else {
+        emit!(stdout, "✅ {} matched {} time(s)", label, scan.findings.len());
+        for finding in &scan.findings {
+            emit!(stdout, "   {}", reporter::format_finding(name, finding));
+        }
+    }
+
+    if scan.suppressed > 0 {
+        emit!(stdout, "🙈 {} match(es) suppressed by enveil:ignore", scan.suppressed);
+    }
+    if scan.allowlisted > 0 {
+        emit!(stdout, "🙈 {} match(es) skipped as known dummy or allowlisted values", scan.allowlisted);
+    }
+    Ok(!scan.findings.is_empty())
+}
+
+/// Mask the secrets of a file or stdin, printing the result or writing it back
+fn run_mask(file: Option<&Path>, write: bool, config_file: Option<&PathBuf>) -> Result<usize, String> {
+    let content = match file {
+        Some(file) => std::fs::read_to_string(file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?,
+        None => {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            content
+        }
+    };
+
+    let config = try_load_config(config_file, Path::new("."))?;
+    let mut detector = SecretDetector::with_custom_rules(&config.rules)?;
+    detector.select_rules(&config.scan.enable_rules, &config.scan.disable_rules)?;
+    let result = detector.mask_content(&content, file);
+
+    match file {
+        Some(file) if write => std::fs::write(file, &result.content)
+            .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?,
+        _ => {
+            let mut stdout = std::io::stdout();
+            stdout
+                .write_all(result.content.as_bytes())
+                .and_then(|_| stdout.flush())
+                .map_err(|e| format!("Failed to write output: {}", e))?;
+        }
+    }
+
+    Ok(result.masked)
+}
+
+/// Arguments of the scan command
+struct ScanCommand<'a> {
+    path: &'a Path,
+    verbose: bool,
+    format: Option<&'a str>,
+    output: Option<&'a PathBuf>,
+    group_by: GroupBy,
+    rules: Option<&'a PathBuf>,
+    staged: bool,
+    exclude: &'a [String],
+    include: &'a [String],
+    fail_on: Option<&'a str>,
+    enable_rules: &'a [String],
+    disable_rules: &'a [String],
+    min_severity: Option<&'a str>,
+    min_confidence: Option<&'a str>,
+    redact_all: bool,
+    include_tests: bool,
+    baseline: Option<&'a PathBuf>,
+    update_baseline: bool,
+    compare_with: Option<&'a PathBuf>,
+    no_cache: bool,
+    tracked_only: bool,
+    since: Option<&'a str>,
+    max_file_size: Option<&'a str>,
+    scan_binary: bool,
+    hidden: bool,
+    env_vars: bool,
+    pids: &'a [u32],
+    home: bool,
+    enforce: bool,
+    no_history: bool,
+    notify: &'a [String],
+    notify_on: &'a str,
+}
+
+/// Run a scan and emit its reports. Returns the report and whether its
+/// findings reach the `--fail-on` threshold or violate a `--enforce` policy.
+fn run_scan(command: &ScanCommand, config_file: Option<&PathBuf>) -> Result<(ScanReport, bool), String> {
+    let stdin = command.path == Path::new(STDIN_PATH);
+    let project_path = if stdin || command.env_vars || command.home { Path::new(".") } else { command.path };
+    let mut config = try_load_config(config_file, project_path)?;
+    
+    if let Some(rules_file) = command.rules {
+        config.rules.extend(config::load_rules_file(rules_file)?);
+    }
+    
+    let (format, fail_on) = report_settings(&config, command.format, command.output, command.fail_on);
+    let outputs = build_outputs(format, command.output, command.verbose, command.group_by)?;
+    
+    let mut options = ScanOptions::from_config(&config);
+    options.exclude.extend_from_slice(command.exclude);
+    options.include.extend_from_slice(command.include);
+    options.enable_rules.extend_from_slice(command.enable_rules);
+    options.disable_rules.extend_from_slice(command.disable_rules);
+    if let Some(level) = command.min_severity {
+        options.min_severity = Some(level.to_string());
+    }
+    if let Some(level) = command.min_confidence {
+        options.min_confidence = Some(level.to_string());
+    }
+    options.include_tests |= command.include_tests;
+    options.tracked_only |= command.tracked_only;
+    if let Some(size) = command.max_file_size {
+        options.max_file_size = Some(size.to_string());
+    }
+    options.scan_binary |= command.scan_binary;
+    options.hidden |= command.hidden;
+    
+    // Logs and JSON consumers reading stderr get no progress bar
+    let progress = std::io::stderr().is_terminal() && !tracing::enabled!(Level::INFO) && !format.ends_with("json");
+    let scanner = Scanner::builder()
+        .options(options)
+        .verbose(command.verbose)
+        .redact_all(command.redact_all)
+        .progress(progress)
+        .build()?;
+    
+    let baseline_path = match command.baseline {
+        Some(path) => path.clone(),
+        None if project_path.is_file() => project_path.with_file_name(init::BASELINE_FILE),
+        None => project_path.join(init::BASELINE_FILE),
+    };
+    let baseline = match !command.update_baseline && (command.baseline.is_some() || baseline_path.exists()) {
+        true => Some(Baseline::load(&baseline_path)?),
+        false => None,
+    };
+    
+    // NDJSON on stdout prints the findings of each file as soon as it is
+    // scanned, instead of the whole report at the end
+    let walked = command.since.is_none() && !command.env_vars && !command.home && !command.staged && !stdin;
+    let streamed = walked && format == "ndjson" && command.output.is_none() && !command.update_baseline;
+    let mut on_file = |file: &SecretFileReport| {
+        if !streamed {
+            return;
+        }
+        let path = Path::new(&file.path)
+            .strip_prefix(command.path)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.path.clone());
+        let findings = file.findings.iter().filter(|f| !baseline.as_ref().is_some_and(|b| b.contains(f)));
+        for finding in findings {
+            match reporter::ndjson_finding(&path, finding) {
+                Ok(line) => emit!(stdout, "{}", line),
+                Err(e) => emit!(stderr, "⚠️  {}", e),
+            }
+        }
+    };
+    
+    let mut previous = None;
+    let mut report = if let Some(rev) = command.since {
+        let changed = scanner.scan_since(project_path, rev)?;
+        previous = Some(changed.before);
+        changed.after
+    } else if command.env_vars {
+        let mut environments = vec![(environ::CURRENT_NAME.to_string(), environ::current())];
+        for pid in command.pids {
+            match environ::of_process(*pid) {
+                Ok(vars) => environments.push((environ::process_path(*pid), vars)),
+                Err(e) => emit!(stderr, "⚠️  {}", e),
+            }
+        }
+        scanner.scan_environments(&environments)
+    } else if command.home {
+        let home = home::home_dir().ok_or("Could not find the home directory (HOME is not set)")?;
+        let files = home::credential_files(&home, std::env::var_os("KUBECONFIG").as_deref());
+        emit!(stderr, "🔍 Auditing {} credential file(s) in {}", files.len(), home.display());
+        scanner.scan_files(&home, &files)
+    } else if command.staged {
+        scanner.scan_staged(command.path)?
+    } else if stdin {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        scanner.scan_text(Path::new(STDIN_NAME), &content)
+    } else if command.no_cache || command.path.is_file() {
+        scanner.scan_streamed(command.path, None, &mut on_file)?
+    } else {
+        let mut cache = ScanCache::load(command.path, &scanner.cache_settings());
+        let report = scanner.scan_streamed(command.path, Some(&mut cache), &mut on_file)?;
+        if let Err(e) = cache.save(command.path) {
+            emit!(stderr, "⚠️  {}", e);
+        }
+        report
+    };
+    
+    if !stdin && !command.staged && !command.env_vars && !command.home {
+        report.mark_committed(command.path);
+    }
+    // Paths relative to the scanned directory read the same on every machine
+    if !stdin && !command.env_vars && !command.home && command.path.is_dir() {
+        report.relative_to(command.path);
+        if let Some(before) = &mut previous {
+            before.relative_to(command.path);
+        }
+    }
+    
+    if command.update_baseline {
+        let baseline = Baseline::from_report(&report);
+        baseline.save(&baseline_path)?;
+        emit!(stdout, "📋 {} secret(s) written to baseline {}", baseline.findings.len(), baseline_path.display());
+        return Ok((report, false));
+    }
+    
+    if let Some(baseline) = &baseline {
+        report.apply_baseline(baseline);
+    }
+    
+    if streamed {
+        emit!(stdout, "{}", reporter::ndjson_summary(&report));
+    } else {
+        reporter::emit_all(&outputs, &report)?;
+    }
+    
+    // Only scans of the whole project make a meaningful history
+    let project_scan = command.path.is_dir() && !stdin && !command.staged && !command.env_vars && !command.home;
+    if project_scan && command.since.is_none() && !command.no_history {
+        let recorded = History::open(&command.path.join(history::HISTORY_FILE))
+            .and_then(|history| history.record(&report, now()));
+        if let Err(e) = recorded {
+            emit!(stderr, "⚠️  {}", e);
+        }
+    }
+    
+    if let Some(old) = command.compare_with {
+        previous = Some(ScanReport::load(old)?);
+    }
+    
+    // Keep machine readable reports on stdout parseable
+    let to_stdout = format == "text" || command.output.is_some();
+    let mut fails = report.fails_on(fail_on);
+    let mut new = None;
+    if let Some(old) = previous {
+        let diff = ScanDiff::between(&old, &report);
+        if to_stdout {
+            print!("{}", style::decorate(&diff.render_text(), Stream::Stdout));
+        } else {
+            eprint!("{}", style::decorate(&diff.render_text(), Stream::Stderr));
+        }
+        fails = fail_on != "none" && diff.new_at_or_above(fail_on) > 0;
+        new = Some(diff.new);
+    }
+    
+    if command.enforce {
+        let summary = enforce_policies(&config.policy, &report, new.as_deref())?;
+        if to_stdout {
+            print!("{}", style::decorate(&summary.text, Stream::Stdout));
+        } else {
+            eprint!("{}", style::decorate(&summary.text, Stream::Stderr));
+        }
+        fails |= summary.violated;
+    }
+    
+    let notifiers = notifiers(&config, command.notify, command.notify_on)?;
+    if !notifiers.is_empty() {
+        // With a compared scan, only new findings are worth a notification
+        let findings: Vec<Located> = match &new {
+            Some(new) => new.iter().map(|entry| (entry.path.as_str(), &entry.finding)).collect(),
+            None => report.secrets.iter()
+                .flat_map(|file| file.findings.iter().map(move |finding| (file.path.as_str(), finding)))
+                .collect(),
+        };
+        let source = if stdin { STDIN_NAME.to_string() } else { command.path.display().to_string() };
+        send_notifications(&notifiers, &source, &findings);
+    }
+    
+    Ok((report, fails))
+}
+
+/// Notifiers of the config and of the `--notify` URLs
+fn notifiers(config: &Config, urls: &[String], min_severity: &str) -> Result<Vec<Notifier>, String> {
+    let mut notifiers = config.notify.iter().map(Notifier::from_config).collect::<Result<Vec<_>, _>>()?;
+    notifiers.extend(urls.iter().map(|url| Notifier::new(url, min_severity)));
+    Ok(notifiers)
+}
+
+/// POST findings to each notifier that pages on them. A failed notification
+/// is a warning, so it does not hide the result of the scan.
+fn send_notifications(notifiers: &[Notifier], source: &str, findings: &[Located]) {
+    for notifier in notifiers {
+        match notifier.notify(source, findings) {
+            Ok(true) => emit!(stderr, "📣 Notified {}", notifier::redact_url(&notifier.url)),
+            Ok(false) => {}
+            Err(e) => emit!(stderr, "⚠️  {}", e),
+        }
+    }
+}
+
+/// Outcome of `scan --enforce`
+struct PolicySummary {
+    text: String,
+    violated: bool,
+}
+
+/// Check the policies of the config against a scan report
+fn enforce_policies(policies: &[Policy], report: &ScanReport, new: Option<&[LocatedFinding]>) -> Result<PolicySummary, String> {
+    if policies.is_empty() {
+        return Ok(PolicySummary {
+            text: "\n⚠️  --enforce: no [[policy]] in the config\n".to_string(),
+            violated: false,
+        });
+    }
+    
+    let violations = policy::evaluate(policies, report, new)?;
+    let mut text = String::from("\n");
+    if violations.is_empty() {
+        text.push_str(&format!("✅ All {} policies passed\n", policies.len()));
+    }
+    for violation in &violations {
+        text.push_str(&format!("🚨 Policy violated: {}\n", violation.summary()));
+        for location in &violation.locations {
+            text.push_str(&format!("   {}\n", location));
+        }
+    }
+    Ok(PolicySummary { text, violated: !violations.is_empty() })
+}
+
+/// Run the checks of a git hook from the repository root, with the
+/// arguments git passed to it. Returns whether the commit or push must be aborted.
+fn run_hook(hook: Hook, args: &[String], allow_bypass: bool, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    tracing::info!(hook = hook.name(), args = ?args, "running hook");
+    emit!(stdout, "🔍 Scanning {} for secrets...", hook.target());
+    
+    let (report, abort) = match hook {
+        Hook::CommitMsg => {
+            // git passes the file holding the message
+            let file = match args.first() {
+                Some(file) => file,
+                None => return Err("commit-msg hook run without the message file".to_string()),
+            };
+            scan_commit_message(Path::new(file), config_file)?
+        }
+        Hook::PreCommit | Hook::PrePush => scan_for_hook(hook, config_file)?,
+        Hook::PreReceive => scan_received_push(config_file)?,
+    };
+    
+    let skip = std::env::var(SKIP_ENV).is_ok_and(|value| value == "1");
+    if abort && skip {
+        if allow_bypass {
+            // Staged with the commit, so the bypass shows in review
+            audit::record_bypass(Path::new("."), hook.name(), &report, hook == Hook::PreCommit)?;
+            emit!(stdout);
+            emit!(stdout, 
+                "⚠️  {}=1: {} allowed despite {} secret(s), recorded in {}",
+                SKIP_ENV,
+                hook.operation(),
+                report.secrets_found,
+                audit::AUDIT_LOG
+            );
+            return Ok(false);
+        }
+        emit!(stdout, "⚠️  {}=1 ignored: install the hooks with --allow-bypass to allow bypasses", SKIP_ENV);
+    }
+    
+    if abort {
+        emit!(stdout);
+        emit!(stdout, "❌ ABORTING {}: secrets detected in {}!", hook.operation().to_uppercase(), hook.target());
+        if hook == Hook::PreReceive {
+            // --no-verify does not skip server hooks
+            emit!(stdout, "Remove the secrets from the commits (they stay in the history otherwise) and push again");
+        } else {
+            emit!(stdout, "To {} anyway, use: git {} --no-verify", hook.operation(), hook.operation());
+            emit!(stdout, "Or fix the secrets and {} again", hook.operation());
+        }
+    } else {
+        emit!(stdout, "✅ No secrets detected in {}", hook.target());
+    }
+    Ok(abort)
+}
+
+/// Scan the staged files (pre-commit) or the project (pre-push)
+fn scan_for_hook(hook: Hook, config_file: Option<&PathBuf>) -> Result<(ScanReport, bool), String> {
+    let command = ScanCommand {
+        path: Path::new("."),
+        verbose: false,
+        format: None,
+        output: None,
+        group_by: GroupBy::File,
+        rules: None,
+        staged: hook == Hook::PreCommit,
+        exclude: &[],
+        include: &[],
+        fail_on: None,
+        enable_rules: &[],
+        disable_rules: &[],
+        min_severity: None,
+        min_confidence: None,
+        redact_all: false,
+        include_tests: false,
+        baseline: None,
+        update_baseline: false,
+        compare_with: None,
+        no_cache: false,
+        tracked_only: false,
+        since: None,
+        max_file_size: None,
+        scan_binary: false,
+        hidden: false,
+        env_vars: false,
+        pids: &[],
+        home: false,
+        enforce: false,
+        no_history: true,
+        notify: &[],
+        notify_on: notifier::DEFAULT_MIN_SEVERITY,
+    };
+    run_scan(&command, config_file)
+}
+
+/// Scan the blobs of the refs a bare repository is receiving, read from
+/// the `<old> <new> <ref>` lines git writes to a pre-receive hook
+fn scan_received_push(config_file: Option<&PathBuf>) -> Result<(ScanReport, bool), String> {
+    let mut updates = String::new();
+    std::io::stdin()
+        .read_to_string(&mut updates)
+        .map_err(|e| format!("Failed to read stdin: {}", e))?;
+    
+    let revs: Vec<String> = updates
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        // An all-zero id deletes the ref
+        .filter(|new| !new.chars().all(|c| c == '0'))
+        .map(str::to_string)
+        .collect();
+    
+    let config = try_load_config(config_file, Path::new("."))?;
+    let (_, fail_on) = report_settings(&config, None, None, None);
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .build()?;
+    let report = scanner.scan_pushed(Path::new("."), &revs)?;
+    reporter::emit_all(&build_outputs("text", None, false, GroupBy::File)?, &report)?;
+    
+    let fails = report.fails_on(fail_on);
+    Ok((report, fails))
+}
+
+/// Scan a commit message, ignoring the comment lines git strips from it
+fn scan_commit_message(file: &Path, config_file: Option<&PathBuf>) -> Result<(ScanReport, bool), String> {
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    // Blank the comments instead of dropping them, to keep line numbers
+    let message: Vec<&str> = content
+        .lines()
+        .map(|line| if line.starts_with('#') { "" } else { line })
+        .collect();
+    
+    let config = try_load_config(config_file, Path::new("."))?;
+    let (_, fail_on) = report_settings(&config, None, None, None);
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .build()?;
+    let report = scanner.scan_text(Path::new("commit message"), &message.join("\n"));
+    reporter::emit_all(&build_outputs("text", None, false, GroupBy::File)?, &report)?;
+    
+    let fails = report.fails_on(fail_on);
+    Ok((report, fails))
+}
+
+/// Output format and `--fail-on` level of a scan, from its flags or the config
+fn report_settings<'a>(
+    config: &'a Config,
+    format: Option<&'a str>,
+    output: Option<&PathBuf>,
+    fail_on: Option<&'a str>,
+) -> (&'a str, &'a str) {
+    // A report file named *.json is saved as JSON unless a format is given
+    let json_output = output
+        .is_some_and(|file| file.extension().is_some_and(|e| e == "json"))
+        .then_some("json");
+    let format = format
+        .or(config.scan.format.as_deref())
+        .or(json_output)
+        .unwrap_or("text");
+    let fail_on = fail_on
+        .or(config.scan.fail_on.as_deref())
+        .unwrap_or("high");
+
+    (format, fail_on)
+}
+
+/// Scan a git repository that is not checked out locally and emit its
+/// reports. Returns whether the findings reach the `--fail-on` threshold.
+fn run_scan_remote(
+    url: &str,
+    branch: Option<&str>,
+    format: Option<&str>,
+    output: Option<&PathBuf>,
+    verbose: bool,
+    fail_on: Option<&str>,
+    config_file: Option<&PathBuf>,
+) -> Result<bool, String> {
+    // The auditing side's config: the audited repository's own allowlist
+    // and rule settings are not trusted
+    let config = try_load_config(config_file, Path::new("."))?;
+    let (format, fail_on) = report_settings(&config, format, output, fail_on);
+    let outputs = build_outputs(format, output, verbose, GroupBy::File)?;
+
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .verbose(verbose)
+        .build()?;
+    emit!(stderr, "📥 Cloning {}", url);
+    let report = scanner.scan_remote(url, branch, None)?;
+    reporter::emit_all(&outputs, &report)?;
+
+    Ok(report.fails_on(fail_on))
+}
+
+/// Arguments of the scan-org command
+struct ScanOrgCommand<'a> {
+    org: &'a str,
+    token: Option<String>,
+    api_url: &'a str,
+    include_forks: bool,
+    include_archived: bool,
+    jobs: usize,
+    verbose: bool,
+    format: Option<&'a str>,
+    output: Option<&'a PathBuf>,
+    fail_on: Option<&'a str>,
+}
+
+/// Audit the repositories of a GitHub organization and write the consolidated
+/// report. Returns whether any repository reaches the `--fail-on` threshold.
+fn run_scan_org(command: &ScanOrgCommand, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let config = try_load_config(config_file, Path::new("."))?;
+    let (format, fail_on) = report_settings(&config, command.format, command.output, command.fail_on);
+
+    let github = GitHub::new(command.api_url, forge_token(&command.token, GITHUB_TOKEN_ENV));
+
+    let repositories: Vec<_> = github
+        .org_repositories(command.org)?
+        .into_iter()
+        .filter(|repo| command.include_forks || !repo.fork)
+        .filter(|repo| command.include_archived || !repo.archived)
+        .collect();
+    emit!(stderr, "📥 Scanning {} repositories of {}", repositories.len(), command.org);
+
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .verbose(command.verbose)
+        .build()?;
+    let report = OrgReport {
+        org: command.org.to_string(),
+        repositories: forge::scan_repositories(&scanner, &repositories, github.git_header().as_deref(), command.jobs),
+    };
+
+    let rendered = match format {
+        "json" => serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("Failed to serialize report: {}", e))?,
+        "text" => report.render_text(),
+        other => return Err(format!("Unsupported format for scan-org: {} (expected text or json)", other)),
+    };
+    match command.output {
+        Some(file) => {
+            std::fs::write(file, rendered)
+                .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+            emit!(stderr, "📋 Report written to {}", file.display());
+        }
+        None => emit!(stdout, "{}", rendered),
+    }
+
+    Ok(report.fails_on(fail_on))
+}
+
+/// Arguments of the scan-pr command
+struct ScanPrCommand<'a> {
+    github: Option<&'a str>,
+    gitlab: Option<&'a str>,
+    token: Option<String>,
+    api_url: Option<&'a str>,
+    comment: bool,
+    verbose: bool,
+    format: Option<&'a str>,
+    output: Option<&'a PathBuf>,
+    fail_on: Option<&'a str>,
+}
+
+/// Split a `repo#number` (or GitLab `project!iid`) reference
+fn parse_change_reference(reference: &str, separator: char) -> Result<(&str, u64), String> {
+    reference
+        .rsplit_once(separator)
+        .and_then(|(repo, number)| Some((repo, number.parse().ok()?)))
+        .filter(|(repo, _)| repo.contains('/'))
+        .ok_or_else(|| format!("Invalid reference: {} (expected e.g. owner/repo{}123)", reference, separator))
+}
+
+/// Access token from the flag or the forge's environment variable
+fn forge_token(token: &Option<String>, env: &str) -> Option<String> {
+    token
+        .clone()
+        .or_else(|| std::env::var(env).ok())
+        .filter(|token| !token.is_empty())
+}
+
+/// Scan the lines added by a pull or merge request and emit its reports,
+/// optionally commenting the findings on it. Returns whether the findings
+/// reach the `--fail-on` threshold.
+fn run_scan_pr(command: &ScanPrCommand, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let config = try_load_config(config_file, Path::new("."))?;
+    let (format, fail_on) = report_settings(&config, command.format, command.output, command.fail_on);
+    let outputs = build_outputs(format, command.output, command.verbose, GroupBy::File)?;
+
+    let scanner = Scanner::builder()
+        .options(ScanOptions::from_config(&config))
+        .verbose(command.verbose)
+        .build()?;
+
+    let report = match (command.github, command.gitlab) {
+        (Some(reference), _) => {
+            let (repo, number) = parse_change_reference(reference, '#')?;
+            let github = GitHub::new(
+                command.api_url.unwrap_or(forge::GITHUB_API),
+                forge_token(&command.token, GITHUB_TOKEN_ENV),
+            );
+            let report = forge::scan_changes(&scanner, &github.pull_request_files(repo, number)?)?;
+            if command.comment && report.secrets_found > 0 {
+                github.comment(repo, number, &forge::review_comment(&report))?;
+                emit!(stderr, "💬 Commented the findings on {}", reference);
+            }
+            report
+        }
+        (None, Some(reference)) => {
+            // GitLab writes merge requests as project!iid
+            let (project, iid) = parse_change_reference(reference, '!')?;
+            let gitlab = GitLab::new(
+                command.api_url.unwrap_or(forge::GITLAB_API),
+                forge_token(&command.token, GITLAB_TOKEN_ENV),
+            );
+            let report = forge::scan_changes(&scanner, &gitlab.merge_request_files(project, iid)?)?;
+            if command.comment && report.secrets_found > 0 {
+                gitlab.comment(project, iid, &forge::review_comment(&report))?;
+                emit!(stderr, "💬 Commented the findings on {}", reference);
+            }
+            report
+        }
+        (None, None) => return Err("Either --github or --gitlab is required".to_string()),
+    };
+    reporter::emit_all(&outputs, &report)?;
+
+    Ok(report.fails_on(fail_on))
+}
+
+/// Arguments of the scan-image command
+struct ScanImageCommand<'a> {
+    image: &'a Path,
+    verbose: bool,
+    format: Option<&'a str>,
+    output: Option<&'a PathBuf>,
+    fail_on: Option<&'a str>,
+    max_file_size: Option<&'a str>,
+    scan_binary: bool,
+}
+
+/// Scan a container image and emit its reports. Returns whether the
+/// findings reach the `--fail-on` threshold.
+fn run_scan_image(command: &ScanImageCommand, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let config = try_load_config(config_file, Path::new("."))?;
+    let (format, fail_on) = report_settings(&config, command.format, command.output, command.fail_on);
+    let outputs = build_outputs(format, command.output, command.verbose, GroupBy::File)?;
+
+    let mut options = ScanOptions::from_config(&config);
+    if let Some(size) = command.max_file_size {
+        options.max_file_size = Some(size.to_string());
+    }
+    options.scan_binary |= command.scan_binary;
+
+    let scanner = Scanner::builder()
+        .options(options)
+        .verbose(command.verbose)
+        .build()?;
+    let report = image::scan_image(&scanner, command.image)?;
+    reporter::emit_all(&outputs, &report)?;
+
+    Ok(report.fails_on(fail_on))
+}
+
+/// Compare two saved scans. Returns whether new secrets reach `fail_on`.
+fn run_diff(old: &Path, new: &Path, format: &str, fail_on: &str) -> Result<bool, String> {
+    let diff = ScanDiff::between(&ScanReport::load(old)?, &ScanReport::load(new)?);
+
+    if format == "json" {
+        let json = serde_json::to_string_pretty(&diff)
+            .map_err(|e| format!("Failed to serialize diff: {}", e))?;
+        emit!(stdout, "{}", json);
+    } else {
+        print!("{}", style::decorate(&diff.render_text(), Stream::Stdout));
+    }
+
+    Ok(fail_on != "none" && diff.new_at_or_above(fail_on) > 0)
+}
+
+/// Read a passphrase from `ENVEIL_PASSPHRASE` or prompt for it without echo
+fn read_passphrase(confirm: bool) -> Result<String, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+
+    let passphrase = rpassword::prompt_password("🔑 Passphrase: ")
+        .map_err(|e| format!("Failed to read passphrase: {}", e))?;
+
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+
+    if confirm {
+        let again = rpassword::prompt_password("🔑 Confirm passphrase: ")
+            .map_err(|e| format!("Failed to read passphrase: {}", e))?;
+        if again != passphrase {
+            return Err("Passphrases do not match".to_string());
+        }
+    }
+
+    Ok(passphrase)
+}
+
+/// Key for `enveil rekey`: read from a file, derived from a new passphrase
+/// (ENVEIL_NEW_PASSPHRASE or prompted twice), or generated
+fn new_key(key_file: Option<&Path>, passphrase: bool) -> Result<NewKey, String> {
+    if let Some(path) = key_file {
+        let encoded = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read key file {}: {}", path.display(), e))?;
+        warn_if_shared(path);
+        return protector::decode_key(encoded.trim()).map(NewKey::raw);
+    }
+
+    if !passphrase {
+        return Ok(NewKey::raw(protector::generate_key()));
+    }
+
+    let passphrase = match std::env::var(NEW_PASSPHRASE_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => passphrase,
+        _ => {
+            let passphrase = rpassword::prompt_password("🔑 New passphrase: ")
+                .map_err(|e| format!("Failed to read passphrase: {}", e))?;
+            let again = rpassword::prompt_password("🔑 Confirm new passphrase: ")
+                .map_err(|e| format!("Failed to read passphrase: {}", e))?;
+            if passphrase.is_empty() {
+                return Err("Passphrase must not be empty".to_string());
+            }
+            if again != passphrase {
+                return Err("Passphrases do not match".to_string());
+            }
+            passphrase
+        }
+    };
+
+    NewKey::from_passphrase(&passphrase)
+}
+
+/// Encryption key given with --key-file, --key or ENVEIL_KEY, in that order.
+/// --key is refused from a terminal unless `insecure` is set, as it ends up
+/// in shell history and `ps`.
+fn explicit_key(key: Option<&str>, key_file: Option<&Path>, insecure: bool) -> Result<Option<[u8; 32]>, String> {
+    if let Some(path) = key_file {
+        let encoded = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read key file {}: {}", path.display(), e))?;
+        warn_if_shared(path);
+        return protector::decode_key(encoded.trim()).map(Some);
+    }
+
+    if let Some(encoded) = key {
+        if std::io::stdin().is_terminal() && !insecure {
+            return Err(
+                "--key leaks the key into shell history and `ps`: use --key-file or ENVEIL_KEY (or pass --insecure-key-arg)"
+                    .to_string(),
+            );
+        }
+        return protector::decode_key(encoded).map(Some);
+    }
+
+    match std::env::var(KEY_ENV) {
+        Ok(encoded) if !encoded.trim().is_empty() => protector::decode_key(encoded.trim()).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Key to decrypt protected files: the identity or the passphrase when
+/// given, otherwise the explicit key, then the one in the OS keychain
+fn decryption_key(
+    project_path: &Path,
+    key: Option<&str>,
+    key_file: Option<&Path>,
+    insecure: bool,
+    passphrase: bool,
+    identity: Option<&Path>,
+) -> Result<DecryptionKey, String> {
+    if let Some(path) = identity {
+        warn_if_shared(path);
+        return Identity::load(path).map(DecryptionKey::Identity);
+    }
+
+    if passphrase {
+        return read_passphrase(false).map(DecryptionKey::Passphrase);
+    }
+
+    match explicit_key(key, key_file, insecure)? {
+        Some(key) => Ok(DecryptionKey::Raw(key)),
+        None => keychain::load_key(project_path)?.map(DecryptionKey::Raw).ok_or_else(|| {
+            "No --key, --key-file or ENVEIL_KEY given and no key stored in the OS keychain for this project".to_string()
+        }),
+    }
+}
+
+/// Warn when a key file is readable by other users
+fn warn_if_shared(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                emit!(stderr, "⚠️  Key file {} is readable by other users (chmod 600 it)", path.display());
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Key of the git filter: the key file, ENVEIL_KEY, then the OS keychain
+/// entry of the repository (git runs filters from its root)
+fn filter_key(key_file: Option<&Path>) -> Result<Option<[u8; 32]>, String> {
+    match explicit_key(None, key_file, false)? {
+        Some(key) => Ok(Some(key)),
+        None => keychain::load_key(Path::new(".")),
+    }
+}
+
+/// Make sure the filter of a repository has a key, storing a new one in the
+/// OS keychain when neither ENVEIL_KEY nor a stored key exists
+fn ensure_filter_key(repo_path: &Path) -> Result<(), String> {
+    if explicit_key(None, None, false)?.is_some() {
+        emit!(stdout, "ℹ️  The filter reads the key from ENVEIL_KEY: keep it set when running git");
+        return Ok(());
+    }
+    if keychain::load_key(repo_path)?.is_some() {
+        emit!(stdout, "🔐 The filter uses the encryption key stored in the OS keychain");
+        return Ok(());
+    }
+    
+    let key = protector::generate_key();
+    keychain::store_key(repo_path, &key)?;
+    emit!(stdout, "🔐 New filter key stored in the OS keychain");
+    emit!(stderr, "⚠️  Share it with whoever needs to read the files (save this!): {}", protector::encode_key(&key));
+    Ok(())
+}
+
+/// Reuse the key already stored in the keychain for a project or one of its
+/// environments, or generate a new one
+fn keychain_key(project_path: &Path, environment: Option<&config::Environment>) -> [u8; 32] {
+    let stored = match environment {
+        Some(environment) => keychain::load_environment_key(project_path, &environment.name),
+        None => keychain::load_key(project_path),
+    };
+    match stored {
+        Ok(Some(key)) => {
+            emit!(stdout, "🔐 Using the encryption key stored in the OS keychain");
+            key
+        }
+        Ok(None) => protector::generate_key(),
+        Err(e) => {
+            emit!(stderr, "⚠️  {}", e);
+            protector::generate_key()
+        }
+    }
+}
+
+/// Key for encrypting .env values without an explicit one: the key stored
+/// in the OS keychain, else a new key that is stored there or printed
+fn value_key(project_path: &Path, store: bool) -> Result<[u8; 32], String> {
+    if let Some(key) = keychain::load_key(project_path)? {
+        emit!(stdout, "🔐 Using the encryption key stored in the OS keychain");
+        return Ok(key);
+    }
+    
+    let key = protector::generate_key();
+    if store {
+        keychain::store_key(project_path, &key)?;
+        emit!(stdout, "🔐 New encryption key stored in the OS keychain");
+    } else {
+        emit!(stderr, "⚠️  Generated encryption key (save this!): {}", protector::encode_key(&key));
+    }
+    Ok(key)
+}
+
+/// Encrypt .env files into a .env.vault bundle, reusing the keys of the
+/// environments already in the keys file and adding new ones to it
+fn run_env_vault_build(files: &[PathBuf], vault_path: &Path, keys_path: &Path) -> Result<(), String> {
+    let read = |path: &Path| std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+    let mut vault = match vault_path.exists() {
+        true => EnvVault::parse(&read(vault_path)?),
+        false => EnvVault::default(),
+    };
+    let mut keys = match keys_path.exists() {
+        true => dotenv_vault::parse_keys(&read(keys_path)?)?,
+        false => Vec::new(),
+    };
+    
+    for file in files {
+        let environment = dotenv_vault::environment_of(file);
+        let key = match keys.iter().find(|key| key.environment == environment) {
+            Some(key) => key.clone(),
+            None => {
+                let key = DotenvKey::generate(&environment);
+                keys.push(key.clone());
+                key
+            }
+        };
+        vault.seal(&read(file)?, &key)?;
+        emit!(stdout, "🔒 {} -> {} ({})", file.display(), vault_path.display(), environment);
+    }
+    
+    std::fs::write(keys_path, dotenv_vault::render_keys(&keys))
+        .map_err(|e| format!("Failed to write {}: {}", keys_path.display(), e))?;
+    protector::restrict_permissions(keys_path, protector::SECURE_FILE_MODE)?;
+    std::fs::write(vault_path, vault.render())
+        .map_err(|e| format!("Failed to write {}: {}", vault_path.display(), e))?;
+    
+    emit!(stdout, "🔑 Keys in {}: set {} to one of them where the app runs", keys_path.display(), dotenv_vault::KEY_ENV);
+    emit!(stdout, "ℹ️  Commit {} but never {}", vault_path.display(), keys_path.display());
+    Ok(())
+}
+
+/// Decrypt an environment of a .env.vault bundle with a key from DOTENV_KEY,
+/// or else from the keys file
+fn run_env_vault_decrypt(vault_path: &Path, keys_path: &Path, environment: Option<&str>) -> Result<String, String> {
+    // Without --environment: the first key of DOTENV_KEY, else development
+    let (keys, default_environment) = match std::env::var(dotenv_vault::KEY_ENV) {
+        Ok(value) if !value.trim().is_empty() => {
+            let keys = DotenvKey::parse_list(&value)?;
+            let first = keys.first().map(|key| key.environment.clone()).unwrap_or_default();
+            (keys, first)
+        }
+        _ => {
+            let content = std::fs::read_to_string(keys_path).map_err(|e| {
+                format!("{} is not set and {} cannot be read: {}", dotenv_vault::KEY_ENV, keys_path.display(), e)
+            })?;
+            (dotenv_vault::parse_keys(&content)?, "development".to_string())
+        }
+    };
+    
+    let wanted = environment.map(str::to_lowercase).unwrap_or(default_environment);
+    let key = keys
+        .iter()
+        .find(|key| key.environment == wanted)
+        .ok_or_else(|| format!("No key for the {} environment", wanted))?;
+    
+    let content = std::fs::read_to_string(vault_path)
+        .map_err(|e| format!("Failed to read {}: {}", vault_path.display(), e))?;
+    EnvVault::parse(&content).open(key)
+}
+
+/// Arguments of `enveil run`
+struct RunCommand<'a> {
+    project_path: &'a Path,
+    secure_path: &'a Path,
+    environment: Option<&'a config::Environment>,
+    key: Option<&'a str>,
+    key_file: Option<&'a Path>,
+    insecure_key_arg: bool,
+    passphrase: bool,
+    identity: Option<&'a Path>,
+    command: &'a [String],
+}
+
+/// Key of a run, found as for other commands except that the key file and
+/// the OS keychain entry of the environment come before those of the project
+fn run_key(run: &RunCommand) -> Result<DecryptionKey, String> {
+    let environment = match run.environment {
+        Some(environment) if !run.passphrase && run.identity.is_none() => environment,
+        _ => return decryption_key(run.project_path, run.key, run.key_file, run.insecure_key_arg, run.passphrase, run.identity),
+    };
+    
+    let key_file = match (run.key, run.key_file) {
+        (None, None) => environment.key_file.as_ref().map(|file| run.project_path.join(file)),
+        (_, key_file) => key_file.map(Path::to_path_buf),
+    };
+    if let Some(key) = explicit_key(run.key, key_file.as_deref(), run.insecure_key_arg)? {
+        return Ok(DecryptionKey::Raw(key));
+    }
+    match keychain::load_environment_key(run.project_path, &environment.name)? {
+        Some(key) => Ok(DecryptionKey::Raw(key)),
+        None => decryption_key(run.project_path, None, None, false, false, None),
+    }
+}
+
+/// Load the .env files of an environment into memory, decrypting them and
+/// their inline encrypted values as needed, and run the command with their
+/// variables, later files overriding earlier ones. Returns the exit code of
+/// the command.
+fn run_with_env(run: &RunCommand) -> Result<i32, String> {
+    let file_names: Vec<&str> = match run.environment {
+        Some(environment) => environment.files.iter().map(String::as_str).collect(),
+        None => vec![".env"],
+    };
+    let mut key = None;
+    let mut vars: Vec<(String, String)> = Vec::new();
+    
+    for file_name in file_names {
+        let content = match runner::locate(run.project_path, run.secure_path, file_name)? {
+            EnvSource::Encrypted(path) => {
+                let input = std::fs::File::open(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let file_key = match key.take() {
+                    Some(file_key) => file_key,
+                    None => run_key(run)?,
+                };
+                let mut plaintext = Vec::new();
+                file_key.decrypt_stream(std::io::BufReader::new(input), &mut plaintext)?;
+                key = Some(file_key);
+                plaintext
+            }
+            EnvSource::Moved(path) | EnvSource::Plain(path) => {
+                std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+            }
+            EnvSource::Vaulted(entry) => Vault::from_env()?.pull(&entry)?,
+        };
+        let content = String::from_utf8(content).map_err(|_| format!("{} is not text", file_name))?;
+        
+        let raw_key = if content.contains(env::ENCRYPTED_PREFIX) {
+            let file_key = match key.take() {
+                Some(file_key) => file_key,
+                None => run_key(run)?,
+            };
+            let raw_key = match &file_key {
+                DecryptionKey::Raw(raw_key) => *raw_key,
+                _ => return Err(format!("{} has values encrypted inline, which need --key, --key-file or ENVEIL_KEY", file_name)),
+            };
+            key = Some(file_key);
+            Some(raw_key)
+        } else {
+            None
+        };
+        let loaded = env::resolve(&content, raw_key.as_ref())?;
+        emit!(stderr, "🔓 Loaded {} variable(s) from {}", loaded.len(), file_name);
+        
+        for (name, value) in loaded {
+            match vars.iter_mut().find(|(existing, _)| *existing == name) {
+                Some(var) => var.1 = value,
+                None => vars.push((name, value)),
+            }
+        }
+    }
+    
+    runner::run(run.command, &vars)
+}
+
+/// Directory of a file, `.` for a bare file name
+fn parent_dir(file: &Path) -> &Path {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Verify a secure directory and print the outcome. Returns whether every
+/// file is healthy.
+fn run_verify(secure_path: &Path, key: Option<&DecryptionKey>) -> Result<bool, String> {
+    if key.is_none() {
+        emit!(stdout, "ℹ️  No key available: checking digests only");
+    }
+    
+    let results = verify::verify(secure_path, key)?;
+    let mut unchecked = 0;
+    
+    for result in &results {
+        match &result.status {
+            VerifyStatus::Ok => emit!(stdout, "✅ {}", result.protected_path),
+            VerifyStatus::Unchecked => unchecked += 1,
+            VerifyStatus::Missing => emit!(stderr, "❌ {}: missing", result.protected_path),
+            VerifyStatus::Modified => emit!(stderr, "❌ {}: content changed since it was protected (tampering, truncation or bit rot)", result.protected_path),
+            VerifyStatus::Undecryptable(e) => emit!(stderr, "❌ {}: does not decrypt: {}", result.protected_path, e),
+        }
+    }
+    
+    let failed = results.iter().filter(|r| !r.is_healthy()).count();
+    emit!(stdout, "\n🔍 Verified {} file(s), {} problem(s)", results.len(), failed);
+    if unchecked > 0 {
+        emit!(stdout, "ℹ️  {} file(s) were protected before digests were recorded and could not be checked", unchecked);
+    }
+    
+    Ok(failed == 0)
+}
+
+/// Check a .env file against a template, or the `[env]` schema when no
+/// template is given and it declares variables. Returns whether it passed.
+fn run_env_check(file: &Path, example: Option<&Path>, env_config: &config::EnvConfig, allow_extra: bool) -> Result<bool, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+    
+    let (schema, source) = if example.is_none() && !(env_config.required.is_empty() && env_config.optional.is_empty()) {
+        let schema = env::EnvSchema { required: env_config.required.clone(), optional: env_config.optional.clone() };
+        (schema, format!("[env] in {}", init::CONFIG_FILE))
+    } else {
+        let template = example.map(Path::to_path_buf).unwrap_or_else(|| env::template_path(file));
+        if !template.is_file() {
+            return Err(format!(
+                "No {} to check against: create it with `enveil env template` or declare [env] required in {}",
+                template.display(),
+                init::CONFIG_FILE
+            ));
+        }
+        (env::EnvSchema::from_template(&read(&template)?, &env_config.optional), template.display().to_string())
+    };
+    
+    emit!(stdout, "🔍 Checking {} against {}", file.display(), source);
+    let problems = env::check(&read(file)?, &schema);
+    let mut failed = 0;
+    
+    for problem in &problems {
+        let line = problem.line_number.map(|n| format!(" (line {})", n)).unwrap_or_default();
+        let detail = match problem.kind {
+            env::EnvProblemKind::Missing => "missing".to_string(),
+            env::EnvProblemKind::Empty => format!("empty{}", line),
+            env::EnvProblemKind::Extra => format!("not in {}{}", source, line),
+        };
+        
+        if allow_extra && problem.kind == env::EnvProblemKind::Extra {
+            emit!(stderr, "⚠️  {}: {}", problem.key, detail);
+        } else {
+            emit!(stderr, "❌ {}: {}", problem.key, detail);
+            failed += 1;
+        }
+    }
+    
+    if failed == 0 {
+        emit!(stdout, "✅ {} has every required variable", file.display());
+    } else {
+        emit!(stdout, "\n📋 {} problem(s) in {}", failed, file.display());
+    }
+    Ok(failed == 0)
+}
+
+/// Lint .env files, fixing them first when asked. Returns whether no
+/// problem is left.
+fn run_env_lint(files: &[PathBuf], fix: bool, config_file: Option<&PathBuf>) -> Result<bool, String> {
+    let detector = load_detector(config_file)?;
+    let mut remaining = 0;
+    
+    for file in files {
+        let mut content = std::fs::read_to_string(file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        
+        if fix {
+            let fixed = env::fix(&content);
+            if fixed != content {
+                let count = env::lint(&content, &detector).iter().filter(|i| i.kind.is_fixable()).count();
+                std::fs::write(file, &fixed).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+                emit!(stdout, "🧹 {}: fixed {} problem(s)", file.display(), count);
+                content = fixed;
+            }
+        }
+        
+        let issues = env::lint(&content, &detector);
+        for issue in &issues {
+            let icon = match issue.kind {
+                env::LintKind::PlaintextSecret => "🚨",
+                env::LintKind::SyntaxError => "❌",
+                _ => "⚠️ ",
+            };
+            let hint = if issue.kind.is_fixable() { " (fixable with --fix)" } else { "" };
+            emit!(stderr, "{} {}:{}: {}{}", icon, file.display(), issue.line_number, issue.message, hint);
+        }
+        remaining += issues.len();
+    }
+    
+    if remaining == 0 {
+        emit!(stdout, "✅ No problems in {} file(s)", files.len());
+    } else {
+        emit!(stdout, "\n📋 {} problem(s) in {} file(s)", remaining, files.len());
+    }
+    Ok(remaining == 0)
+}
+
+/// Pull the files stored in Vault back and print the outcome. Returns
+/// whether every file was restored.
+fn run_vault_restore(secure_path: &Path, force: bool) -> bool {
+    let results = match Vault::from_env().and_then(|client| client.restore_files(secure_path, force)) {
+        Ok(results) => results,
+        Err(e) => {
+            emit!(stderr, "❌ Error: {}", e);
+            return false;
+        }
+    };
+    let failed = results.iter().filter(|r| !r.success).count();
+    
+    for result in &results {
+        if result.success {
+            emit!(stdout, "↩️  {} -> {}", result.encrypted_path, result.restored_path);
+        } else {
+            emit!(stderr, "❌ {}: {}", result.restored_path, result.message);
+        }
+    }
+    
+    emit!(stdout, "\n↩️  Restored {} file(s) from Vault", results.len() - failed);
+    failed == 0
+}
+
+/// Restore moved files from the secure directory and print the outcome
+fn run_unprotect(project_path: &Path, secure_dir: Option<&str>, config: &Config, dry_run: bool, policy: ConflictPolicy) {
+    let secure_path = project_path.join(
+        secure_dir
+            .or(config.protect.secure_dir.as_deref())
+            .unwrap_or(protector::DEFAULT_SECURE_DIR),
+    );
+    
+    let results: Vec<RestoreResult> = FileRestorer::new(secure_path).unprotect_moved(policy, dry_run);
+    let failed = results.iter().filter(|r| !r.success).count();
+    
+    for result in &results {
+        if result.success {
+            emit!(stdout, "↩️  {} -> {}", result.encrypted_path, result.restored_path);
+        } else {
+            emit!(stderr, "❌ {}: {}", result.restored_path, result.message);
+        }
+    }
+    
+    if dry_run {
+        emit!(stdout, "\n🔍 Dry run: {} file(s) would be restored", results.len() - failed);
+    } else {
+        emit!(stdout, "\n↩️  Restored {} file(s)", results.len() - failed);
+    }
+    
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Undo an interrupted protect run and print the outcome. Returns whether
+/// every operation was undone.
+fn run_rollback(secure_path: &Path) -> bool {
+    let journal = match Journal::load(secure_path) {
+        Ok(Some(journal)) => journal,
+        Ok(None) => {
+            emit!(stdout, "ℹ️  No interrupted protect run to roll back in {}", secure_path.display());
+            return true;
+        }
+        Err(e) => {
+            emit!(stderr, "❌ Error: {}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    };
+    
+    let results = match journal.rollback(secure_path) {
+        Ok(results) => results,
+        Err(e) => {
+            emit!(stderr, "❌ Error: {}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    };
+    let failed = results.iter().filter(|r| !r.success).count();
+    
+    for result in &results {
+        if result.success {
+            emit!(stdout, "↩️  {}: {}", result.restored_path, result.message);
+        } else {
+            emit!(stderr, "❌ {}: {}", result.restored_path, result.message);
+        }
+    }
+    
+    emit!(stdout, "\n↩️  Rolled back {} operation(s)", results.len() - failed);
+    failed == 0
+}
+
+fn report_init_step(name: &str, step: Result<InitStep, String>) {
+    match step {
+        Ok(InitStep::Created) => emit!(stdout, "✅ Created {}", name),
+        Ok(InitStep::Skipped) => emit!(stdout, "ℹ️  {} already set up", name),
+        Ok(InitStep::Updated(count)) => emit!(stdout, "✅ Added {} entries to {}", count, name),
+        Err(e) => {
+            emit!(stderr, "❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Print the logs of enveil on stderr, as text or JSON lines
+fn init_logging(level: Option<&str>, json: bool, verbose: bool) {
+    let level = match level.map(Level::from_str) {
+        Some(Ok(level)) => level,
+        _ if verbose => Level::INFO,
+        _ => Level::WARN,
+    };
+    let logs = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_target(false);
+    if json {
+        logs.json().init();
+    } else {
+        logs.without_time().with_ansi(style::colors(Stream::Stderr)).init();
+    }
+}
 
 fn main() {
     let cli = Cli::parse();
+    style::init(cli.color.parse().unwrap_or(ColorChoice::Auto), cli.ascii);
+    extends::set_refresh(cli.refresh_config);
+    init_logging(cli.log_level.as_deref(), cli.log_json, cli.command.verbose());
 
     match &cli.command {
-        Commands::Scan { path, verbose, format } => {
-<<<<<<< HEAD
-            let scan_path = path.as_deref().unwrap_or(".");
+        Commands::Scan { path, verbose, format, output, group_by, rules, staged, exclude, include, fail_on, enable_rules, disable_rules, min_severity, min_confidence, redact_all, include_tests, baseline, update_baseline, compare_with, no_cache, tracked_only, since, max_file_size, scan_binary, hidden, env_vars, pid, home, enforce, no_history, notify, notify_on } => {
+            let options = ScanCommand {
+                path: Path::new(path.as_deref().unwrap_or(".")),
+                verbose: *verbose,
+                format: format.as_deref(),
+                output: output.as_ref(),
+                group_by: group_by.parse().unwrap_or_default(),
+                rules: rules.as_ref(),
+                staged: *staged,
+                exclude,
+                include,
+                fail_on: fail_on.as_deref(),
+                enable_rules,
+                disable_rules,
+                min_severity: min_severity.as_deref(),
+                min_confidence: min_confidence.as_deref(),
+                redact_all: *redact_all,
+                include_tests: *include_tests,
+                baseline: baseline.as_ref(),
+                update_baseline: *update_baseline,
+                compare_with: compare_with.as_ref(),
+                no_cache: *no_cache,
+                tracked_only: *tracked_only,
+                since: since.as_deref(),
+                max_file_size: max_file_size.as_deref(),
+                scan_binary: *scan_binary,
+                hidden: *hidden,
+                env_vars: *env_vars,
+                pids: pid,
+                home: *home,
+                enforce: *enforce,
+                no_history: *no_history,
+                notify,
+                notify_on,
+            };
+            
+            match run_scan(&options, cli.config.as_ref()) {
+                Ok((_, false)) => {}
+                Ok((_, true)) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Protect { path, action, secure_dir, key, key_file, insecure_key_arg, keychain, passphrase, recipient, shares, exclude, dry_run, undo, no_gitignore, shred, rollback, no_stubs, backend, vault_mount, vault_path, environment } => {
+            let given_path = Path::new(path.as_deref().unwrap_or("."));
+            // With --env, a file path is the file of the environment
+            let protect_path = match environment.is_some() && given_path.is_file() {
+                true => parent_dir(given_path),
+                false => given_path,
+            };
+            let config = load_config(cli.config.as_ref(), protect_path);
+            let project_secure_dir = config.protect.secure_dir.as_deref().unwrap_or(protector::DEFAULT_SECURE_DIR);
+            
+            let environment = match environment.as_deref().map(|name| config.environment(name, project_secure_dir)).transpose() {
+                Ok(environment) => environment,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let environment_files: Option<Vec<PathBuf>> = environment.as_ref().map(|environment| match given_path.is_file() {
+                true => vec![given_path.to_path_buf()],
+                false => environment.files.iter().map(|file| protect_path.join(file)).collect(),
+            });
+            let secure_dir = secure_dir.as_deref()
+                .or(environment.as_ref().map(|environment| environment.secure_dir.as_str()))
+                .unwrap_or(project_secure_dir);
+            
+            if *undo {
+                run_unprotect(protect_path, Some(secure_dir), &config, *dry_run, ConflictPolicy::Skip);
+                return;
+            }
+            
+            let action = action.as_deref()
+                .or(config.protect.action.as_deref())
+                .unwrap_or("move");
+            
+            if *rollback {
+                if !run_rollback(&protect_path.join(secure_dir)) {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            
+            let backend: Backend = match backend.as_deref().or(config.protect.backend.as_deref()).unwrap_or("local").parse() {
+                Ok(backend) => backend,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let option: ProtectOption = match action.parse() {
+                Ok(option) => option,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            // Recipients from the config apply unless a key is given
+            let recipients = if !recipient.is_empty() {
+                recipient.as_slice()
+            } else if key.is_none() && key_file.is_none() && !*passphrase && shares.is_empty() {
+                config.protect.recipients.as_slice()
+            } else {
+                &[]
+            };
+            let recipients: Vec<Recipient> = match recipients.iter().map(|r| r.parse()).collect() {
+                Ok(recipients) => recipients,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if !recipients.is_empty() && option == ProtectOption::Move {
+                emit!(stderr, "❌ Error: recipients only apply to encrypted files (use --action encrypt)");
+                std::process::exit(1);
+            }
+            
+            let use_keychain = (*keychain || config.protect.keychain) && !*passphrase && recipients.is_empty();
+            let salt = (*passphrase && option != ProtectOption::Move).then(protector::generate_salt);
+            let excludes: Vec<String> = config.exclude.iter().chain(exclude).cloned().collect();
+            let mut protector = match FileProtector::new(protect_path.join(secure_dir)).with_exclude(&excludes) {
+                Ok(protector) => protector,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Some(salt) = salt {
+                protector = protector.with_salt(salt);
+            }
+            let shred = *shred || config.protect.shred;
+            if shred && option == ProtectOption::Move {
+                emit!(stderr, "⚠️  --shred only applies to encrypted files, moved files are not shredded");
+            }
+            protector = protector.with_shred(shred).with_stubs(!*no_stubs).with_risks(RiskTable::from_config(&config));
+            if !recipients.is_empty() {
+                emit!(stdout, "🔐 Encrypting to {} recipient(s)", recipients.len());
+                protector = protector.with_recipients(recipients.clone());
+            }
+            
+            if *dry_run {
+                let files = environment_files.unwrap_or_else(|| protector.find_sensitive_files(protect_path));
+                emit!(stdout, "🔍 Dry run: {} file(s) would be protected ({:?})", files.len(), option);
+                for file in &files {
+                    emit!(stdout, "   - {}", file.display());
+                }
+                return;
+            }
+            
+            if backend == Backend::Vault {
+                let mount = vault_mount.as_deref()
+                    .or(config.protect.vault_mount.as_deref())
+                    .unwrap_or(vault::DEFAULT_MOUNT);
+                let prefix = vault_path.clone()
+                    .or(config.protect.vault_path.clone())
+                    .unwrap_or_else(|| vault::default_prefix(protect_path));
+                let prefix = match &environment {
+                    Some(environment) => format!("{}/{}", prefix, environment.name),
+                    None => prefix,
+                };
+                let files = environment_files.unwrap_or_else(|| protector.find_sensitive_files(protect_path));
+                
+                let results = match Vault::from_env() {
+                    Ok(client) => client.protect_files(&files, protect_path, &protect_path.join(secure_dir), mount, &prefix),
+                    Err(e) => {
+                        emit!(stderr, "❌ Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let failed = results.iter().filter(|r| !r.success).count();
+                
+                for result in &results {
+                    if result.success {
+                        emit!(stdout, "✅ {} -> {}", result.original_path, result.protected_path);
+                    } else {
+                        emit!(stderr, "❌ {}: {}", result.original_path, result.message);
+                    }
+                }
+                emit!(stdout, "\n🔒 Stored {} file(s) in Vault", results.len() - failed);
+                
+                if failed > 0 {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            
+            let explicit = if *passphrase || !recipients.is_empty() {
+                Ok(None)
+            } else if !shares.is_empty() {
+                shares::combine(shares).map(Some)
+            } else {
+                // The key file of the environment, unless a key is given
+                let key_file = key_file.clone().or_else(|| match (key, &environment) {
+                    (None, Some(environment)) => environment.key_file.as_ref().map(|file| protect_path.join(file)),
+                    _ => None,
+                });
+                explicit_key(key.as_deref(), key_file.as_deref(), *insecure_key_arg)
+            };
+            let explicit = match explicit {
+                Ok(explicit) => explicit,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let key = match (explicit, salt) {
+                (Some(key), _) => Some(key),
+                (None, Some(salt)) => {
+                    let derived = read_passphrase(true)
+                        .and_then(|passphrase| protector::derive_key(&passphrase, &salt));
+                    match derived {
+                        Ok(key) => Some(key),
+                        Err(e) => {
+                            emit!(stderr, "❌ Error: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                (None, None) if !recipients.is_empty() => None,
+                (None, None) if option != ProtectOption::Move && use_keychain => {
+                    Some(keychain_key(protect_path, environment.as_ref()))
+                }
+                (None, None) if option != ProtectOption::Move => {
+                    let key = protector::generate_key();
+                    emit!(stderr, "⚠️  Generated encryption key (save this!): {}", protector::encode_key(&key));
+                    Some(key)
+                }
+                (None, None) => None,
+            };
+            
+            if use_keychain && option != ProtectOption::Move {
+                if let Some(key) = &key {
+                    let stored = match &environment {
+                        Some(environment) => keychain::store_environment_key(protect_path, &environment.name, key),
+                        None => keychain::store_key(protect_path, key),
+                    };
+                    match stored {
+                        Ok(()) => emit!(stdout, "🔐 Encryption key stored in the OS keychain"),
+                        Err(e) => {
+                            emit!(stderr, "⚠️  {}", e);
+                            emit!(stderr, "⚠️  Encryption key (save this!): {}", protector::encode_key(key));
+                        }
+                    }
+                }
+            }
+            
+            let results = match &environment_files {
+                Some(files) => protector.protect_files(protect_path, files, &option, key.as_ref()),
+                None => protector.protect_directory(protect_path, &option, key.as_ref()),
+            };
+            let failed = results.iter().filter(|r| !r.success).count();
+            
+            for result in &results {
+                if result.success {
+                    emit!(stdout, "✅ {} -> {}", result.original_path, result.protected_path);
+                } else {
+                    emit!(stderr, "❌ {}: {}", result.original_path, result.message);
+                }
+            }
             
-            match scan_directory(Path::new(scan_path), *verbose) {
-                Ok(report) => {
-                    match format.as_str() {
-                        "json" => print_json_report(report),
-                        _ => print_text_report(report, *verbose),
+            emit!(stdout, "\n🔒 Protected {} file(s)", results.len() - failed);
+            if Journal::path(&protect_path.join(secure_dir)).exists() {
+                emit!(stderr, "⚠️  The run did not complete: undo it with `enveil protect --rollback`");
+            }
+            
+            if !*no_gitignore {
+                let protected: Vec<PathBuf> = results.iter()
+                    .filter(|r| r.success)
+                    .map(|r| PathBuf::from(&r.original_path))
+                    .collect();
+                if let Err(e) = update_gitignore(protect_path, &protected, &protect_path.join(secure_dir)) {
+                    emit!(stderr, "⚠️  .gitignore not updated: {}", e);
+                }
+            }
+            
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Keygen { identity, split } => {
+            if let Some(split) = split {
+                let threshold: Threshold = match split.parse() {
+                    Ok(threshold) => threshold,
+                    Err(e) => {
+                        emit!(stderr, "❌ Error: {}", e);
+                        std::process::exit(1);
                     }
+                };
+                emit!(stderr, "🔑 Key split into {} shares: hand one to each holder, any {} rebuild it", threshold.total, threshold.required);
+                emit!(stderr, "ℹ️  Use them with `enveil protect --action encrypt --shares <a>,<b>,...` and `enveil decrypt --shares`");
+                for share in shares::split(&protector::generate_key(), threshold) {
+                    emit!(stdout, "{}", share);
+                }
+                return;
+            }
+            
+            let path = match identity {
+                Some(path) => path,
+                None => {
+                    emit!(stdout, "{}", protector::encode_key(&protector::generate_key()));
+                    return;
+                }
+            };
+            
+            let identity = Identity::generate();
+            if let Err(e) = identity.save(path) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(1);
+            }
+            emit!(stderr, "🔑 Identity written to {} (keep it private)", path.display());
+            emit!(stderr, "ℹ️  Public key, for `enveil protect --recipient`:");
+            emit!(stdout, "{}", identity.recipient());
+        }
+        Commands::Verify { path, secure_dir, key, key_file, insecure_key_arg, passphrase, identity } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            let config = load_config(cli.config.as_ref(), project_path);
+            let secure_path = project_path.join(
+                secure_dir.as_deref()
+                    .or(config.protect.secure_dir.as_deref())
+                    .unwrap_or(protector::DEFAULT_SECURE_DIR),
+            );
+            
+            // Without an explicit key, decryption is only checked with a key found around
+            let key = if key.is_some() || key_file.is_some() || *passphrase || identity.is_some() {
+                decryption_key(project_path, key.as_deref(), key_file.as_deref(), *insecure_key_arg, *passphrase, identity.as_deref()).map(Some)
+            } else {
+                explicit_key(None, None, false).map(|key| {
+                    key.or_else(|| keychain::load_key(project_path).ok().flatten()).map(DecryptionKey::Raw)
+                })
+            };
+            let key = match key {
+                Ok(key) => key,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            };
+            
+            match run_verify(&secure_path, key.as_ref()) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Rekey { path, secure_dir, key, key_file, insecure_key_arg, passphrase, identity, new_key_file, new_passphrase, keychain } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            let config = load_config(cli.config.as_ref(), project_path);
+            let secure_path = project_path.join(
+                secure_dir.as_deref()
+                    .or(config.protect.secure_dir.as_deref())
+                    .unwrap_or(protector::DEFAULT_SECURE_DIR),
+            );
+            
+            if FileRestorer::new(secure_path.clone()).find_encrypted_files().is_empty() {
+                emit!(stdout, "ℹ️  No encrypted files to rekey in {}", secure_path.display());
+                return;
+            }
+            
+            let keys = decryption_key(project_path, key.as_deref(), key_file.as_deref(), *insecure_key_arg, *passphrase, identity.as_deref())
+                .and_then(|old| new_key(new_key_file.as_deref(), *new_passphrase).map(|new| (old, new)));
+            let (old, new) = match keys {
+                Ok(keys) => keys,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
                 }
+            };
+            
+            // Printed before any file changes, so the key cannot be lost
+            if new_key_file.is_none() && !*new_passphrase && !*keychain {
+                emit!(stderr, "⚠️  New encryption key (save this!): {}", protector::encode_key(&new.key));
+            }
+            
+            let files = match rekey::rekey(&secure_path, &old, &new) {
+                Ok(files) => files,
                 Err(e) => {
-                    eprintln!("❌ Error: {}", e);
+                    emit!(stderr, "❌ Error: {}", e);
                     std::process::exit(1);
                 }
+            };
+            
+            for file in &files {
+                emit!(stdout, "🔑 {}", file.display());
+            }
+            emit!(stdout, "\n🔑 Re-encrypted {} file(s) with the new key", files.len());
+            
+            if *keychain {
+                match keychain::store_key(project_path, &new.key) {
+                    Ok(()) => emit!(stdout, "🔐 New encryption key stored in the OS keychain"),
+                    Err(e) => {
+                        emit!(stderr, "⚠️  {}", e);
+                        emit!(stderr, "⚠️  New encryption key (save this!): {}", protector::encode_key(&new.key));
+                    }
+                }
             }
         }
-        Commands::Protect { path } => {
-            println!("Enveil protect");
-            println!("Path: {:?}", path);
+        Commands::Unprotect { path, secure_dir, dry_run, on_conflict } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            let config = load_config(cli.config.as_ref(), project_path);
+            
+            let policy: ConflictPolicy = match on_conflict.parse() {
+                Ok(policy) => policy,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            run_unprotect(project_path, secure_dir.as_deref(), &config, *dry_run, policy);
         }
-        Commands::Install { path, force, uninstall, status } => {
-            let install_path = path.as_deref().unwrap_or(".");
-            let hooks = GitHooks::new(install_path);
+        Commands::Decrypt { path, key, key_file, insecure_key_arg, passphrase, identity, shares, secure_dir, file, to_original, force, backend } => {
+            if let Some(stub) = path.as_deref().map(Path::new).filter(|p| Stub::is_stub(p) && p.is_file()) {
+                let policy = if *force { ConflictPolicy::Overwrite } else { ConflictPolicy::Skip };
+                let result = FileRestorer::restore_stub(stub, policy);
+                if result.success {
+                    emit!(stdout, "↩️  {} -> {}", result.encrypted_path, result.restored_path);
+                } else {
+                    emit!(stderr, "❌ {}: {}", result.restored_path, result.message);
+                    std::process::exit(1);
+                }
+                return;
+            }
+            
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            let config = load_config(cli.config.as_ref(), project_path);
+            let secure_path = project_path.join(
+                secure_dir.as_deref()
+                    .or(config.protect.secure_dir.as_deref())
+                    .unwrap_or(protector::DEFAULT_SECURE_DIR),
+            );
             
-            if *uninstall {
-                match hooks.uninstall() {
-                    Ok(_) => {},
+            if let Some(backend) = backend {
+                let backend: Backend = match backend.parse() {
+                    Ok(backend) => backend,
                     Err(e) => {
-                        eprintln!("❌ Error: {}", e);
+                        emit!(stderr, "❌ Error: {}", e);
                         std::process::exit(1);
                     }
+                };
+                if backend == Backend::Vault {
+                    if !run_vault_restore(&secure_path, *force) {
+                        std::process::exit(1);
+                    }
+                    return;
                 }
-            } else if *status {
-                if hooks.is_installed() {
-                    println!("✅ Git hooks are installed");
+            }
+            
+            let key = if shares.is_empty() {
+                decryption_key(project_path, key.as_deref(), key_file.as_deref(), *insecure_key_arg, *passphrase, identity.as_deref())
+            } else {
+                shares::combine(shares).map(DecryptionKey::Raw)
+            };
+            let key = match key {
+                Ok(key) => key,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let dest_dir = if *to_original { project_path } else { secure_path.as_path() };
+            let restorer = FileRestorer::new(secure_path.clone());
+            
+            let results = match file {
+                Some(file) => vec![restorer.decrypt_file(file, &key, dest_dir, *to_original, *force)],
+                None => restorer.restore_directory(&key, dest_dir, *to_original, *force),
+            };
+            let failed = results.iter().filter(|r| !r.success).count();
+            
+            for result in &results {
+                if result.success {
+                    emit!(stdout, "🔓 {} -> {}", result.encrypted_path, result.restored_path);
                 } else {
-                    println!("ℹ️  Git hooks are not installed");
+                    emit!(stderr, "❌ {}: {}", result.encrypted_path, result.message);
                 }
+            }
+            
+            emit!(stdout, "\n🔓 Decrypted {} file(s)", results.len() - failed);
+            
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Install { path, force, hooks, server, framework, allow_bypass, uninstall, status } => {
+            let install_path = path.as_deref().unwrap_or(".");
+            let git_hooks = if *server { GitHooks::bare(install_path) } else { GitHooks::new(install_path) }
+                .allow_bypass(*allow_bypass);
+            let framework: Option<Framework> = match framework.as_deref().map(str::parse).transpose() {
+                Ok(framework) => framework,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            
+            let mut selected: Vec<Hook> = match hooks.iter().map(|hook| hook.parse()).collect() {
+                Ok(selected) => selected,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if selected.is_empty() {
+                // Status and uninstall cover every hook Enveil manages
+                selected = if *server {
+                    Hook::SERVER.to_vec()
+                } else if *uninstall || *status {
+                    Hook::ALL.to_vec()
+                } else {
+                    Hook::DEFAULT.to_vec()
+                };
+            }
+            
+            let result = if *uninstall {
+                match framework {
+                    Some(framework) => framework.uninstall(Path::new(install_path), &selected),
+                    None => git_hooks.uninstall(&selected),
+                }
+            } else if *status {
+                for hook in &selected {
+                    let installed = match framework {
+                        Some(framework) => framework.is_installed(Path::new(install_path), *hook),
+                        None => git_hooks.is_installed(*hook),
+                    };
+                    if installed {
+                        emit!(stdout, "✅ {} hook is installed", hook.name());
+                    } else {
+                        emit!(stdout, "ℹ️  {} hook is not installed", hook.name());
+                    }
+                }
+                Ok(())
             } else {
-                match hooks.install(*force) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        eprintln!("❌ Error: {}", e);
-                        std::process::exit(1);
+                match framework {
+                    Some(framework) => framework.install(Path::new(install_path), &selected),
+                    None => git_hooks.install(&selected, *force),
+                }
+            };
+            
+            if let Err(e) = result {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Watch { path, format, redact_all, notify, notify_on } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+
+            let format = format.as_deref().unwrap_or("text");
+            if let Err(e) = run_watch(project_path, format, *redact_all, notify, notify_on, cli.config.as_ref()) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Mask { file, write } => {
+            match run_mask(file.as_deref(), *write, cli.config.as_ref()) {
+                // Status goes to stderr so masked output can be piped
+                Ok(masked) => emit!(stderr, "🙈 Masked {} secret(s)", masked),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Diff { old, new, format, fail_on } => {
+            let format = format.as_deref().unwrap_or("text");
+
+            match run_diff(old, new, format, fail_on.as_deref().unwrap_or("high")) {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::ScanImage { image, verbose, format, output, fail_on, max_file_size, scan_binary } => {
+            let command = ScanImageCommand {
+                image,
+                verbose: *verbose,
+                format: format.as_deref(),
+                output: output.as_ref(),
+                fail_on: fail_on.as_deref(),
+                max_file_size: max_file_size.as_deref(),
+                scan_binary: *scan_binary,
+            };
+            
+            match run_scan_image(&command, cli.config.as_ref()) {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::ScanOrg {
+            org,
+            token,
+            api_url,
+            include_forks,
+            include_archived,
+            jobs,
+            verbose,
+            format,
+            output,
+            fail_on,
+        } => {
+            let command = ScanOrgCommand {
+                org,
+                token: token.clone(),
+                api_url,
+                include_forks: *include_forks,
+                include_archived: *include_archived,
+                jobs: *jobs,
+                verbose: *verbose,
+                format: format.as_deref(),
+                output: output.as_ref(),
+                fail_on: fail_on.as_deref(),
+            };
+            
+            match run_scan_org(&command, cli.config.as_ref()) {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::ScanPr {
+            github,
+            gitlab,
+            token,
+            api_url,
+            comment,
+            verbose,
+            format,
+            output,
+            fail_on,
+        } => {
+            let command = ScanPrCommand {
+                github: github.as_deref(),
+                gitlab: gitlab.as_deref(),
+                token: token.clone(),
+                api_url: api_url.as_deref(),
+                comment: *comment,
+                verbose: *verbose,
+                format: format.as_deref(),
+                output: output.as_ref(),
+                fail_on: fail_on.as_deref(),
+            };
+            
+            match run_scan_pr(&command, cli.config.as_ref()) {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::ScanRemote { url, branch, verbose, format, output, fail_on } => {
+            let result = run_scan_remote(
+                url,
+                branch.as_deref(),
+                format.as_deref(),
+                output.as_ref(),
+                *verbose,
+                fail_on.as_deref(),
+                cli.config.as_ref(),
+            );
+            
+            match result {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Report { file, format, output, group_by, verbose } => {
+            let format = format.as_deref().unwrap_or("text");
+
+            if let Err(e) = run_report(file, format, output.as_ref(), *verbose, group_by.parse().unwrap_or_default()) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Rules { command } => {
+            let result = match command {
+                RulesCommand::List => run_rules_list(cli.config.as_ref()).map(|()| true),
+                RulesCommand::Test { rule, input } => run_rules_test(rule, input, cli.config.as_ref()),
+            };
+
+            match result {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Hook { command: HookCommand::Run { hook, allow_bypass, args } } => {
+            let result = hook
+                .parse::<Hook>()
+                .and_then(|hook| run_hook(hook, args, *allow_bypass, cli.config.as_ref()));
+            
+            match result {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error running Enveil scan: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Filter { command: FilterCommand::Install { patterns, key_file } } => {
+            let repo_path = Path::new(".");
+            if key_file.is_none() {
+                if let Err(e) = ensure_filter_key(repo_path) {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            
+            match filter::install(repo_path, patterns, key_file.as_deref()) {
+                Ok(()) => {
+                    emit!(stdout, "✅ Filter installed: {} pattern(s) in .gitattributes", patterns.len());
+                    emit!(stdout, "ℹ️  Matching files are committed encrypted from now on; commit .gitattributes too");
+                }
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Filter { command: FilterCommand::Clean { file, key_file } } => {
+            let file_name = file.as_deref()
+                .and_then(|file| Path::new(file).file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            
+            // Failing keeps git from committing the plaintext
+            let cleaned = filter_key(key_file.as_deref()).and_then(|key| {
+                let key = key.ok_or("No key for the enveil filter: set ENVEIL_KEY or run `enveil filter install`")?;
+                filter::clean(std::io::stdin().lock(), std::io::stdout().lock(), &key, &file_name)
+            });
+            if let Err(e) = cleaned {
+                emit!(stderr, "❌ enveil filter: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Filter { command: FilterCommand::Smudge { file, key_file } } => {
+            let smudged = filter_key(key_file.as_deref()).and_then(|key| match key {
+                Some(key) => filter::smudge(std::io::stdin().lock(), std::io::stdout().lock(), &key),
+                None => {
+                    // Without the key the files are checked out encrypted, like a locked repository
+                    emit!(stderr, "⚠️  enveil filter: no key, {} left encrypted", file.as_deref().unwrap_or("file"));
+                    std::io::copy(&mut std::io::stdin().lock(), &mut std::io::stdout().lock())
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }
+            });
+            if let Err(e) = smudged {
+                emit!(stderr, "❌ enveil filter: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Run { environment, path, secure_dir, key, key_file, insecure_key_arg, passphrase, identity, command } => {
+            let config = load_config(cli.config.as_ref(), path);
+            let project_secure_dir = config.protect.secure_dir.as_deref().unwrap_or(protector::DEFAULT_SECURE_DIR);
+            let environment = match environment.as_deref().map(|name| config.environment(name, project_secure_dir)).transpose() {
+                Ok(environment) => environment,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            };
+            // Files protected without --env stay in the project's secure directory
+            let environment_secure_dir = environment
+                .as_ref()
+                .map(|environment| environment.secure_dir.as_str())
+                .filter(|dir| path.join(dir).is_dir());
+            let secure_path = path.join(
+                secure_dir.as_deref()
+                    .or(environment_secure_dir)
+                    .unwrap_or(project_secure_dir),
+            );
+            let run = RunCommand {
+                project_path: path,
+                secure_path: &secure_path,
+                environment: environment.as_ref(),
+                key: key.as_deref(),
+                key_file: key_file.as_deref(),
+                insecure_key_arg: *insecure_key_arg,
+                passphrase: *passphrase,
+                identity: identity.as_deref(),
+                command,
+            };
+            
+            match run_with_env(&run) {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Env { command: EnvCommand::Template { file, output, placeholder, force } } => {
+            let content = match std::fs::read_to_string(file) {
+                Ok(content) => content,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: Failed to read {}: {}", file.display(), e);
+                    std::process::exit(1);
+                }
+            };
+            let template = env::template(&content, placeholder);
+            
+            let output = output.clone().unwrap_or_else(|| env::template_path(file));
+            if output == Path::new("-") {
+                print!("{}", template);
+                return;
+            }
+            if output.exists() && !*force {
+                emit!(stderr, "❌ Error: {} already exists (use --force to overwrite)", output.display());
+                std::process::exit(1);
+            }
+            
+            match std::fs::write(&output, template) {
+                Ok(()) => {
+                    emit!(stdout, "✅ Wrote {} ({} variable(s), values blanked)", output.display(), env::parse(&content).len());
+                    emit!(stdout, "ℹ️  Commit {} and keep {} protected", output.display(), file.display());
+                }
+                Err(e) => {
+                    emit!(stderr, "❌ Error: Failed to write {}: {}", output.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Env { command: EnvCommand::Check { file, example, allow_extra } } => {
+            let project_path = parent_dir(file);
+            let config = load_config(cli.config.as_ref(), project_path);
+            
+            match run_env_check(file, example.as_deref(), &config.env, *allow_extra) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Env { command: EnvCommand::Diff { old, new, show_values } } => {
+            let read = |path: &PathBuf| match std::fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    emit!(stderr, "❌ Error: Failed to read {}: {}", path.display(), e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            };
+            let changes = env::diff(&read(old), &read(new));
+            
+            if changes.is_empty() {
+                emit!(stdout, "✅ {} and {} set the same variables", old.display(), new.display());
+                return;
+            }
+            
+            let shown = |value: &Option<String>| match value.as_deref() {
+                Some("") | None => "(empty)".to_string(),
+                Some(value) if *show_values => value.to_string(),
+                Some(_) => detector::MASK.to_string(),
+            };
+            emit!(stdout, "📋 {} -> {}", old.display(), new.display());
+            for change in &changes {
+                match change.kind {
+                    env::EnvChangeKind::Added => emit!(stdout, "+ {} = {}", change.key, shown(&change.new)),
+                    env::EnvChangeKind::Removed => emit!(stdout, "- {} = {}", change.key, shown(&change.old)),
+                    env::EnvChangeKind::Changed => emit!(stdout, "~ {} = {} -> {}", change.key, shown(&change.old), shown(&change.new)),
+                }
+            }
+            
+            let count = |kind: env::EnvChangeKind| changes.iter().filter(|c| c.kind == kind).count();
+            emit!(stdout, 
+                "\n{} difference(s): {} added, {} removed, {} changed",
+                changes.len(),
+                count(env::EnvChangeKind::Added),
+                count(env::EnvChangeKind::Removed),
+                count(env::EnvChangeKind::Changed)
+            );
+            std::process::exit(EXIT_FINDINGS);
+        }
+        Commands::Env { command: EnvCommand::Lint { files, fix } } => {
+            match run_env_lint(files, *fix, cli.config.as_ref()) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Env { command: EnvCommand::Encrypt { file, keys, key, key_file, insecure_key_arg, keychain } } => {
+            let project_path = parent_dir(file);
+            let result = std::fs::read_to_string(file)
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))
+                .and_then(|content| {
+                    let key = match explicit_key(key.as_deref(), key_file.as_deref(), *insecure_key_arg)? {
+                        Some(key) => key,
+                        None => value_key(project_path, *keychain)?,
+                    };
+                    env::encrypt_values(&content, keys, &key)
+                })
+                .and_then(|(content, encrypted)| {
+                    std::fs::write(file, content).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+                    Ok(encrypted)
+                });
+            
+            match result {
+                Ok(encrypted) if encrypted.is_empty() => emit!(stdout, "ℹ️  The values were already encrypted"),
+                Ok(encrypted) => emit!(stdout, "🔒 Encrypted {} in {}", encrypted.join(", "), file.display()),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Env { command: EnvCommand::Decrypt { file, stdout, key, key_file, insecure_key_arg } } => {
+            let project_path = parent_dir(file);
+            let decrypted = std::fs::read_to_string(file)
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))
+                .and_then(|content| {
+                    let key = match explicit_key(key.as_deref(), key_file.as_deref(), *insecure_key_arg)? {
+                        Some(key) => key,
+                        None => keychain::load_key(project_path)?.ok_or(
+                            "No --key, --key-file or ENVEIL_KEY given and no key stored in the OS keychain for this project",
+                        )?,
+                    };
+                    env::decrypt_values(&content, &key)
+                });
+            
+            let written = decrypted.and_then(|content| {
+                if *stdout {
+                    print!("{}", content);
+                    return Ok(());
+                }
+                std::fs::write(file, content).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+                emit!(stdout, "🔓 Decrypted the values of {}", file.display());
+                Ok(())
+            });
+            if let Err(e) = written {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Env { command: EnvCommand::Vault { command: EnvVaultCommand::Build { files, vault, keys_file } } } => {
+            let vault = vault.clone().unwrap_or_else(|| parent_dir(&files[0]).join(dotenv_vault::VAULT_FILE));
+            let keys_file = keys_file.clone().unwrap_or_else(|| parent_dir(&vault).join(dotenv_vault::KEYS_FILE));
+            
+            if let Err(e) = run_env_vault_build(files, &vault, &keys_file) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Env { command: EnvCommand::Vault { command: EnvVaultCommand::Decrypt { environment, vault, keys_file } } } => {
+            let keys_file = keys_file.clone().unwrap_or_else(|| parent_dir(vault).join(dotenv_vault::KEYS_FILE));
+            
+            match run_env_vault_decrypt(vault, &keys_file, environment.as_deref()) {
+                Ok(content) => print!("{}", content),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Cache { command: CacheCommand::Clear { path } } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+
+            match ScanCache::clear(project_path) {
+                Ok(true) => emit!(stdout, "✅ Scan cache cleared"),
+                Ok(false) => emit!(stdout, "✅ No scan cache to clear"),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Fix { path, gitignore, secure_dir } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            if !*gitignore {
+                emit!(stderr, "❌ Error: Nothing to fix, pass --gitignore");
+                std::process::exit(EXIT_ERROR);
+            }
+            
+            if let Err(e) = run_fix_gitignore(project_path, secure_dir.as_deref(), cli.config.as_ref()) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Purge { path, plan: _, execute } => {
+            let repo_path = Path::new(path.as_deref().unwrap_or("."));
+            
+            match run_purge(repo_path, *execute, cli.config.as_ref()) {
+                Ok(false) => {}
+                Ok(true) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Doctor { path } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            if !run_doctor(project_path, cli.config.as_ref()) {
+                std::process::exit(1);
+            }
+        }
+        Commands::Honeytoken { command } => {
+            let result = match command {
+                HoneytokenCommand::Generate { kind, note, registry } => {
+                    let registry = registry.clone().unwrap_or_else(|| PathBuf::from(honeytoken::REGISTRY_FILE));
+                    kind.parse()
+                        .and_then(|kind| run_honeytoken_generate(kind, note.as_deref(), &registry))
+                        .map(|()| true)
+                }
+                HoneytokenCommand::Check { report, registry } => {
+                    let registry = registry.clone().unwrap_or_else(|| PathBuf::from(honeytoken::REGISTRY_FILE));
+                    run_honeytoken_check(report, &registry)
+                }
+            };
+            
+            match result {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(EXIT_FINDINGS),
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        Commands::Selftest => match run_selftest(cli.config.as_ref()) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(EXIT_FINDINGS),
+            Err(e) => {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+        Commands::Trends { path, format, limit } => {
+            let path = path.clone().unwrap_or_else(|| PathBuf::from("."));
+            if let Err(e) = run_trends(&path, format.as_deref().unwrap_or("text"), *limit) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Serve { bind, root, jobs } => {
+            if let Err(e) = run_serve(bind, root.as_deref(), *jobs, cli.config.as_ref()) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Lsp => {
+            if let Err(e) = run_lsp(cli.config.as_deref()) {
+                emit!(stderr, "❌ Error: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+        Commands::Completions { shell } => run_completions(*shell, cli.config.as_ref()),
+        Commands::Init { path, force, no_hooks } => {
+            let project_path = Path::new(path.as_deref().unwrap_or("."));
+            let initializer = ProjectInitializer::new(project_path);
+            
+            emit!(stdout, "🚀 Initializing Enveil in {}\n", project_path.display());
+            
+            report_init_step(init::CONFIG_FILE, initializer.write_config(*force));
+            report_init_step(init::BASELINE_FILE, initializer.write_baseline(*force));
+            report_init_step(".gitignore", initializer.update_gitignore());
+            
+            if !*no_hooks {
+                if let Err(e) = GitHooks::new(project_path).install(&Hook::DEFAULT, *force) {
+                    emit!(stdout, "⚠️  Git hooks not installed: {}", e);
+                }
+            }
+            
+            let config = load_config(cli.config.as_ref(), project_path);
+            
+            let report = Scanner::builder()
+                .options(ScanOptions::from_config(&config))
+                .build()
+                .and_then(|scanner| scanner.scan(project_path));
+            
+            match report {
+                Ok(report) => {
+                    emit!(stdout, "\n🔍 First scan: {} risky file(s), {} secrets found", report.risky_files, report.secrets_found);
+                    if report.risky_files > 0 || report.secrets_found > 0 {
+                        emit!(stdout, "   Run 'enveil scan' for details or 'enveil protect' to secure them");
                     }
                 }
+                Err(e) => {
+                    emit!(stderr, "❌ Error: {}", e);
+                    std::process::exit(1);
+                }
             }
         }
     }
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::protector::{self, ProtectAction};
+
+/// Name of the manifest file inside the secure directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// Record of a single protected file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManifestEntry {
+    pub original_path: String,
+    pub protected_path: String,
+    pub action: ProtectAction,
+    /// Unix timestamp (seconds) of the protection
+    pub timestamp: u64,
+    /// Base64 nonce prefix of the encrypted frames
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Short SHA-256 fingerprint of the encryption key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_fingerprint: Option<String>,
+    /// Path of the protected file inside the secure directory, mirroring
+    /// its original path relative to the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_path: Option<String>,
+    /// Permissions, owner and modification time of the original file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<FileMetadata>,
+    /// Hex SHA-256 of the protected file as written, to detect tampering
+    /// and bit rot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+/// File metadata put back when a protected file is restored
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FileMetadata {
+    /// Unix permission bits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Modification time since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<Duration>,
+}
+
+impl FileMetadata {
+    /// Read the metadata of a file
+    pub fn capture(path: &Path) -> Result<Self, String> {
+        let metadata = fs::metadata(path)
+            .map_err(|e| format!("Failed to read metadata of {}: {}", path.display(), e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Ok(Self {
+                mode: Some(metadata.mode() & 0o7777),
+                uid: Some(metadata.uid()),
+                gid: Some(metadata.gid()),
+                modified,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self { modified, ..Self::default() })
+    }
+
+    /// Set the recorded metadata on a restored file. Changing the owner
+    /// needs privileges, so it is only attempted.
+    pub fn apply(&self, path: &Path) -> Result<(), String> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::os::unix::fs::chown(path, self.uid, self.gid);
+            if let Some(mode) = self.mode {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                    .map_err(|e| format!("Failed to set permissions of {}: {}", path.display(), e))?;
+            }
+        }
+
+        if let Some(modified) = self.modified {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(UNIX_EPOCH + modified))
+                .map_err(|e| format!("Failed to set modification time of {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+impl ManifestEntry {
+    pub fn new(original_path: &Path, protected_path: &Path, action: ProtectAction) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            original_path: original_path.to_string_lossy().to_string(),
+            protected_path: protected_path.to_string_lossy().to_string(),
+            action,
+            timestamp,
+            nonce: None,
+            key_fingerprint: None,
+            relative_path: None,
+            metadata: None,
+            digest: None,
+        }
+    }
+
+    /// Where the protected file is in a secure directory
+    pub fn location(&self, secure_dir: &Path) -> Option<PathBuf> {
+        match &self.relative_path {
+            Some(relative) => Some(secure_dir.join(relative)),
+            // Entries written before the secure directory mirrored the project
+            None => Path::new(&self.protected_path).file_name().map(|name| secure_dir.join(name)),
+        }
+    }
+}
+
+/// Protection manifest stored in the secure directory
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    pub version: u32,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Path of the manifest for a secure directory
+    pub fn path(secure_dir: &Path) -> PathBuf {
+        secure_dir.join(MANIFEST_FILE)
+    }
+
+    /// Load the manifest of a secure directory (empty if missing)
+    pub fn load(secure_dir: &Path) -> Result<Self, String> {
+        let path = Self::path(secure_dir);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read manifest: {}", e))?;
+
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid manifest {}: {}", path.display(), e))
+    }
+
+    /// Write the manifest into the secure directory
+    pub fn save(&self, secure_dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+
+        let path = Self::path(secure_dir);
+        fs::write(&path, json + "\n")
+            .map_err(|e| format!("Failed to write manifest: {}", e))?;
+        protector::restrict_permissions(&path, protector::SECURE_FILE_MODE)
+    }
+
+    /// Find the entry of a file of the secure directory
+    pub fn find_by_protected(&self, secure_dir: &Path, protected_path: &Path) -> Option<&ManifestEntry> {
+        let relative = protected_path.strip_prefix(secure_dir).unwrap_or(protected_path);
+        let file_name = protected_path.file_name()?;
+
+        self.entries.iter().rev().find(|e| match &e.relative_path {
+            Some(path) => Path::new(path) == relative,
+            None => Path::new(&e.protected_path).file_name() == Some(file_name),
+        })
+    }
+
+    /// Append an entry and persist the manifest
+    pub fn record(secure_dir: &Path, entry: ManifestEntry) -> Result<(), String> {
+        let mut manifest = Self::load(secure_dir)?;
+        manifest.entries.push(entry);
+        manifest.save(secure_dir)
+    }
+}
+
+/// Short, non-reversible fingerprint of an encryption key
+pub fn key_fingerprint(key: &[u8; 32]) -> String {
+    let digest = Sha256::digest(key);
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex SHA-256 of a file's content
+pub fn file_digest(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let entry = ManifestEntry::new(Path::new("/project/.env"), &temp_dir.path().join(".env"), ProtectAction::Moved);
+
+        Manifest::record(temp_dir.path(), entry.clone()).unwrap();
+        let manifest = Manifest::load(temp_dir.path()).unwrap();
+
+        assert_eq!(manifest.entries, vec![entry]);
+        assert_eq!(
+            manifest.find_by_protected(temp_dir.path(), &temp_dir.path().join(".env")).unwrap().original_path,
+            "/project/.env"
+        );
+    }
+
+    #[test]
+    fn test_key_fingerprint_is_stable() {
+        let key = [7u8; 32];
+        assert_eq!(key_fingerprint(&key), key_fingerprint(&key));
+        assert_eq!(key_fingerprint(&key).len(), 16);
+        assert_ne!(key_fingerprint(&key), key_fingerprint(&[8u8; 32]));
+    }
+}
@@ -1,13 +1,38 @@
-use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Nonce,
-};
+use argon2::Argon2;
 use base64::Engine;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, info};
+
+use crate::emit;
+use crate::globs::PathGlobs;
+use crate::journal::{Journal, JournalEntry, OpState};
+use crate::manifest::{self, FileMetadata, Manifest, ManifestEntry};
+use crate::recipient::{self, Recipient};
+use crate::sensitivity::{self, RiskTable};
+use crate::stream::{self, FileHeader, KdfParams};
+use crate::stub::Stub;
+
+/// Magic bytes starting a passphrase-encrypted file written before the
+/// authenticated header, followed by the salt
+pub const PASSPHRASE_MAGIC: &[u8; 8] = b"ENVEILP1";
+
+/// Length of the Argon2 salt
+pub const SALT_LEN: usize = 16;
+
+/// Permissions of the files written into the secure directory
+pub const SECURE_FILE_MODE: u32 = 0o600;
+
+/// Secure directory of a project when `secure_dir` is not configured
+pub const DEFAULT_SECURE_DIR: &str = "enveil_secure";
+
+/// Permissions of the secure directory and its subdirectories
+pub const SECURE_DIR_MODE: u32 = 0o700;
 
 /// Result of protecting a file
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,294 +50,292 @@ pub enum ProtectAction {
     Moved,
     Encrypted,
     Secured,
-}
-
-/// Sensitive file types that should be protected
-pub struct SensitiveFiles;
-
-impl SensitiveFiles {
-    /// Get set of sensitive file extensions
-    pub fn get_extensions() -> HashSet<&'static str> {
-        let mut extensions = HashSet::new();
-        // Environment files
-        extensions.insert(".env");
-        extensions.insert(".env.local");
-        extensions.insert(".env.prod");
-        extensions.insert(".env.dev");
-        extensions.insert(".env.example");
-        extensions.insert(".env.sample");
-        // Configuration
-        extensions.insert(".json");
-        extensions.insert(".yaml");
-        extensions.insert(".yml");
-        extensions.insert(".toml");
-        extensions.insert(".ini");
-        extensions.insert(".conf");
-        extensions.insert(".config");
-        // Keys & Credentials
-        extensions.insert(".pem");
-        extensions.insert(".key");
-        extensions.insert(".pub");
-        extensions.insert(".p12");
-        extensions.insert(".pfx");
-        extensions.insert(".crt");
-        extensions.insert(".cer");
-        // Database
-        extensions.insert(".sql");
-        extensions.insert(".db");
-        extensions.insert(".sqlite");
-        extensions.insert(".sqlite3");
-        // Backup
-        extensions.insert(".log");
-        extensions.insert(".bak");
-        extensions.insert(".backup");
-        extensions.insert(".old");
-        extensions
-    }
-
-    /// Get set of sensitive file names
-    pub fn get_sensitive_names() -> HashSet<&'static str> {
-        let mut names = HashSet::new();
-        names.insert(".env");
-        names.insert(".env.local");
-        names.insert(".env.production");
-        names.insert(".env.development");
-        names.insert("id_rsa");
-        names.insert("id_ed25519");
-        names.insert("id_dsa");
-        names.insert("id_ecdsa");
-        names.insert("known_hosts");
-        names.insert("authorized_keys");
-        names.insert("npmrc");
-        names.insert(".npmrc");
-        names.insert("pip.conf");
-        names.insert(".netrc");
-        names.insert(".git-credentials");
-        names.insert("service-account.json");
-        names.insert("credentials.json");
-        names.insert("secrets.yaml");
-        names.insert("secrets.yml");
-        names
-    }
-
-    /// Check if a file is sensitive
-    pub fn is_sensitive(path: &Path) -> bool {
-        let file_name = path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("");
-        
-        let extension = path.extension()
-            .and_then(|e| e.to_str())
-            .map(|e| format!(".{}", e))
-            .unwrap_or_default();
-
-        // Check by name first
-        if Self::get_sensitive_names().contains(&file_name) || file_name.starts_with(".env") {
-            return true;
-        }
-
-        // Check by extension
-        Self::get_extensions().contains(&extension.as_str())
-    }
+    /// Content pushed to a Vault KV path, see `vault`
+    Vaulted,
 }
 
 /// File protector for securing sensitive files
 pub struct FileProtector {
     secure_dir: PathBuf,
+    /// Argon2 salt written to encrypted files when the key comes from a passphrase
+    salt: Option<[u8; SALT_LEN]>,
+    exclude: PathGlobs,
+    /// Overwrite originals before deleting them after encryption
+    shred: bool,
+    /// Leave a stub pointing to the secure directory in place of moved files
+    stubs: bool,
+    /// Public keys encrypted files are encrypted to, each with its own file key
+    recipients: Vec<Recipient>,
+    /// File types picked up as sensitive, as in scans
+    risks: RiskTable,
 }
 
 impl FileProtector {
     /// Create a new file protector
     pub fn new(secure_dir: PathBuf) -> Self {
-        Self { secure_dir }
+        Self {
+            secure_dir,
+            salt: None,
+            exclude: PathGlobs::default(),
+            shred: false,
+            stubs: true,
+            recipients: Vec::new(),
+            risks: RiskTable::default(),
+        }
+    }
+
+    /// Write a passphrase header with this salt before each encrypted file
+    pub fn with_salt(mut self, salt: [u8; SALT_LEN]) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Overwrite the content of encrypted originals before deleting them
+    pub fn with_shred(mut self, shred: bool) -> Self {
+        self.shred = shred;
+        self
+    }
+
+    /// Leave a `.enveil` stub at the original location of moved files
+    pub fn with_stubs(mut self, stubs: bool) -> Self {
+        self.stubs = stubs;
+        self
+    }
+
+    /// Encrypt each file with a random key wrapped for these recipients
+    /// instead of a shared key
+    pub fn with_recipients(mut self, recipients: Vec<Recipient>) -> Self {
+        self.recipients = recipients;
+        self
+    }
+
+    /// Pick up the file types of this risk table (the `[severity]` of the config)
+    pub fn with_risks(mut self, risks: RiskTable) -> Self {
+        self.risks = risks;
+        self
+    }
+
+    /// Leave files matching these glob patterns (e.g. `fixtures/**`) in place
+    pub fn with_exclude(mut self, patterns: &[String]) -> Result<Self, String> {
+        self.exclude = PathGlobs::new(patterns)?;
+        Ok(self)
     }
 
-    /// Protect a file (move or encrypt based on option)
+    /// Protect a file (move or encrypt based on option). It keeps its path
+    /// relative to the secure directory's parent, the project root.
     pub fn protect_file(
         &self,
         source_path: &Path,
         action: &ProtectOption,
         key: Option<&[u8; 32]>,
+    ) -> ProtectResult {
+        let root = self.secure_dir.parent().unwrap_or(Path::new(""));
+        self.protect_at(source_path, &relative_to(root, source_path), action, key)
+    }
+
+    /// Protect a file into `relative` below the secure directory
+    fn protect_at(
+        &self,
+        source_path: &Path,
+        relative: &Path,
+        action: &ProtectOption,
+        key: Option<&[u8; 32]>,
     ) -> ProtectResult {
         let source_path = source_path.to_path_buf();
         
         if !source_path.exists() {
-            return ProtectResult {
-                original_path: source_path.to_string_lossy().to_string(),
-                protected_path: String::new(),
-                action: ProtectAction::Secured,
-                success: false,
-                message: "Source file does not exist".to_string(),
-            };
+            return failure(&source_path, ProtectAction::Secured, "Source file does not exist".to_string());
         }
 
         // Create secure directory if it doesn't exist
-        if !self.secure_dir.exists() {
-            if let Err(e) = fs::create_dir_all(&self.secure_dir) {
-                return ProtectResult {
-                    original_path: source_path.to_string_lossy().to_string(),
-                    protected_path: String::new(),
-                    action: ProtectAction::Secured,
-                    success: false,
-                    message: format!("Failed to create secure directory: {}", e),
-                };
-            }
+        if let Err(e) = self.ensure_secure_dir() {
+            return failure(&source_path, ProtectAction::Secured, e);
         }
 
-        match action {
-            ProtectOption::Move => self.move_to_secure(&source_path),
-            ProtectOption::Encrypt => self.encrypt_file(&source_path, key),
-            ProtectOption::Both => {
-                // First encrypt, then move
-                let encrypt_result = self.encrypt_file(&source_path, key);
-                if encrypt_result.success {
-                    // Remove original file after encryption
-                    let _ = fs::remove_file(&source_path);
-                    encrypt_result
-                } else {
-                    encrypt_result
-                }
-            }
+        let dest_path = match self.destination(&protected_relative(relative, action)) {
+            Ok(path) => path,
+            Err(e) => return failure(&source_path, ProtectAction::Secured, e),
+        };
+
+        let mut result = self.write_protected(&source_path, &dest_path, action, key);
+        if result.success {
+            self.remove_original(&mut result);
         }
+        result
     }
 
-    /// Move file to secure directory
-    fn move_to_secure(&self, source: &Path) -> ProtectResult {
-        let file_name = source.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-        
-        let dest_path = self.secure_dir.join(file_name);
+    /// Create the secure directory, readable only by the user
+    fn ensure_secure_dir(&self) -> Result<(), String> {
+        if self.secure_dir.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.secure_dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| restrict_permissions(&self.secure_dir, SECURE_DIR_MODE))
+            .map_err(|e| format!("Failed to create secure directory: {}", e))
+    }
 
-        // Handle naming conflicts
-        let dest_path = self.get_unique_path(&dest_path);
+    /// Write the protected copy of a file to `dest_path` and record it,
+    /// leaving the original in place
+    fn write_protected(
+        &self,
+        source: &Path,
+        dest_path: &Path,
+        action: &ProtectOption,
+        key: Option<&[u8; 32]>,
+    ) -> ProtectResult {
+        match action {
+            ProtectOption::Move => self.move_to_secure(source, dest_path),
+            // Encryption already moves the result into the secure directory
+            ProtectOption::Encrypt | ProtectOption::Both => self.encrypt_file(source, dest_path, key),
+        }
+    }
+
+    /// Remove the original of a written file, shredding it when encrypted
+    /// and asked to
+    fn remove_original(&self, result: &mut ProtectResult) {
+        let source = Path::new(&result.original_path);
+        let removed = if self.shred && result.action == ProtectAction::Encrypted {
+            shred_file(source)
+        } else {
+            fs::remove_file(source).map_err(|e| e.to_string())
+        };
+        let removed = removed.map_err(|e| format!("original not removed: {}", e));
+        let stubbed = match (&removed, &result.action) {
+            (Ok(()), ProtectAction::Moved) if self.stubs => Stub::new(Path::new(&result.protected_path), &self.secure_dir)
+                .write(source)
+                .map(|_| ()),
+            _ => Ok(()),
+        };
+        result.message = with_warning(with_warning(std::mem::take(&mut result.message), removed), stubbed);
+    }
 
-        match fs::copy(source, &dest_path) {
+    /// Copy file to secure directory
+    fn move_to_secure(&self, source: &Path, dest_path: &Path) -> ProtectResult {
+        match fs::copy(source, dest_path) {
             Ok(_) => {
-                // Remove original
-                let remove_result = fs::remove_file(source);
+                let recorded = self.record_protection(source, dest_path, ProtectAction::Moved, None, None);
+                let secured = restrict_permissions(dest_path, SECURE_FILE_MODE);
                 
                 ProtectResult {
                     original_path: source.to_string_lossy().to_string(),
                     protected_path: dest_path.to_string_lossy().to_string(),
                     action: ProtectAction::Moved,
                     success: true,
-                    message: if remove_result.is_ok() {
-                        "File moved to secure directory".to_string()
-                    } else {
-                        "File copied to secure directory (original removal failed)".to_string()
-                    },
+                    message: with_warning(with_warning("File moved to secure directory".to_string(), recorded), secured),
                 }
             }
-            Err(e) => ProtectResult {
-                original_path: source.to_string_lossy().to_string(),
-                protected_path: String::new(),
-                action: ProtectAction::Moved,
-                success: false,
-                message: format!("Failed to move file: {}", e),
-            },
+            Err(e) => failure(source, ProtectAction::Moved, format!("Failed to move file: {}", e)),
         }
     }
 
     /// Encrypt file with AES-256-GCM
-    fn encrypt_file(&self, source: &Path, key: Option<&[u8; 32]>) -> ProtectResult {
-        let file_name = source.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-        
+    fn encrypt_file(&self, source: &Path, dest_path: &Path, key: Option<&[u8; 32]>) -> ProtectResult {
         // Generate random key if not provided
         let key = match key {
+            // Files encrypted to recipients each get their own key
+            _ if !self.recipients.is_empty() => generate_key(),
             Some(k) => *k,
             None => {
-                let mut key = [0u8; 32];
-                rand::thread_rng().fill(&mut key);
+                let key = generate_key();
                 // In production, this key should be stored securely
                 // For now, we'll print it (in production, use proper key management)
-                eprintln!("⚠️  Generated encryption key (save this!): {}", base64::engine::general_purpose::STANDARD.encode(key));
+                emit!(stderr, "⚠️  Generated encryption key (save this!): {}", encode_key(&key));
                 key
             }
         };
 
-        // Read file content
-        let plaintext = match fs::read(source) {
-            Ok(data) => data,
-            Err(e) => {
-                return ProtectResult {
-                    original_path: source.to_string_lossy().to_string(),
-                    protected_path: String::new(),
-                    action: ProtectAction::Encrypted,
-                    success: false,
-                    message: format!("Failed to read file: {}", e),
-                };
-            }
-        };
-
-        // Create cipher
-        let cipher = match Aes256Gcm::new_from_slice(&key) {
-            Ok(c) => c,
-            Err(e) => {
-                return ProtectResult {
-                    original_path: source.to_string_lossy().to_string(),
-                    protected_path: String::new(),
-                    action: ProtectAction::Encrypted,
-                    success: false,
-                    message: format!("Failed to create cipher: {}", e),
-                };
-            }
-        };
-
-        // Generate random nonce
-        let mut nonce_bytes = [0u8; 12];
-        rand::thread_rng().fill(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        // A first pass computes the checksum stored in the header, the
+        // second streams the file through the cipher
+        let file_name = source.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let kdf = self.salt.as_ref().map(|salt| KdfParams::argon2id(salt));
+        let open = || fs::File::open(source).map(io::BufReader::new).map_err(|e| format!("Failed to read file: {}", e));
+        let written = open()
+            .and_then(|input| stream::checksum(&key, input))
+            .and_then(|checksum| {
+                let mut header = FileHeader::new(&file_name, kdf, checksum);
+                header.recipients = recipient::wrap_key(&key, &self.recipients)?;
+                let output = fs::File::create(dest_path)
+                    .map_err(|e| format!("Failed to write encrypted file: {}", e))?;
+                stream::encrypt(open()?, io::BufWriter::new(output), &key, &header)?;
+                Ok(header.nonce_prefix)
+            });
 
-        // Encrypt
-        let ciphertext = match cipher.encrypt(nonce, plaintext.as_ref()) {
-            Ok(ct) => ct,
-            Err(e) => {
-                return ProtectResult {
-                    original_path: source.to_string_lossy().to_string(),
-                    protected_path: String::new(),
-                    action: ProtectAction::Encrypted,
-                    success: false,
-                    message: format!("Encryption failed: {}", e),
+        match written {
+            Ok(nonce_prefix) => {
+                let recorded = self.record_protection(
+                    source,
+                    dest_path,
+                    ProtectAction::Encrypted,
+                    Some(nonce_prefix),
+                    // The file key is not shared, only the recipients matter
+                    Some(&key).filter(|_| self.recipients.is_empty()),
+                );
+                let secured = restrict_permissions(dest_path, SECURE_FILE_MODE);
+                
+                let message = if self.shred {
+                    "File encrypted and moved to secure directory, original shredded"
+                } else {
+                    "File encrypted and moved to secure directory"
                 };
-            }
-        };
-
-        // Prepend nonce to ciphertext
-        let mut encrypted_data = Vec::with_capacity(12 + ciphertext.len());
-        encrypted_data.extend_from_slice(&nonce_bytes);
-        encrypted_data.extend_from_slice(&ciphertext);
-
-        // Write encrypted file with .enc extension
-        let enc_file_name = format!("{}.enc", file_name);
-        let dest_path = self.secure_dir.join(&enc_file_name);
-        let dest_path = self.get_unique_path(&dest_path);
-
-        match fs::write(&dest_path, &encrypted_data) {
-            Ok(_) => {
-                // Remove original
-                let _ = fs::remove_file(source);
                 
                 ProtectResult {
                     original_path: source.to_string_lossy().to_string(),
                     protected_path: dest_path.to_string_lossy().to_string(),
                     action: ProtectAction::Encrypted,
                     success: true,
-                    message: "File encrypted and moved to secure directory".to_string(),
+                    message: with_warning(with_warning(message.to_string(), recorded), secured),
                 }
             }
-            Err(e) => ProtectResult {
-                original_path: source.to_string_lossy().to_string(),
-                protected_path: String::new(),
-                action: ProtectAction::Encrypted,
-                success: false,
-                message: format!("Failed to write encrypted file: {}", e),
-            },
+            Err(e) => {
+                // Never leave a partial encrypted file behind
+                let _ = fs::remove_file(dest_path);
+                failure(source, ProtectAction::Encrypted, e)
+            }
         }
     }
 
+    /// Record a protected file in the secure directory manifest
+    fn record_protection(
+        &self,
+        source: &Path,
+        dest: &Path,
+        action: ProtectAction,
+        nonce: Option<String>,
+        key: Option<&[u8; 32]>,
+    ) -> Result<(), String> {
+        // Store an absolute path so the file can be restored from anywhere
+        let original = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+
+        let mut entry = ManifestEntry::new(&original, dest, action);
+        entry.nonce = nonce;
+        entry.key_fingerprint = key.map(manifest::key_fingerprint);
+        entry.metadata = FileMetadata::capture(source).ok();
+        entry.digest = manifest::file_digest(dest).ok();
+        entry.relative_path = dest
+            .strip_prefix(&self.secure_dir)
+            .ok()
+            .map(|relative| relative.to_string_lossy().to_string());
+
+        Manifest::record(&self.secure_dir, entry)
+    }
+
+    /// Free path for `relative` below the secure directory, creating its
+    /// parent directories
+    fn destination(&self, relative: &Path) -> Result<PathBuf, String> {
+        let dest_path = self.secure_dir.join(relative);
+        if let Some(parent) = dest_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            for dir in parent.ancestors().take_while(|dir| dir.starts_with(&self.secure_dir)) {
+                restrict_permissions(dir, SECURE_DIR_MODE)?;
+            }
+        }
+
+        // Handle naming conflicts
+        Ok(self.get_unique_path(&dest_path))
+    }
+
     /// Get unique path by appending number if file exists
     fn get_unique_path(&self, path: &Path) -> PathBuf {
         if !path.exists() {
@@ -361,45 +384,332 @@ impl FileProtector {
             return results;
         }
 
-        self.scan_and_protect_recursive(dir_path, action, key, &mut results);
-        results
+        let files = self.find_sensitive_files(dir_path);
+        self.protect_files(dir_path, &files, action, key)
     }
 
-    fn scan_and_protect_recursive(
+    /// Protect a set of files of the project at `root`. Like
+    /// `protect_directory`, every protected copy is written before any
+    /// original is removed, and a failure rolls the whole run back.
+    pub fn protect_files(
         &self,
-        dir_path: &Path,
+        root: &Path,
+        files: &[PathBuf],
         action: &ProtectOption,
         key: Option<&[u8; 32]>,
-        results: &mut Vec<ProtectResult>,
-    ) {
-        let skip_dirs = [".git", "node_modules", "target", "dist", "build", "vendor", "enveil_secure"];
+    ) -> Vec<ProtectResult> {
+        let mut results = Vec::new();
 
+        if Journal::path(&self.secure_dir).exists() {
+            results.push(failure(
+                &Journal::path(&self.secure_dir),
+                ProtectAction::Secured,
+                "A previous protect run was interrupted: run `enveil protect --rollback` first".to_string(),
+            ));
+            return results;
+        }
+
+        if files.is_empty() {
+            return results;
+        }
+
+        if let Err(e) = self.ensure_secure_dir() {
+            results.push(failure(root, ProtectAction::Secured, e));
+            return results;
+        }
+
+        // Plan every destination and journal it before touching any file
+        let mut operations: Vec<JournalEntry> = Vec::new();
+        for path in files {
+            if !path.is_file() {
+                results.push(failure(path, ProtectAction::Secured, "Source file does not exist".to_string()));
+                return results;
+            }
+            let relative = protected_relative(&relative_to(root, path), action);
+            let dest = match self.destination(&relative) {
+                Ok(dest) => dest,
+                Err(e) => {
+                    results.push(failure(path, ProtectAction::Secured, e));
+                    return results;
+                }
+            };
+            operations.push(JournalEntry {
+                original_path: path.to_string_lossy().to_string(),
+                protected_path: dest.to_string_lossy().to_string(),
+                action: protect_action(action),
+                state: OpState::Planned,
+            });
+        }
+        let mut journal = Journal::new(operations);
+        if let Err(e) = journal.save(&self.secure_dir) {
+            results.push(failure(root, ProtectAction::Secured, e));
+            return results;
+        }
+
+        // Write every protected copy first: a failure leaves all originals
+        // in place and is rolled back
+        for (index, path) in files.iter().enumerate() {
+            let dest = PathBuf::from(&journal.operations[index].protected_path);
+            let mut result = self.write_protected(path, &dest, action, key);
+            if result.success {
+                if let Err(e) = journal.set_state(&self.secure_dir, index, OpState::Written) {
+                    result.success = false;
+                    result.message = e;
+                }
+            }
+            let failed = !result.success;
+            results.push(result);
+            if failed {
+                return self.abort(&journal, results);
+            }
+        }
+
+        // Then remove the originals
+        for (index, result) in results.iter_mut().enumerate() {
+            self.remove_original(result);
+            let saved = journal.set_state(&self.secure_dir, index, OpState::Completed);
+            result.message = with_warning(std::mem::take(&mut result.message), saved);
+            info!(original = %result.original_path, protected = %result.protected_path, "protected");
+        }
+
+        if let Err(e) = Journal::remove(&self.secure_dir) {
+            results.push(failure(&Journal::path(&self.secure_dir), ProtectAction::Secured, e));
+        }
+        results
+    }
+
+    /// Roll back a run whose last file could not be written
+    fn abort(&self, journal: &Journal, mut results: Vec<ProtectResult>) -> Vec<ProtectResult> {
+        let failed = results.last().map(|r| r.original_path.clone()).unwrap_or_default();
+        let rollback = journal.rollback(&self.secure_dir);
+
+        for result in results.iter_mut().filter(|r| r.success) {
+            let undone = match &rollback {
+                Ok(steps) => steps
+                    .iter()
+                    .find(|step| step.encrypted_path == result.protected_path)
+                    .map(|step| if step.success { Ok(()) } else { Err(step.message.clone()) })
+                    .unwrap_or(Ok(())),
+                Err(e) => Err(e.clone()),
+            };
+            result.success = false;
+            result.message = match undone {
+                Ok(()) => format!("Rolled back, {} could not be protected", failed),
+                Err(e) => format!("Not rolled back ({}): run `enveil protect --rollback`", e),
+            };
+        }
+        results
+    }
+
+    /// List sensitive files in a directory that protection would act on
+    pub fn find_sensitive_files(&self, dir_path: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        self.collect_sensitive_recursive(dir_path, dir_path, &mut files);
+        files
+    }
+
+    fn collect_sensitive_recursive(&self, root: &Path, dir_path: &Path, files: &mut Vec<PathBuf>) {
         if let Ok(entries) = fs::read_dir(dir_path) {
             for entry in entries.flatten() {
                 let path = entry.path();
 
+                if self.exclude.matches(root, &path) {
+                    debug!(path = %path.display(), "skipped: excluded");
+                    continue;
+                }
+
                 if path.is_dir() {
                     let dir_name = path.file_name()
                         .and_then(|n| n.to_str())
                         .unwrap_or("");
                     
-                    if !dir_name.starts_with('.') && !skip_dirs.contains(&dir_name) {
-                        self.scan_and_protect_recursive(&path, action, key, results);
+                    if !sensitivity::is_skipped_dir(dir_name, &self.secure_dir, false) {
+                        self.collect_sensitive_recursive(root, &path, files);
                     }
-                } else if path.is_file() && SensitiveFiles::is_sensitive(&path) {
+                } else if path.is_file() && self.risks.needs_protection(&path) {
                     // Skip if already in secure directory
-                    if path.parent().map(|p| p == self.secure_dir).unwrap_or(false) {
+                    if path.starts_with(&self.secure_dir) {
                         continue;
                     }
                     
-                    let result = self.protect_file(&path, action, key);
-                    results.push(result);
+                    debug!(path = %path.display(), "sensitive file");
+                    files.push(path);
                 }
             }
         }
     }
 }
 
+/// Path of `source` below `root` without `.` components, or only its file
+/// name when it is not below `root`
+pub(crate) fn relative_to(root: &Path, source: &Path) -> PathBuf {
+    let below_root = source
+        .strip_prefix(root)
+        .ok()
+        .filter(|relative| relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)))
+        .map(|relative| {
+            relative
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .collect::<PathBuf>()
+        })
+        .filter(|relative| !relative.as_os_str().is_empty());
+
+    match below_root {
+        Some(relative) => relative,
+        None => PathBuf::from(source.file_name().unwrap_or_else(|| OsStr::new("unknown"))),
+    }
+}
+
+/// Path below the secure directory of a file protected with `action`
+fn protected_relative(relative: &Path, action: &ProtectOption) -> PathBuf {
+    match action {
+        ProtectOption::Move => relative.to_path_buf(),
+        ProtectOption::Encrypt | ProtectOption::Both => {
+            let file_name = relative.file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+            relative.with_file_name(format!("{}.enc", file_name))
+        }
+    }
+}
+
+/// Action recorded for a protect option
+fn protect_action(action: &ProtectOption) -> ProtectAction {
+    match action {
+        ProtectOption::Move => ProtectAction::Moved,
+        ProtectOption::Encrypt | ProtectOption::Both => ProtectAction::Encrypted,
+    }
+}
+
+fn failure(path: &Path, action: ProtectAction, message: String) -> ProtectResult {
+    ProtectResult {
+        original_path: path.to_string_lossy().to_string(),
+        protected_path: String::new(),
+        action,
+        success: false,
+        message,
+    }
+}
+
+/// Size of the buffer `shred_file` overwrites files with
+const SHRED_CHUNK: usize = 64 * 1024;
+
+/// Overwrite a file with random bytes, then zeros, flushing each pass to
+/// disk, and delete it.
+///
+/// Best effort only: SSDs (wear levelling), copy-on-write and journaling
+/// filesystems (btrfs, ZFS, APFS) and snapshots or backups can keep the old
+/// blocks. Full-disk encryption is the only reliable protection there.
+pub fn shred_file(path: &Path) -> Result<(), String> {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let len = fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len();
+    let mut file = fs::File::options()
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    // Overwrite in fixed-size chunks so large files are not held in memory
+    let mut buffer = vec![0u8; SHRED_CHUNK];
+    for random in [true, false] {
+        let mut pass = || -> std::io::Result<()> {
+            file.seek(SeekFrom::Start(0))?;
+            let mut remaining = len;
+            while remaining > 0 {
+                let chunk = &mut buffer[..remaining.min(SHRED_CHUNK as u64) as usize];
+                if random {
+                    rand::thread_rng().fill(&mut *chunk);
+                } else {
+                    chunk.fill(0);
+                }
+                file.write_all(chunk)?;
+                remaining -= chunk.len() as u64;
+            }
+            file.sync_all()
+        };
+        pass().map_err(|e| format!("Failed to overwrite {}: {}", path.display(), e))?;
+    }
+    drop(file);
+
+    fs::remove_file(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
+
+/// Set Unix permission bits (nothing on other platforms)
+pub fn restrict_permissions(path: &Path, mode: u32) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("Failed to set permissions of {}: {}", path.display(), e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+
+    Ok(())
+}
+
+fn with_warning(message: String, outcome: Result<(), String>) -> String {
+    match outcome {
+        Ok(()) => message,
+        Err(e) => format!("{} (warning: {})", message, e),
+    }
+}
+
+/// Generate a random AES-256 key
+pub fn generate_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill(&mut key);
+    key
+}
+
+/// Encode a key as base64 for display
+pub fn encode_key(key: &[u8; 32]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(key)
+}
+
+/// Decode a base64 key as printed by `encode_key`
+pub fn decode_key(encoded: &str) -> Result<[u8; 32], String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid base64 key: {}", e))?;
+
+    bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| format!("Invalid key length: expected 32 bytes, got {}", b.len()))
+}
+
+/// Generate a random salt for passphrase key derivation
+pub fn generate_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill(&mut salt);
+    salt
+}
+
+/// Derive an AES-256 key from a passphrase with Argon2id
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    derive_key_with(
+        passphrase,
+        salt,
+        argon2::Params::DEFAULT_M_COST,
+        argon2::Params::DEFAULT_T_COST,
+        argon2::Params::DEFAULT_P_COST,
+    )
+}
+
+/// Derive a key with explicit Argon2id costs, as recorded in file headers
+pub fn derive_key_with(passphrase: &str, salt: &[u8], memory_kib: u32, iterations: u32, parallelism: u32) -> Result<[u8; 32], String> {
+    let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
 /// Protection options
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProtectOption {
@@ -408,13 +718,15 @@ pub enum ProtectOption {
     Both,
 }
 
-impl ProtectOption {
-    pub fn from_str(s: &str) -> Self {
+impl FromStr for ProtectOption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "move" => ProtectOption::Move,
-            "encrypt" => ProtectOption::Encrypt,
-            "both" => ProtectOption::Both,
-            _ => ProtectOption::Move, // Default
+            "move" => Ok(ProtectOption::Move),
+            "encrypt" => Ok(ProtectOption::Encrypt),
+            "both" => Ok(ProtectOption::Both),
+            _ => Err(format!("Unknown protect action: {} (expected move, encrypt or both)", s)),
         }
     }
 }
@@ -422,30 +734,207 @@ impl ProtectOption {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
     use tempfile::TempDir;
 
     #[test]
-    fn test_is_sensitive_env() {
-        let path = Path::new("/project/.env");
-        assert!(SensitiveFiles::is_sensitive(path));
+    fn test_secure_dir_mirrors_project_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        for dir in ["services/api", "services/web"] {
+            fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join(".env"), "KEY=value\n").unwrap();
+        }
+
+        let protector = FileProtector::new(secure_dir.clone());
+        let results = protector.protect_directory(temp_dir.path(), &ProtectOption::Move, None);
+        assert!(results.iter().all(|r| r.success));
+        assert!(secure_dir.join("services/api/.env").exists());
+        assert!(secure_dir.join("services/web/.env").exists());
+        assert!(!Journal::path(&secure_dir).exists());
+
+        fs::create_dir_all(temp_dir.path().join("services/api")).unwrap();
+        fs::write(temp_dir.path().join("services/api/.env"), "KEY=other\n").unwrap();
+        protector.protect_file(&temp_dir.path().join("services/api/.env"), &ProtectOption::Encrypt, Some(&generate_key()));
+        assert!(secure_dir.join("services/api/.env.enc").exists());
+
+        let manifest = Manifest::load(&secure_dir).unwrap();
+        let relative: Vec<_> = manifest.entries.iter().filter_map(|e| e.relative_path.as_deref()).collect();
+        assert!(relative.contains(&Path::new("services").join("web").join(".env").to_str().unwrap()));
+        assert!(relative.contains(&Path::new("services").join("api").join(".env.enc").to_str().unwrap()));
+    }
+
+    #[test]
+    fn test_interrupted_run_blocks_protect() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::create_dir_all(&secure_dir).unwrap();
+        fs::write(temp_dir.path().join(".env"), "KEY=value\n").unwrap();
+        Journal::new(Vec::new()).save(&secure_dir).unwrap();
+
+        let results = FileProtector::new(secure_dir).protect_directory(temp_dir.path(), &ProtectOption::Move, None);
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].success);
+        assert!(results[0].message.contains("--rollback"));
+        assert!(temp_dir.path().join(".env").exists());
+    }
+
+    #[test]
+    fn test_failed_plan_leaves_originals() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::create_dir_all(&secure_dir).unwrap();
+        fs::create_dir_all(temp_dir.path().join("api")).unwrap();
+        fs::write(temp_dir.path().join(".env"), "KEY=value\n").unwrap();
+        fs::write(temp_dir.path().join("api/.env"), "KEY=value\n").unwrap();
+        // A file where the mirrored directory should go
+        fs::write(secure_dir.join("api"), "").unwrap();
+
+        let results = FileProtector::new(secure_dir.clone()).protect_directory(temp_dir.path(), &ProtectOption::Move, None);
+        assert!(results.iter().any(|r| !r.success));
+        assert!(temp_dir.path().join(".env").exists());
+        assert!(temp_dir.path().join("api/.env").exists());
+        assert!(!Journal::path(&secure_dir).exists());
+    }
+
+    #[test]
+    fn test_encrypt_with_shred_deletes_original() {
+        let temp_dir = TempDir::new().unwrap();
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let protector = FileProtector::new(temp_dir.path().join("enveil_secure")).with_shred(true);
+        let result = protector.protect_file(&env_file, &ProtectOption::Both, Some(&generate_key()));
+        assert!(result.success);
+        assert!(result.message.contains("original shredded"), "{}", result.message);
+        assert!(!env_file.exists());
+
+        assert!(shred_file(&env_file).is_err());
+    }
+
+    #[test]
+    fn test_shred_overwrites_files_larger_than_a_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("dump.sql");
+        let content = "INSERT INTO users VALUES ('secret');\n".repeat(5000);
+        assert!(content.len() > SHRED_CHUNK);
+        fs::write(&file, &content).unwrap();
+        // A second link to the same blocks shows what shredding left behind
+        let link = temp_dir.path().join("link.sql");
+        fs::hard_link(&file, &link).unwrap();
+
+        shred_file(&file).unwrap();
+        assert!(!file.exists());
+        assert_eq!(fs::read(&link).unwrap(), vec![0u8; content.len()]);
+    }
+
+    #[test]
+    fn test_find_sensitive_files_skips_secure_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::create_dir_all(&secure_dir).unwrap();
+        fs::write(temp_dir.path().join(".env"), "KEY=value\n").unwrap();
+        fs::write(temp_dir.path().join("readme.txt"), "hello\n").unwrap();
+        fs::write(secure_dir.join("old.env"), "KEY=value\n").unwrap();
+
+        let protector = FileProtector::new(secure_dir);
+        let files = protector.find_sensitive_files(temp_dir.path());
+
+        assert_eq!(files, vec![temp_dir.path().join(".env")]);
+    }
+
+    #[test]
+    fn test_find_sensitive_files_honors_exclude() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixtures = temp_dir.path().join("fixtures");
+        fs::create_dir_all(&fixtures).unwrap();
+        fs::write(fixtures.join(".env"), "KEY=value\n").unwrap();
+        fs::write(temp_dir.path().join(".env"), "KEY=value\n").unwrap();
+        fs::write(temp_dir.path().join("schema.generated.json"), "{}\n").unwrap();
+
+        let protector = FileProtector::new(temp_dir.path().join("enveil_secure"))
+            .with_exclude(&["fixtures/**".to_string(), "*.generated.json".to_string()])
+            .unwrap();
+
+        assert_eq!(protector.find_sensitive_files(temp_dir.path()), vec![temp_dir.path().join(".env")]);
+    }
+
+    #[test]
+    fn test_find_sensitive_files_honors_risk_table() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("passwords.kdbx"), "vault\n").unwrap();
+        fs::write(temp_dir.path().join("dump.bak"), "data\n").unwrap();
+        let overrides = [(".kdbx", "high"), (".bak", "none")]
+            .iter()
+            .map(|(file_type, level)| (file_type.to_string(), level.to_string()))
+            .collect();
+
+        let protector = FileProtector::new(temp_dir.path().join("enveil_secure"));
+        assert_eq!(protector.find_sensitive_files(temp_dir.path()), vec![temp_dir.path().join("dump.bak")]);
+        let protector = protector.with_risks(RiskTable::new(&overrides));
+        assert_eq!(protector.find_sensitive_files(temp_dir.path()), vec![temp_dir.path().join("passwords.kdbx")]);
+    }
+
+    #[test]
+    fn test_protect_records_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "KEY=value\n").unwrap();
+        let original = fs::canonicalize(&env_file).unwrap();
+
+        let key = generate_key();
+        let result = FileProtector::new(secure_dir.clone()).protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+        assert!(result.success);
+
+        let manifest = Manifest::load(&secure_dir).unwrap();
+        assert_eq!(manifest.entries.len(), 1);
+        let entry = &manifest.entries[0];
+        assert_eq!(entry.original_path, original.to_string_lossy());
+        assert_eq!(entry.action, ProtectAction::Encrypted);
+        assert!(entry.nonce.is_some());
+        assert_eq!(entry.key_fingerprint, Some(manifest::key_fingerprint(&key)));
+    }
+
+    #[test]
+    fn test_key_round_trip() {
+        let key = generate_key();
+        assert_eq!(decode_key(&encode_key(&key)).unwrap(), key);
+        assert!(decode_key("c2hvcnQ=").is_err());
     }
 
     #[test]
-    fn test_is_sensitive_json() {
-        let path = Path::new("/project/credentials.json");
-        assert!(SensitiveFiles::is_sensitive(path));
+    fn test_derive_key_depends_on_salt() {
+        let salt = generate_salt();
+        let key = derive_key("correct horse", &salt).unwrap();
+        assert_eq!(derive_key("correct horse", &salt).unwrap(), key);
+        assert_ne!(derive_key("correct horse", &generate_salt()).unwrap(), key);
+        assert_ne!(derive_key("battery staple", &salt).unwrap(), key);
     }
 
     #[test]
-    fn test_is_sensitive_rsa_key() {
-        let path = Path::new("/project/id_rsa");
-        assert!(SensitiveFiles::is_sensitive(path));
+    fn test_salt_header_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "KEY=value\n").unwrap();
+
+        let salt = generate_salt();
+        let key = derive_key("passphrase", &salt).unwrap();
+        let result = FileProtector::new(secure_dir)
+            .with_salt(salt)
+            .protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+        assert!(result.success);
+
+        let data = fs::read(&result.protected_path).unwrap();
+        let (header, _) = FileHeader::read(&mut data.as_slice()).unwrap();
+        assert_eq!(header.kdf, Some(KdfParams::argon2id(&salt)));
+        assert_eq!(header.file_name, ".env");
     }
 
     #[test]
-    fn test_is_not_sensitive() {
-        let path = Path::new("/project/readme.txt");
-        assert!(!SensitiveFiles::is_sensitive(path));
+    fn test_protect_option_from_str() {
+        assert_eq!("encrypt".parse::<ProtectOption>().unwrap(), ProtectOption::Encrypt);
+        assert_eq!("BOTH".parse::<ProtectOption>().unwrap(), ProtectOption::Both);
+        assert!("shred".parse::<ProtectOption>().is_err());
     }
 }
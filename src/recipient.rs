@@ -0,0 +1,232 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use base64::Engine;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::protector;
+
+/// Prefix of encoded recipients (public keys)
+pub const RECIPIENT_PREFIX: &str = "enveil1";
+
+/// Prefix of encoded identities (private keys)
+pub const IDENTITY_PREFIX: &str = "ENVEIL-SECRET-KEY-";
+
+/// Domain separation of the key wrapping keys
+const WRAP_INFO: &[u8] = b"enveil x25519 file key";
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn decode_32(encoded: &str, what: &str) -> Result<[u8; 32], String> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| format!("Invalid {}", what))
+}
+
+/// Public key files are encrypted to
+#[derive(Clone, Copy, PartialEq)]
+pub struct Recipient(PublicKey);
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", RECIPIENT_PREFIX, encode(self.0.as_bytes()))
+    }
+}
+
+impl fmt::Debug for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Recipient({})", self)
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .trim()
+            .strip_prefix(RECIPIENT_PREFIX)
+            .ok_or_else(|| format!("Invalid recipient {} (expected {}…)", s, RECIPIENT_PREFIX))?;
+        decode_32(encoded, "recipient").map(|bytes| Recipient(PublicKey::from(bytes)))
+    }
+}
+
+/// Private key of a teammate, decrypting the files encrypted to its recipient
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Identity({})", self.recipient())
+    }
+}
+
+impl FromStr for Identity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .trim()
+            .strip_prefix(IDENTITY_PREFIX)
+            .ok_or_else(|| format!("Invalid identity (expected {}…)", IDENTITY_PREFIX))?;
+        decode_32(encoded, "identity").map(|bytes| Identity(StaticSecret::from(bytes)))
+    }
+}
+
+impl Identity {
+    /// Generate a new identity
+    pub fn generate() -> Self {
+        Identity(StaticSecret::random_from_rng(rand::rngs::OsRng))
+    }
+
+    /// Public key to give to whoever encrypts files for this identity
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    /// Encoded private key
+    pub fn to_secret_string(&self) -> String {
+        format!("{}{}", IDENTITY_PREFIX, encode(self.0.as_bytes()))
+    }
+
+    /// Read an identity file, skipping `#` comment lines
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read identity {}: {}", path.display(), e))?;
+
+        content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .ok_or_else(|| format!("No identity in {}", path.display()))?
+            .parse()
+    }
+
+    /// Write the identity to a new file only the owner can read
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+
+        let content = format!(
+            "# recipient: {}\n{}\n",
+            self.recipient(),
+            self.to_secret_string()
+        );
+        fs::write(path, content).map_err(|e| format!("Failed to write identity {}: {}", path.display(), e))?;
+        protector::restrict_permissions(path, protector::SECURE_FILE_MODE)
+    }
+
+    /// Recover the file key from the wrapped key meant for this identity
+    pub fn unwrap_key(&self, wrapped: &[WrappedKey]) -> Result<[u8; 32], String> {
+        let recipient = self.recipient();
+
+        for entry in wrapped {
+            let ephemeral = match decode_32(&entry.ephemeral, "wrapped key") {
+                Ok(bytes) => PublicKey::from(bytes),
+                Err(_) => continue,
+            };
+            let shared = self.0.diffie_hellman(&ephemeral);
+            let cipher = wrapping_cipher(shared.as_bytes(), &ephemeral, &recipient.0);
+            let sealed = match base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(&entry.key) {
+                Ok(sealed) => sealed,
+                Err(_) => continue,
+            };
+
+            if let Ok(key) = cipher.decrypt(Nonce::from_slice(&[0u8; 12]), sealed.as_slice()) {
+                if let Ok(key) = <[u8; 32]>::try_from(key) {
+                    return Ok(key);
+                }
+            }
+        }
+
+        Err("File was not encrypted to this identity".to_string())
+    }
+}
+
+/// File key encrypted to one recipient, stored in the file header
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WrappedKey {
+    /// Base64 ephemeral X25519 public key
+    pub ephemeral: String,
+    /// Base64 AES-256-GCM encrypted file key
+    pub key: String,
+}
+
+/// AES-256-GCM cipher keyed with HKDF-SHA256 of an X25519 shared secret.
+/// Each wrapping key is used once, so the nonce is fixed.
+fn wrapping_cipher(shared: &[u8; 32], ephemeral: &PublicKey, recipient: &PublicKey) -> Aes256Gcm {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes().as_slice()].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Aes256Gcm::new_from_slice(&key).expect("32 bytes is a valid AES-256 key")
+}
+
+/// Encrypt a file key to every recipient
+pub fn wrap_key(key: &[u8; 32], recipients: &[Recipient]) -> Result<Vec<WrappedKey>, String> {
+    recipients
+        .iter()
+        .map(|recipient| {
+            let secret = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+            let ephemeral = PublicKey::from(&secret);
+            let shared = secret.diffie_hellman(&recipient.0);
+            let sealed = wrapping_cipher(shared.as_bytes(), &ephemeral, &recipient.0)
+                .encrypt(Nonce::from_slice(&[0u8; 12]), key.as_slice())
+                .map_err(|e| format!("Failed to wrap file key: {}", e))?;
+
+            Ok(WrappedKey {
+                ephemeral: encode(ephemeral.as_bytes()),
+                key: encode(&sealed),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_wrap_and_unwrap() {
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let key = protector::generate_key();
+
+        let wrapped = wrap_key(&key, &[alice.recipient(), bob.recipient()]).unwrap();
+        assert_eq!(wrapped.len(), 2);
+        assert_eq!(alice.unwrap_key(&wrapped).unwrap(), key);
+        assert_eq!(bob.unwrap_key(&wrapped).unwrap(), key);
+        assert!(Identity::generate().unwrap_key(&wrapped).is_err());
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let identity = Identity::generate();
+        let recipient = identity.recipient();
+
+        assert!(recipient.to_string().starts_with(RECIPIENT_PREFIX));
+        assert_eq!(recipient.to_string().parse::<Recipient>().unwrap(), recipient);
+        assert!("enveil1short".parse::<Recipient>().is_err());
+
+        let path = temp_dir.path().join("identity.txt");
+        identity.save(&path).unwrap();
+        assert_eq!(Identity::load(&path).unwrap().recipient(), recipient);
+        assert!(identity.save(&path).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::io::{self, BufReader, PipeReader};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use tempfile::NamedTempFile;
+
+use crate::manifest::{self, Manifest};
+use crate::protector::{self, SALT_LEN};
+use crate::restorer::{DecryptionKey, FileRestorer};
+use crate::stream::{self, FileHeader, KdfParams};
+
+/// Key the files of a secure directory are re-encrypted with
+#[derive(Debug, Clone)]
+pub struct NewKey {
+    pub key: [u8; 32],
+    /// Salt the key was derived with, for passphrase keys
+    pub salt: Option<[u8; SALT_LEN]>,
+}
+
+impl NewKey {
+    pub fn raw(key: [u8; 32]) -> Self {
+        Self { key, salt: None }
+    }
+
+    /// Derive a new key from a passphrase, with a fresh salt
+    pub fn from_passphrase(passphrase: &str) -> Result<Self, String> {
+        let salt = protector::generate_salt();
+        let key = protector::derive_key(passphrase, &salt)?;
+        Ok(Self { key, salt: Some(salt) })
+    }
+}
+
+/// Re-encrypt every `.enc` file of the secure directory from `old` to `new`.
+/// All files are written to temporary files first and only renamed over the
+/// originals once every one of them succeeded, so a wrong key or a corrupt
+/// file leaves the directory untouched. The plaintext never touches the disk.
+/// Returns the re-encrypted files.
+pub fn rekey(secure_dir: &Path, old: &DecryptionKey, new: &NewKey) -> Result<Vec<PathBuf>, String> {
+    let files = FileRestorer::new(secure_dir.to_path_buf()).find_encrypted_files();
+    let mut manifest = Manifest::load(secure_dir)?;
+
+    let mut staged = Vec::new();
+    for file in &files {
+        match reencrypt(file, old, new) {
+            Ok(temp) => staged.push(temp),
+            // Staged files are deleted when dropped
+            Err(e) => return Err(format!("{}: {} (no file was changed)", file.display(), e)),
+        }
+    }
+
+    let fingerprint = manifest::key_fingerprint(&new.key);
+    for (file, (temp, nonce_prefix)) in files.iter().zip(staged) {
+        temp.persist(file)
+            .map_err(|e| format!("Failed to replace {}: {}", file.display(), e.error))?;
+        protector::restrict_permissions(file, protector::SECURE_FILE_MODE)?;
+
+        let entry = manifest
+            .entries
+            .iter()
+            .rposition(|e| e.location(secure_dir).as_deref() == Some(file.as_path()));
+        if let Some(index) = entry {
+            manifest.entries[index].key_fingerprint = Some(fingerprint.clone());
+            manifest.entries[index].nonce = Some(nonce_prefix);
+            manifest.entries[index].digest = manifest::file_digest(file).ok();
+        }
+    }
+
+    manifest.save(secure_dir)?;
+    Ok(files)
+}
+
+/// Write `file` re-encrypted under the new key to a temporary file next to
+/// it, returning it with its nonce prefix
+fn reencrypt(file: &Path, old: &DecryptionKey, new: &NewKey) -> Result<(NamedTempFile, String), String> {
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let file_name = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let kdf = new.salt.as_ref().map(|salt| KdfParams::argon2id(salt));
+
+    // Like protect: one pass for the checksum, one through the cipher
+    let checksum = with_plaintext(file, old, |plaintext| stream::checksum(&new.key, plaintext))?;
+    let header = FileHeader::new(&file_name, kdf, checksum);
+
+    let mut temp = NamedTempFile::new_in(dir)
+        .map_err(|e| format!("Failed to create temporary file: {}", e))?;
+    with_plaintext(file, old, |plaintext| {
+        stream::encrypt(plaintext, io::BufWriter::new(temp.as_file_mut()), &new.key, &header)
+    })?;
+
+    Ok((temp, header.nonce_prefix))
+}
+
+/// Hand the plaintext of an encrypted file to `consume`, decrypting it on
+/// another thread through a pipe. A decryption failure (wrong key, tampered
+/// file) wins over whatever `consume` returned.
+fn with_plaintext<T>(file: &Path, key: &DecryptionKey, consume: impl FnOnce(PipeReader) -> Result<T, String>) -> Result<T, String> {
+    let input = fs::File::open(file).map_err(|e| format!("Failed to read file: {}", e))?;
+    let (reader, writer) = io::pipe().map_err(|e| format!("Failed to create pipe: {}", e))?;
+    let key = key.clone();
+    let decryption = thread::spawn(move || key.decrypt_stream(BufReader::new(input), writer));
+
+    // The reader is dropped here, so the decryption stops if it is not read to the end
+    let consumed = consume(reader);
+    decryption
+        .join()
+        .map_err(|_| "Decryption thread panicked".to_string())??;
+    consumed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::{FileProtector, ProtectOption};
+    use tempfile::TempDir;
+
+    fn protected_project(key: &[u8; 32]) -> (TempDir, PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::write(temp_dir.path().join(".env"), "API_KEY=abc\n").unwrap();
+        fs::write(temp_dir.path().join("id_rsa"), "key\n").unwrap();
+
+        let protector = FileProtector::new(secure_dir.clone());
+        let results = protector.protect_directory(temp_dir.path(), &ProtectOption::Encrypt, Some(key));
+        assert!(results.iter().all(|r| r.success));
+        (temp_dir, secure_dir)
+    }
+
+    #[test]
+    fn test_rekey_round_trip() {
+        let old = protector::generate_key();
+        let new = NewKey::raw(protector::generate_key());
+        let (_temp_dir, secure_dir) = protected_project(&old);
+
+        let files = rekey(&secure_dir, &DecryptionKey::Raw(old), &new).unwrap();
+        assert_eq!(files.len(), 2);
+
+        let env = fs::read(secure_dir.join(".env.enc")).unwrap();
+        assert_eq!(DecryptionKey::Raw(new.key).decrypt(&env).unwrap(), b"API_KEY=abc\n");
+        assert!(DecryptionKey::Raw(old).decrypt(&env).is_err());
+
+        let manifest = Manifest::load(&secure_dir).unwrap();
+        let fingerprint = manifest::key_fingerprint(&new.key);
+        assert!(manifest.entries.iter().all(|e| e.key_fingerprint.as_ref() == Some(&fingerprint)));
+    }
+
+    #[test]
+    fn test_rekey_to_passphrase() {
+        let old = protector::generate_key();
+        let new = NewKey::from_passphrase("correct horse").unwrap();
+        let (_temp_dir, secure_dir) = protected_project(&old);
+
+        rekey(&secure_dir, &DecryptionKey::Raw(old), &new).unwrap();
+
+        let env = fs::read(secure_dir.join(".env.enc")).unwrap();
+        let passphrase = DecryptionKey::Passphrase("correct horse".to_string());
+        assert_eq!(passphrase.decrypt(&env).unwrap(), b"API_KEY=abc\n");
+    }
+
+    #[test]
+    fn test_wrong_key_changes_nothing() {
+        let old = protector::generate_key();
+        let (_temp_dir, secure_dir) = protected_project(&old);
+        let before = fs::read(secure_dir.join(".env.enc")).unwrap();
+        let manifest = Manifest::load(&secure_dir).unwrap();
+
+        let wrong = DecryptionKey::Raw(protector::generate_key());
+        assert!(rekey(&secure_dir, &wrong, &NewKey::raw(protector::generate_key())).is_err());
+
+        assert_eq!(fs::read(secure_dir.join(".env.enc")).unwrap(), before);
+        assert_eq!(Manifest::load(&secure_dir).unwrap(), manifest);
+        let leftovers = fs::read_dir(&secure_dir).unwrap().filter(|e| {
+            let name = e.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with(".tmp")
+        });
+        assert_eq!(leftovers.count(), 0);
+    }
+}
@@ -0,0 +1,1042 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::baseline::Baseline;
+use crate::detector::{self, SecretFinding};
+use crate::git;
+use crate::style::{self, Stream};
+
+/// A risky file found during a scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub path: String,
+    pub file_type: String,
+    pub risk_level: String,
+}
+
+/// Secrets detected in a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretFileReport {
+    pub path: String,
+    pub findings: Vec<SecretFinding>,
+}
+
+/// Where a secret was found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub path: String,
+    pub line_number: usize,
+}
+
+/// The same secret found in several places, grouped by fingerprint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretGroup {
+    pub fingerprint: String,
+    pub rule_id: String,
+    pub secret_type: String,
+    pub severity: String,
+    pub locations: Vec<Location>,
+}
+
+/// Full result of a scan run. Files and secrets are sorted by path and
+/// findings by line, and fields serialize in declaration order, so reports
+/// of the same tree diff cleanly between runs and machines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub total_files: usize,
+    pub risky_files: usize,
+    pub files: Vec<ScanResult>,
+    pub secrets_found: usize,
+    pub secrets: Vec<SecretFileReport>,
+    /// Secrets found in more than one place
+    #[serde(default)]
+    pub duplicates: Vec<SecretGroup>,
+    /// Matches skipped because of inline `enveil:ignore` markers
+    #[serde(default)]
+    pub suppressed: usize,
+    /// Findings skipped because they are in the baseline
+    #[serde(default)]
+    pub baselined: usize,
+    /// Files not scanned because they are over the size limit
+    #[serde(default)]
+    pub skipped: Vec<String>,
+    /// Reported files already committed to git: their secrets are exposed
+    /// in the history and must be rotated
+    #[serde(default)]
+    pub committed: Vec<String>,
+}
+
+impl ScanReport {
+    pub fn new(mut files: Vec<ScanResult>, mut secrets: Vec<SecretFileReport>, suppressed: usize) -> Self {
+        files.sort_by(|a, b| path_order(&a.path, &b.path));
+        secrets.sort_by(|a, b| path_order(&a.path, &b.path));
+        for file in &mut secrets {
+            file.findings.sort_by_key(|f| f.line_number);
+        }
+
+        Self {
+            total_files: files.len(),
+            risky_files: files.len(),
+            files,
+            secrets_found: secrets.iter().map(|s| s.findings.len()).sum(),
+            duplicates: group_duplicates(&secrets),
+            secrets,
+            suppressed,
+            baselined: 0,
+            skipped: Vec::new(),
+            committed: Vec::new(),
+        }
+    }
+
+    /// Load a report saved with `enveil scan --format json --output <file>`
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read report {}: {}", path.display(), e))?;
+
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid scan report {}: {}", path.display(), e))
+    }
+
+    /// Report paths relative to the scanned `root` (e.g. a temporary
+    /// checkout). The root itself, when a single file was scanned, keeps its path.
+    pub fn relative_to(&mut self, root: &std::path::Path) {
+        let relative = |path: &mut String| {
+            match std::path::Path::new(path.as_str()).strip_prefix(root) {
+                Ok(stripped) if !stripped.as_os_str().is_empty() => *path = stripped.to_string_lossy().to_string(),
+                _ => {}
+            }
+        };
+
+        self.files.iter_mut().for_each(|f| relative(&mut f.path));
+        self.secrets.iter_mut().for_each(|s| relative(&mut s.path));
+        self.skipped.iter_mut().for_each(relative);
+        self.committed.iter_mut().for_each(relative);
+        for group in &mut self.duplicates {
+            group.locations.iter_mut().for_each(|l| relative(&mut l.path));
+        }
+    }
+
+    /// Mark the reported files that are in the git history of the scanned
+    /// `root`. Nothing is marked outside a git repository.
+    pub fn mark_committed(&mut self, root: &std::path::Path) {
+        let dir = if root.is_file() { root.parent().unwrap_or(root) } else { root };
+        let git_dir = if dir.as_os_str().is_empty() { std::path::Path::new(".") } else { dir };
+        let committed: HashSet<String> = match git::committed_files(git_dir) {
+            Ok(files) => files.into_iter().collect(),
+            Err(_) => return,
+        };
+
+        let paths = self.files.iter().map(|f| &f.path).chain(self.secrets.iter().map(|s| &s.path));
+        let mut marked: Vec<String> = paths
+            .filter(|path| {
+                std::path::Path::new(path.as_str())
+                    .strip_prefix(dir)
+                    .map(|relative| {
+                        let relative: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+                        committed.contains(&relative.join("/"))
+                    })
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        marked.sort();
+        marked.dedup();
+        self.committed = marked;
+    }
+
+    /// Whether a reported file is in the git history
+    pub fn is_committed(&self, path: &str) -> bool {
+        self.committed.iter().any(|p| p == path)
+    }
+
+    /// Drop the findings whose secret is in the baseline
+    pub fn apply_baseline(&mut self, baseline: &Baseline) {
+        for file in &mut self.secrets {
+            let before = file.findings.len();
+            file.findings.retain(|f| !baseline.contains(f));
+            self.baselined += before - file.findings.len();
+        }
+        self.secrets.retain(|file| !file.findings.is_empty());
+        self.secrets_found = self.secrets.iter().map(|s| s.findings.len()).sum();
+        self.duplicates = group_duplicates(&self.secrets);
+    }
+
+    /// Number of secret findings at or above a severity level
+    pub fn findings_at_or_above(&self, level: &str) -> usize {
+        let threshold = detector::severity_rank(level);
+
+        self.secrets
+            .iter()
+            .flat_map(|file| &file.findings)
+            .filter(|f| detector::severity_rank(&f.severity) >= threshold)
+            .count()
+    }
+
+    /// Whether the scan should fail for a `--fail-on` level
+    pub fn fails_on(&self, fail_on: &str) -> bool {
+        fail_on != "none" && self.findings_at_or_above(fail_on) > 0
+    }
+}
+
+/// Renders a scan report into a specific output format
+pub trait Reporter {
+    /// Render the report to a string
+    fn render(&self, report: &ScanReport) -> Result<String, String>;
+}
+
+/// How the text report lists secrets
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GroupBy {
+    /// One flat list, file by file
+    #[default]
+    File,
+    /// Under the secret type and rule that matched
+    Rule,
+    /// Under the directory of the file
+    Dir,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "file" => Ok(Self::File),
+            "rule" => Ok(Self::Rule),
+            "dir" => Ok(Self::Dir),
+            _ => Err(format!("Invalid grouping: {} (expected file, rule or dir)", s)),
+        }
+    }
+}
+
+impl GroupBy {
+    /// Heading a finding is listed under, `None` for the flat list
+    fn key(&self, path: &str, finding: &SecretFinding) -> Option<String> {
+        match self {
+            Self::File => None,
+            Self::Rule => Some(format!("{} ({})", finding.secret_type, finding.rule_id)),
+            Self::Dir => match Path::new(path).parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => Some(format!("{}/", dir.to_string_lossy())),
+                _ => Some(".".to_string()),
+            },
+        }
+    }
+}
+
+/// Human readable report for the terminal
+pub struct TextReporter {
+    pub verbose: bool,
+    pub group_by: GroupBy,
+}
+
+impl Reporter for TextReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        let mut out = String::new();
+
+        out.push_str("\n📁 Enveil Scan Report\n\n");
+        out.push_str(&format!("Total risky files found: {}\n\n", report.risky_files));
+
+        if report.files.is_empty() {
+            out.push_str("✅ No risky files detected!\n");
+        } else {
+            // Sort by risk level (high first)
+            let mut files: Vec<&ScanResult> = report.files.iter().collect();
+            files.sort_by_key(|f| risk_order(&f.risk_level));
+
+            for file in files {
+                let icon = match file.risk_level.as_str() {
+                    "high" => "🔴",
+                    "medium" => "🟡",
+                    _ => "🟢",
+                };
+
+                out.push_str(&format!("{} [{}] {}{}\n", icon, file.risk_level.to_uppercase(), file.path, committed_tag(report, &file.path)));
+
+                if self.verbose {
+                    out.push_str(&format!("   Type: {}\n", file.file_type));
+                }
+            }
+        }
+
+        out.push_str(&format!("\n🔑 {} secrets found\n", report.secrets_found));
+
+        if !report.duplicates.is_empty() {
+            let repeated: usize = report.duplicates.iter().map(|g| g.locations.len()).sum();
+            out.push_str(&format!(
+                "🔁 {} of them are {} secret(s) found in several places\n",
+                repeated,
+                report.duplicates.len()
+            ));
+        }
+        if report.suppressed > 0 {
+            out.push_str(&format!("🙈 {} suppressed by enveil:ignore\n", report.suppressed));
+        }
+        if report.baselined > 0 {
+            out.push_str(&format!("📋 {} already in the baseline\n", report.baselined));
+        }
+        if !report.skipped.is_empty() {
+            out.push_str(&format!("⚠️  {} file(s) over the size limit not scanned\n", report.skipped.len()));
+            for path in &report.skipped {
+                out.push_str(&format!("   {}\n", path));
+            }
+        }
+
+        if !report.committed.is_empty() {
+            out.push_str(&format!(
+                "🚨 {} file(s) already committed to git: ignoring or protecting them is not enough, rotate their secrets\n",
+                report.committed.len()
+            ));
+        }
+
+        // Secrets found in several places are listed once, with their other locations
+        let groups: HashMap<(&str, &str), &SecretGroup> = report
+            .duplicates
+            .iter()
+            .map(|g| ((g.rule_id.as_str(), g.fingerprint.as_str()), g))
+            .collect();
+        let mut listed = HashSet::new();
+        // Findings under their heading, in heading order, with --group-by rule|dir
+        let mut sections: BTreeMap<String, (usize, String)> = BTreeMap::new();
+
+        for file in &report.secrets {
+            for finding in &file.findings {
+                let key = (finding.rule_id.as_str(), finding.fingerprint.as_str());
+                let group = groups.get(&key);
+                if group.is_some() && !listed.insert(key) {
+                    continue;
+                }
+
+                let (section, indent) = match self.group_by.key(&file.path, finding) {
+                    Some(heading) => {
+                        let section = sections.entry(heading).or_default();
+                        section.0 += 1;
+                        (&mut section.1, "      ")
+                    }
+                    None => (&mut out, "   "),
+                };
+                section.push_str(&format!("{}{}{}\n", indent, format_finding(&file.path, finding), committed_tag(report, &file.path)));
+                for other in group.iter().flat_map(|g| &g.locations[1..]) {
+                    section.push_str(&format!("{}   also at {}:{}\n", indent, other.path, other.line_number));
+                }
+            }
+        }
+
+        for (heading, (count, findings)) in sections {
+            out.push_str(&format!("\n   {}: {} finding(s)\n{}", heading, count, findings));
+        }
+
+        Ok(out)
+    }
+}
+
+/// Tag of files whose secrets are in the git history, not just on disk
+fn committed_tag(report: &ScanReport, path: &str) -> &'static str {
+    if report.is_committed(path) {
+        " [COMMITTED — rotation required]"
+    } else {
+        ""
+    }
+}
+
+/// One line describing a finding:
+/// `path:line (key.path) [TYPE] severity (confidence confidence, RULE_ID, tags) masked line`
+pub fn format_finding(path: &str, finding: &SecretFinding) -> String {
+    let location = match &finding.key_path {
+        Some(key_path) => format!("{}:{} ({})", path, finding.line_number, key_path),
+        None => format!("{}:{}", path, finding.line_number),
+    };
+
+    let tags: String = finding.tags.iter().map(|tag| format!(", {}", tag)).collect();
+
+    format!(
+        "{} [{}] {} ({} confidence, {}{}) {}",
+        location, finding.secret_type, finding.severity, finding.confidence, finding.rule_id, tags, finding.line_content
+    )
+}
+
+/// Machine readable JSON report
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        serde_json::to_string_pretty(report)
+            .map(|json| format!("{}\n", json))
+            .map_err(|e| format!("Failed to serialize report: {}", e))
+    }
+}
+
+/// Newline-delimited JSON: one object per finding, then a summary object,
+/// for consumers reading results as they stream in
+pub struct NdjsonReporter;
+
+impl Reporter for NdjsonReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        let mut out = String::new();
+        for file in &report.secrets {
+            for finding in &file.findings {
+                out.push_str(&ndjson_finding(&file.path, finding)?);
+                out.push('\n');
+            }
+        }
+        out.push_str(&ndjson_summary(report));
+        out.push('\n');
+        Ok(out)
+    }
+}
+
+/// NDJSON line of a finding: `{"type": "finding", "path": ..., <finding fields>}`
+pub fn ndjson_finding(path: &str, finding: &SecretFinding) -> Result<String, String> {
+    let mut line = serde_json::Map::new();
+    line.insert("type".to_string(), "finding".into());
+    line.insert("path".to_string(), path.into());
+    match serde_json::to_value(finding) {
+        Ok(serde_json::Value::Object(fields)) => line.extend(fields),
+        Ok(_) => {}
+        Err(e) => return Err(format!("Failed to serialize finding: {}", e)),
+    }
+    Ok(serde_json::Value::Object(line).to_string())
+}
+
+/// Last NDJSON line of a scan: its totals
+pub fn ndjson_summary(report: &ScanReport) -> String {
+    serde_json::json!({
+        "type": "summary",
+        "total_files": report.total_files,
+        "risky_files": report.risky_files,
+        "secrets_found": report.secrets_found,
+        "suppressed": report.suppressed,
+        "baselined": report.baselined,
+        "skipped": report.skipped,
+        "committed": report.committed,
+    })
+    .to_string()
+}
+
+/// JUnit XML report where each finding is a failed test case, for CI test views
+pub struct JunitReporter;
+
+impl Reporter for JunitReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        let count = report.secrets_found;
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+        // CI systems may ignore empty suites, so a clean scan is one passing case
+        let tests = count.max(1);
+        out.push_str(&format!("<testsuites name=\"enveil\" tests=\"{}\" failures=\"{}\">\n", tests, count));
+        out.push_str(&format!("  <testsuite name=\"enveil scan\" tests=\"{}\" failures=\"{}\">\n", tests, count));
+
+        if count == 0 {
+            out.push_str("    <testcase classname=\"enveil\" name=\"no secrets found\"/>\n");
+        }
+
+        for file in &report.secrets {
+            for finding in &file.findings {
+                out.push_str(&format!(
+                    "    <testcase classname=\"{}\" name=\"{}:{}\">\n",
+                    xml_escape(&finding.rule_id),
+                    xml_escape(&file.path),
+                    finding.line_number
+                ));
+                out.push_str(&format!(
+                    "      <failure type=\"{}\" message=\"{} secret ({} confidence)\">{}</failure>\n",
+                    xml_escape(&finding.secret_type),
+                    xml_escape(&finding.severity),
+                    xml_escape(&finding.confidence),
+                    xml_escape(&format_finding(&file.path, finding))
+                ));
+                out.push_str("    </testcase>\n");
+            }
+        }
+
+        out.push_str("  </testsuite>\n</testsuites>\n");
+        Ok(out)
+    }
+}
+
+/// Markdown report with tables, for pull request comments and wikis
+pub struct MarkdownReporter;
+
+impl Reporter for MarkdownReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        let mut out = String::from("# Enveil Scan Report\n\n");
+        out.push_str(&format!(
+            "**{}** risky file(s), **{}** secret(s) found\n",
+            report.risky_files, report.secrets_found
+        ));
+
+        if !report.files.is_empty() {
+            out.push_str("\n## Risky files\n\n| Risk | File | Type |\n| --- | --- | --- |\n");
+            let mut files: Vec<&ScanResult> = report.files.iter().collect();
+            files.sort_by_key(|f| risk_order(&f.risk_level));
+            for file in files {
+                out.push_str(&format!(
+                    "| {} | `{}`{} | {} |\n",
+                    file.risk_level,
+                    markdown_escape(&file.path),
+                    committed_tag(report, &file.path),
+                    markdown_escape(&file.file_type)
+                ));
+            }
+        }
+
+        if !report.secrets.is_empty() {
+            out.push_str("\n## Secrets\n\n| Location | Type | Severity | Confidence | Rule | Line |\n| --- | --- | --- | --- | --- | --- |\n");
+            for file in &report.secrets {
+                for finding in &file.findings {
+                    out.push_str(&format!(
+                        "| `{}:{}` | {} | {} | {} | {} | `{}` |\n",
+                        markdown_escape(&file.path),
+                        finding.line_number,
+                        markdown_escape(&finding.secret_type),
+                        finding.severity,
+                        finding.confidence,
+                        markdown_escape(&finding.rule_id),
+                        markdown_escape(&finding.line_content)
+                    ));
+                }
+            }
+        }
+
+        if report.suppressed > 0 || report.baselined > 0 {
+            out.push_str(&format!(
+                "\n{} suppressed by enveil:ignore, {} already in the baseline\n",
+                report.suppressed, report.baselined
+            ));
+        }
+        Ok(out)
+    }
+}
+
+/// Keep text inside a Markdown table cell: pipes would split it and
+/// backticks would end its code span
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('`', "'").replace(['\r', '\n'], " ")
+}
+
+/// Standalone HTML page, to archive or share a scan
+pub struct HtmlReporter;
+
+impl Reporter for HtmlReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        let mut out = String::from(concat!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>Enveil Scan Report</title>\n<style>\n",
+            "body { font-family: sans-serif; margin: 2em; }\n",
+            "table { border-collapse: collapse; margin-bottom: 2em; }\n",
+            "th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\n",
+            ".critical, .high { color: #b00020; } .medium { color: #b36b00; }\n",
+            "</style>\n</head>\n<body>\n<h1>Enveil Scan Report</h1>\n",
+        ));
+        out.push_str(&format!(
+            "<p>{} risky file(s), {} secret(s) found</p>\n",
+            report.risky_files, report.secrets_found
+        ));
+
+        if !report.files.is_empty() {
+            out.push_str("<h2>Risky files</h2>\n<table>\n<tr><th>Risk</th><th>File</th><th>Type</th></tr>\n");
+            let mut files: Vec<&ScanResult> = report.files.iter().collect();
+            files.sort_by_key(|f| risk_order(&f.risk_level));
+            for file in files {
+                out.push_str(&format!(
+                    "<tr><td class=\"{0}\">{0}</td><td><code>{1}</code>{2}</td><td>{3}</td></tr>\n",
+                    xml_escape(&file.risk_level),
+                    xml_escape(&file.path),
+                    xml_escape(committed_tag(report, &file.path)),
+                    xml_escape(&file.file_type)
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+
+        if !report.secrets.is_empty() {
+            out.push_str(concat!(
+                "<h2>Secrets</h2>\n<table>\n<tr><th>Location</th><th>Type</th><th>Severity</th>",
+                "<th>Confidence</th><th>Rule</th><th>Line</th></tr>\n",
+            ));
+            for file in &report.secrets {
+                for finding in &file.findings {
+                    out.push_str(&format!(
+                        "<tr><td><code>{0}:{1}</code></td><td>{2}</td><td class=\"{3}\">{3}</td><td>{4}</td><td>{5}</td><td><code>{6}</code></td></tr>\n",
+                        xml_escape(&file.path),
+                        finding.line_number,
+                        xml_escape(&finding.secret_type),
+                        xml_escape(&finding.severity),
+                        xml_escape(&finding.confidence),
+                        xml_escape(&finding.rule_id),
+                        xml_escape(&finding.line_content),
+                    ));
+                }
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        Ok(out)
+    }
+}
+
+/// SARIF 2.1.0 log, for GitHub code scanning and other static analysis viewers
+pub struct SarifReporter;
+
+impl Reporter for SarifReporter {
+    fn render(&self, report: &ScanReport) -> Result<String, String> {
+        // One rule entry per rule that matched, in order of first finding
+        let mut rules: Vec<serde_json::Value> = Vec::new();
+        let mut rule_ids = HashSet::new();
+        let mut results = Vec::new();
+
+        for file in &report.secrets {
+            for finding in &file.findings {
+                if rule_ids.insert(finding.rule_id.as_str()) {
+                    rules.push(serde_json::json!({
+                        "id": finding.rule_id,
+                        "name": finding.secret_type,
+                        "shortDescription": { "text": finding.description.as_deref().unwrap_or(&finding.secret_type) },
+                        "defaultConfiguration": { "level": sarif_level(&finding.severity) },
+                    }));
+                }
+
+                results.push(serde_json::json!({
+                    "ruleId": finding.rule_id,
+                    "level": sarif_level(&finding.severity),
+                    "message": {
+                        "text": format!("{} secret ({} severity, {} confidence)", finding.secret_type, finding.severity, finding.confidence),
+                    },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": file.path.replace('\\', "/") },
+                            "region": {
+                                "startLine": finding.line_number,
+                                "startColumn": finding.secret_span.start,
+                                "endColumn": finding.secret_span.end,
+                                "snippet": { "text": finding.line_content },
+                            },
+                        },
+                    }],
+                    "partialFingerprints": { "enveil/v1": finding.fingerprint },
+                    "properties": { "severity": finding.severity, "confidence": finding.confidence },
+                }));
+            }
+        }
+
+        let log = serde_json::json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "enveil",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    },
+                },
+                "results": results,
+            }],
+        });
+
+        serde_json::to_string_pretty(&log)
+            .map(|json| format!("{}\n", json))
+            .map_err(|e| format!("Failed to serialize report: {}", e))
+    }
+}
+
+/// SARIF level of a finding severity
+fn sarif_level(severity: &str) -> &'static str {
+    match severity {
+        "critical" | "high" => "error",
+        "medium" => "warning",
+        _ => "note",
+    }
+}
+
+/// Escape text for XML attributes and content
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if c.is_control() && c != '\t' && c != '\n' => escaped.push('\u{FFFD}'),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Get the reporter for a format name
+pub fn reporter_for(format: &str, verbose: bool, group_by: GroupBy) -> Result<Box<dyn Reporter>, String> {
+    match format {
+        "text" => Ok(Box::new(TextReporter { verbose, group_by })),
+        "json" => Ok(Box::new(JsonReporter)),
+        "ndjson" => Ok(Box::new(NdjsonReporter)),
+        "junit" => Ok(Box::new(JunitReporter)),
+        "markdown" => Ok(Box::new(MarkdownReporter)),
+        "html" => Ok(Box::new(HtmlReporter)),
+        "sarif" => Ok(Box::new(SarifReporter)),
+        _ => Err(format!("Unknown output format: {}", format)),
+    }
+}
+
+/// Where a rendered report is written
+pub enum OutputTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+/// A reporter paired with its destination
+pub struct Output {
+    pub reporter: Box<dyn Reporter>,
+    pub target: OutputTarget,
+}
+
+impl Output {
+    pub fn new(reporter: Box<dyn Reporter>, target: OutputTarget) -> Self {
+        Self { reporter, target }
+    }
+
+    /// Render the report and write it to the target
+    pub fn emit(&self, report: &ScanReport) -> Result<(), String> {
+        let rendered = self.reporter.render(report)?;
+
+        match &self.target {
+            OutputTarget::Stdout => {
+                // Decorated like any other message: machine readable formats
+                // have no line starting with an emoji and pass unchanged
+                print!("{}", style::decorate(&rendered, Stream::Stdout));
+                Ok(())
+            }
+            OutputTarget::File(path) => fs::write(path, rendered)
+                .map_err(|e| format!("Failed to write report to {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Emit a report to every configured output
+pub fn emit_all(outputs: &[Output], report: &ScanReport) -> Result<(), String> {
+    for output in outputs {
+        output.emit(report)?;
+    }
+    Ok(())
+}
+
+/// Group the findings of the same rule and secret found in more than one place
+fn group_duplicates(secrets: &[SecretFileReport]) -> Vec<SecretGroup> {
+    let mut groups: Vec<SecretGroup> = Vec::new();
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for file in secrets {
+        for finding in &file.findings {
+            let location = Location { path: file.path.clone(), line_number: finding.line_number };
+            let key = (finding.rule_id.as_str(), finding.fingerprint.as_str());
+
+            match index.get(&key) {
+                Some(&i) => groups[i].locations.push(location),
+                None => {
+                    index.insert(key, groups.len());
+                    groups.push(SecretGroup {
+                        fingerprint: finding.fingerprint.clone(),
+                        rule_id: finding.rule_id.clone(),
+                        secret_type: finding.secret_type.clone(),
+                        severity: finding.severity.clone(),
+                        locations: vec![location],
+                    });
+                }
+            }
+        }
+    }
+
+    groups.retain(|g| g.locations.len() > 1);
+    groups
+}
+
+/// Order of paths component by component, the order the scanner walks
+/// directories in (`a/b` before `a.txt`)
+fn path_order(a: &str, b: &str) -> Ordering {
+    std::path::Path::new(a).cmp(std::path::Path::new(b))
+}
+
+fn risk_order(risk_level: &str) -> u8 {
+    match risk_level {
+        "high" => 0,
+        "medium" => 1,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::Span;
+    use tempfile::TempDir;
+
+    fn sample_report() -> ScanReport {
+        ScanReport {
+            total_files: 2,
+            risky_files: 2,
+            files: vec![
+                ScanResult {
+                    path: "config.json".to_string(),
+                    file_type: ".json".to_string(),
+                    risk_level: "medium".to_string(),
+                },
+                ScanResult {
+                    path: ".env".to_string(),
+                    file_type: ".env".to_string(),
+                    risk_level: "high".to_string(),
+                },
+            ],
+            secrets_found: 0,
+            secrets: Vec::new(),
+            duplicates: Vec::new(),
+            suppressed: 0,
+            baselined: 0,
+            skipped: Vec::new(),
+            committed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_text_reporter_sorts_high_first() {
+        let text = TextReporter { verbose: false, group_by: GroupBy::File }.render(&sample_report()).unwrap();
+        let high = text.find("[HIGH] .env").unwrap();
+        let medium = text.find("[MEDIUM] config.json").unwrap();
+        assert!(high < medium);
+        assert!(text.contains("0 secrets found"));
+    }
+
+    #[test]
+    fn test_json_reporter() {
+        let json = JsonReporter.render(&sample_report()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["risky_files"], 2);
+    }
+
+    #[test]
+    fn test_load_report_of_an_older_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut value = serde_json::to_value(sample_report()).unwrap();
+        for field in ["duplicates", "suppressed", "baselined", "skipped", "committed"] {
+            value.as_object_mut().unwrap().remove(field);
+        }
+        let path = temp_dir.path().join("old.json");
+        fs::write(&path, value.to_string()).unwrap();
+
+        let report = ScanReport::load(&path).unwrap();
+        assert_eq!(report.secrets_found, sample_report().secrets_found);
+        assert!(report.duplicates.is_empty());
+        assert_eq!((report.suppressed, report.baselined), (0, 0));
+    }
+
+    fn finding(severity: &str) -> SecretFinding {
+        SecretFinding {
+            rule_id: "TEST".to_string(),
+            secret_type: "TEST".to_string(),
+            line_number: 1,
+            line_content: "****".to_string(),
+            matched_pattern: String::new(),
+            severity: severity.to_string(),
+            confidence: "high".to_string(),
+            description: None,
+            key_path: None,
+            match_span: Span { start: 1, end: 5 },
+            secret_span: Span { start: 1, end: 5 },
+            fingerprint: detector::fingerprint(severity),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_fails_on_threshold() {
+        let mut report = sample_report();
+        report.secrets.push(SecretFileReport {
+            path: "app.py".to_string(),
+            findings: vec![finding("medium"), finding("high")],
+        });
+
+        assert_eq!(report.findings_at_or_above("low"), 2);
+        assert_eq!(report.findings_at_or_above("high"), 1);
+        assert!(report.fails_on("high"));
+        assert!(!report.fails_on("none"));
+        assert!(!report.fails_on("critical"));
+
+        report.secrets[0].findings.pop();
+        assert!(!report.fails_on("high"));
+        assert!(report.fails_on("medium"));
+    }
+
+    #[test]
+    fn test_mark_committed() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").args(args).current_dir(root).status().unwrap();
+        };
+        git(&["init", "-q"]);
+        fs::write(root.join(".env"), "API_KEY=abc\n").unwrap();
+        git(&["add", ".env"]);
+        git(&["-c", "user.name=dev", "-c", "user.email=dev@example.com", "commit", "-q", "-m", "env"]);
+        git(&["rm", "-q", "--cached", ".env"]);
+
+        let mut report = sample_report();
+        for file in &mut report.files {
+            file.path = root.join(&file.path).to_string_lossy().to_string();
+        }
+        report.mark_committed(root);
+
+        let env = root.join(".env").to_string_lossy().to_string();
+        assert_eq!(report.committed, std::slice::from_ref(&env));
+        let text = TextReporter { verbose: false, group_by: GroupBy::File }.render(&report).unwrap();
+        assert!(text.contains(&format!("{} [COMMITTED — rotation required]", env)));
+        assert!(text.contains("1 file(s) already committed to git"));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut report = sample_report();
+        let mut password = finding("high");
+        password.rule_id = "PWD001".to_string();
+        password.secret_type = "PASSWORD".to_string();
+        password.fingerprint = detector::fingerprint("password");
+        report.secrets = vec![
+            SecretFileReport { path: "services/payments/app.py".to_string(), findings: vec![finding("high"), password.clone()] },
+            SecretFileReport { path: "settings.py".to_string(), findings: vec![password] },
+        ];
+
+        let flat = TextReporter { verbose: false, group_by: GroupBy::File }.render(&report).unwrap();
+        assert!(flat.contains("\n   services/payments/app.py:1 [TEST]"));
+        assert!(!flat.contains("finding(s)"));
+
+        let by_rule = TextReporter { verbose: false, group_by: GroupBy::Rule }.render(&report).unwrap();
+        let password = by_rule.find("   PASSWORD (PWD001): 2 finding(s)\n").unwrap();
+        let test = by_rule.find("   TEST (TEST): 1 finding(s)\n").unwrap();
+        assert!(password < test);
+        assert!(by_rule.contains("      settings.py:1 [PASSWORD]"));
+
+        let by_dir = TextReporter { verbose: false, group_by: GroupBy::Dir }.render(&report).unwrap();
+        assert!(by_dir.contains("   .: 1 finding(s)\n      settings.py:1 [PASSWORD]"));
+        assert!(by_dir.contains("   services/payments/: 2 finding(s)\n"));
+
+        assert_eq!("Rule".parse::<GroupBy>().unwrap(), GroupBy::Rule);
+        assert!("severity".parse::<GroupBy>().is_err());
+    }
+
+    #[test]
+    fn test_sorted_and_relative() {
+        let mut second = finding("high");
+        second.line_number = 7;
+        let mut report = ScanReport::new(
+            Vec::new(),
+            vec![
+                SecretFileReport { path: "/tmp/scan/b.py".to_string(), findings: vec![second, finding("low")] },
+                SecretFileReport { path: "/tmp/scan/a.txt".to_string(), findings: vec![finding("low")] },
+                SecretFileReport { path: "/tmp/scan/a/z.py".to_string(), findings: vec![finding("low")] },
+            ],
+            0,
+        );
+        report.committed = vec!["/tmp/scan/b.py".to_string()];
+        report.relative_to(Path::new("/tmp/scan"));
+
+        let paths: Vec<&str> = report.secrets.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, vec!["a/z.py", "a.txt", "b.py"]);
+        let lines: Vec<usize> = report.secrets[2].findings.iter().map(|f| f.line_number).collect();
+        assert_eq!(lines, vec![1, 7]);
+        assert_eq!(report.committed, vec!["b.py"]);
+        assert_eq!(report.duplicates[0].locations[0].path, "a/z.py");
+
+        // A scanned file keeps its path
+        report.relative_to(Path::new("b.py"));
+        assert_eq!(report.secrets[2].path, "b.py");
+    }
+
+    #[test]
+    fn test_junit_reporter() {
+        let xml = JunitReporter.render(&sample_report()).unwrap();
+        assert!(xml.contains("tests=\"1\" failures=\"0\""));
+        assert!(xml.contains("name=\"no secrets found\""));
+
+        let mut report = sample_report();
+        let mut leaked = finding("high");
+        leaked.line_content = "token = \"<ghp_****…wxyz>\" & more".to_string();
+        report.secrets.push(SecretFileReport { path: "app.py".to_string(), findings: vec![leaked] });
+        report.secrets_found = 1;
+
+        let xml = JunitReporter.render(&report).unwrap();
+        assert!(xml.contains("tests=\"1\" failures=\"1\""));
+        assert!(xml.contains("<testcase classname=\"TEST\" name=\"app.py:1\">"));
+        assert!(xml.contains("message=\"high secret (high confidence)\""));
+        assert!(xml.contains("token = &quot;&lt;ghp_****…wxyz&gt;&quot; &amp; more</failure>"));
+    }
+
+    #[test]
+    fn test_ndjson_reporter() {
+        let mut report = sample_report();
+        report.secrets.push(SecretFileReport { path: "app.py".to_string(), findings: vec![finding("high"), finding("low")] });
+        report.secrets_found = 2;
+
+        let ndjson = NdjsonReporter.render(&report).unwrap();
+        let lines: Vec<serde_json::Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "finding");
+        assert_eq!(lines[0]["path"], "app.py");
+        assert_eq!(lines[0]["rule_id"], "TEST");
+        assert_eq!(lines[1]["severity"], "low");
+        assert_eq!(lines[2]["type"], "summary");
+        assert_eq!(lines[2]["secrets_found"], 2);
+    }
+
+    #[test]
+    fn test_markdown_and_html_reporters() {
+        let mut report = sample_report();
+        let mut leaked = finding("high");
+        leaked.line_content = "token = \"<ghp_****…wxyz>\" | more".to_string();
+        report.secrets.push(SecretFileReport { path: "app.py".to_string(), findings: vec![leaked] });
+        report.secrets_found = 1;
+
+        let markdown = MarkdownReporter.render(&report).unwrap();
+        assert!(markdown.contains("**2** risky file(s), **1** secret(s) found"));
+        assert!(markdown.find("| high | `.env` |").unwrap() < markdown.find("| medium | `config.json` |").unwrap());
+        assert!(markdown.contains("| `app.py:1` | TEST | high | high | TEST | `token = \"<ghp_****…wxyz>\" \\| more` |"));
+
+        let html = HtmlReporter.render(&report).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td class=\"high\">high</td><td><code>.env</code></td>"));
+        assert!(html.contains("<code>token = &quot;&lt;ghp_****…wxyz&gt;&quot; | more</code>"));
+    }
+
+    #[test]
+    fn test_sarif_reporter() {
+        let mut report = sample_report();
+        let mut medium = finding("medium");
+        medium.line_number = 3;
+        report.secrets.push(SecretFileReport { path: "app.py".to_string(), findings: vec![finding("critical"), medium] });
+        report.secrets_found = 2;
+
+        let sarif: serde_json::Value = serde_json::from_str(&SarifReporter.render(&report).unwrap()).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "enveil");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 1);
+        assert_eq!(run["results"][0]["ruleId"], "TEST");
+        assert_eq!(run["results"][0]["level"], "error");
+        assert_eq!(run["results"][1]["level"], "warning");
+        let location = &run["results"][1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "app.py");
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["startColumn"], 1);
+        assert_eq!(run["results"][0]["partialFingerprints"]["enveil/v1"], detector::fingerprint("critical"));
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(reporter_for("xml", false, GroupBy::File).is_err());
+    }
+}
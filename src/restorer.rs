@@ -0,0 +1,749 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::protector::{self, ProtectAction};
+use crate::recipient::Identity;
+use crate::stream::{self, FileHeader};
+use crate::stub::{Stub, STUB_EXTENSION};
+
+/// Length of the nonce prepended to encrypted files
+const NONCE_LEN: usize = 12;
+
+/// Result of restoring a protected file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    pub encrypted_path: String,
+    pub restored_path: String,
+    pub success: bool,
+    pub message: String,
+}
+
+/// What to do when a restored file's original location is occupied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "rename" => Ok(ConflictPolicy::Rename),
+            _ => Err(format!("Unknown conflict policy: {} (expected skip, overwrite or rename)", s)),
+        }
+    }
+}
+
+/// Key used to decrypt protected files
+#[derive(Debug, Clone)]
+pub enum DecryptionKey {
+    /// Raw AES-256 key as printed by protect
+    Raw([u8; 32]),
+    /// Passphrase the key is derived from, using the salt in each file header
+    Passphrase(String),
+    /// Private key of a recipient the files were encrypted to
+    Identity(Identity),
+}
+
+impl DecryptionKey {
+    /// Decrypt the content of an encrypted file
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut plaintext = Vec::new();
+        self.decrypt_stream(data, &mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    /// Decrypt an encrypted file from `reader` into `writer`, one frame at
+    /// a time. Files written before the authenticated header are still
+    /// read, the oldest ones whole.
+    pub fn decrypt_stream<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> Result<(), String> {
+        let read_error = |e: std::io::Error| format!("Failed to read file: {}", e);
+        let mut magic = [0u8; 8];
+        let len = stream::read_full(&mut reader, &mut magic).map_err(read_error)?;
+
+        if magic[..len].starts_with(stream::MAGIC) {
+            let (header, aad) = FileHeader::read(&mut magic[..len].chain(&mut reader))?;
+            if !header.recipients.is_empty() {
+                let key = match self {
+                    DecryptionKey::Identity(identity) => identity.unwrap_key(&header.recipients)?,
+                    _ => return Err("File is encrypted to recipients (use --identity)".to_string()),
+                };
+                return stream::decrypt(reader, writer, &key, &header, &aad);
+            }
+            let key = self.resolve(header.kdf.is_some(), |passphrase| match &header.kdf {
+                Some(kdf) => kdf.derive(passphrase),
+                None => Err("Missing key derivation parameters".to_string()),
+            })?;
+            return stream::decrypt(reader, writer, &key, &header, &aad);
+        }
+
+        let passphrase_header = &magic[..len] == protector::PASSPHRASE_MAGIC.as_slice();
+        let mut salt = [0u8; protector::SALT_LEN];
+        if passphrase_header && stream::read_full(&mut reader, &mut salt).map_err(read_error)? < salt.len() {
+            return Err("Encrypted file is truncated".to_string());
+        }
+        let key = self.resolve(passphrase_header, |passphrase| protector::derive_key(passphrase, &salt))?;
+
+        // The bytes after the passphrase header, or the first bytes read
+        let head = if passphrase_header {
+            let len = stream::read_full(&mut reader, &mut magic).map_err(read_error)?;
+            magic[..len].to_vec()
+        } else {
+            magic[..len].to_vec()
+        };
+
+        if head.as_slice() == stream::LEGACY_STREAM_MAGIC.as_slice() {
+            return stream::decrypt_legacy(reader, writer, &key);
+        }
+
+        let mut data = head;
+        reader.read_to_end(&mut data).map_err(read_error)?;
+        let plaintext = decrypt_bytes(&data, &key)?;
+        writer
+            .write_all(&plaintext)
+            .map_err(|e| format!("Failed to write decrypted file: {}", e))
+    }
+
+    /// The key of a file, derived with `derive` when it is passphrase-protected
+    fn resolve(&self, passphrase_protected: bool, derive: impl FnOnce(&str) -> Result<[u8; 32], String>) -> Result<[u8; 32], String> {
+        match (self, passphrase_protected) {
+            (DecryptionKey::Raw(key), false) => Ok(*key),
+            (DecryptionKey::Passphrase(passphrase), true) => derive(passphrase),
+            (DecryptionKey::Raw(_), true) => Err("File is passphrase-protected (use --passphrase)".to_string()),
+            (DecryptionKey::Passphrase(_), false) => Err("File was encrypted with a raw key (use --key)".to_string()),
+            (DecryptionKey::Identity(_), _) => Err("File was not encrypted to a recipient (use --key or --passphrase)".to_string()),
+        }
+    }
+}
+
+/// Decrypts files written by `FileProtector` back to plaintext
+pub struct FileRestorer {
+    secure_dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl FileRestorer {
+    /// Create a new file restorer
+    pub fn new(secure_dir: PathBuf) -> Self {
+        // A missing or unreadable manifest only disables original-path lookup
+        let manifest = Manifest::load(&secure_dir).unwrap_or_default();
+        Self { secure_dir, manifest }
+    }
+
+    /// Original location of a protected file, as recorded in the manifest
+    pub fn original_path(&self, enc_path: &Path) -> Option<PathBuf> {
+        self.manifest
+            .find_by_protected(&self.secure_dir, enc_path)
+            .map(|entry| PathBuf::from(&entry.original_path))
+    }
+
+    /// List the encrypted files in the secure directory and below
+    pub fn find_encrypted_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        collect_encrypted(&self.secure_dir, &mut files);
+        files.sort();
+        files
+    }
+
+    /// Decrypt every `.enc` file of the secure directory into `dest_dir`.
+    ///
+    /// With `to_original`, files recorded in the manifest go back to their
+    /// original path and `dest_dir` is only used for unrecorded files.
+    pub fn restore_directory(&self, key: &DecryptionKey, dest_dir: &Path, to_original: bool, force: bool) -> Vec<RestoreResult> {
+        if !self.secure_dir.is_dir() {
+            return vec![RestoreResult {
+                encrypted_path: self.secure_dir.to_string_lossy().to_string(),
+                restored_path: String::new(),
+                success: false,
+                message: "Secure directory does not exist".to_string(),
+            }];
+        }
+
+        self.find_encrypted_files()
+            .iter()
+            .map(|path| self.decrypt_file(path, key, dest_dir, to_original, force))
+            .collect()
+    }
+
+    /// Decrypt a single `.enc` file into `dest_dir` (or its original path)
+    pub fn decrypt_file(&self, enc_path: &Path, key: &DecryptionKey, dest_dir: &Path, to_original: bool, force: bool) -> RestoreResult {
+        let encrypted_path = enc_path.to_string_lossy().to_string();
+
+        let file_name = match enc_path.file_name().and_then(|n| n.to_str()) {
+            Some(name) if name.ends_with(".enc") && name.len() > 4 => &name[..name.len() - 4],
+            _ => {
+                return RestoreResult {
+                    encrypted_path,
+                    restored_path: String::new(),
+                    success: false,
+                    message: "Not an encrypted file (expected .enc extension)".to_string(),
+                };
+            }
+        };
+
+        // Files keep their place relative to the secure directory
+        let relative = enc_path
+            .strip_prefix(&self.secure_dir)
+            .map(|relative| relative.with_file_name(file_name))
+            .unwrap_or_else(|_| PathBuf::from(file_name));
+        let dest_path = match self.original_path(enc_path) {
+            Some(original) if to_original => original,
+            _ => dest_dir.join(relative),
+        };
+        let restored_path = dest_path.to_string_lossy().to_string();
+
+        if dest_path.exists() && !force {
+            return RestoreResult {
+                encrypted_path,
+                restored_path,
+                success: false,
+                message: "Destination already exists. Use --force to overwrite.".to_string(),
+            };
+        }
+
+        let parent = dest_path.parent().unwrap_or(dest_dir);
+        if let Err(e) = fs::create_dir_all(parent) {
+            return RestoreResult {
+                encrypted_path,
+                restored_path: String::new(),
+                success: false,
+                message: format!("Failed to create destination directory: {}", e),
+            };
+        }
+
+        // Decrypt into a temporary file so a failure never leaves partial
+        // plaintext behind or clobbers the destination
+        let decrypted = fs::File::open(enc_path)
+            .map_err(|e| format!("Failed to read file: {}", e))
+            .and_then(|input| {
+                let mut output = tempfile::NamedTempFile::new_in(parent)
+                    .map_err(|e| format!("Failed to write decrypted file: {}", e))?;
+                key.decrypt_stream(io::BufReader::new(input), io::BufWriter::new(output.as_file_mut()))?;
+                output
+                    .persist(&dest_path)
+                    .map_err(|e| format!("Failed to write decrypted file: {}", e.error))
+            });
+
+        match decrypted {
+            Ok(_) => RestoreResult {
+                encrypted_path,
+                restored_path,
+                success: true,
+                message: with_warning("File decrypted", self.restore_metadata(enc_path, &dest_path)),
+            },
+            Err(e) => RestoreResult {
+                encrypted_path,
+                restored_path: String::new(),
+                success: false,
+                message: e,
+            },
+        }
+    }
+
+    /// Move files protected with `--action move` back to their original
+    /// location, as recorded in the manifest.
+    pub fn unprotect_moved(&self, policy: ConflictPolicy, dry_run: bool) -> Vec<RestoreResult> {
+        let mut manifest = self.manifest.clone();
+        let mut results = Vec::new();
+        let mut restored = Vec::new();
+
+        for (index, entry) in manifest.entries.iter().enumerate() {
+            if entry.action != ProtectAction::Moved {
+                continue;
+            }
+
+            let protected = match entry.location(&self.secure_dir) {
+                Some(path) => path,
+                None => continue,
+            };
+
+            if !protected.exists() {
+                continue;
+            }
+
+            let dest = match resolve_conflict(Path::new(&entry.original_path), policy) {
+                Some(dest) => dest,
+                None => {
+                    results.push(RestoreResult {
+                        encrypted_path: protected.to_string_lossy().to_string(),
+                        restored_path: entry.original_path.clone(),
+                        success: false,
+                        message: "Original location is occupied (skipped)".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let result = if dry_run {
+                RestoreResult {
+                    encrypted_path: protected.to_string_lossy().to_string(),
+                    restored_path: dest.to_string_lossy().to_string(),
+                    success: true,
+                    message: "Would restore".to_string(),
+                }
+            } else {
+                let result = self.move_back(entry, &protected, &dest);
+                if result.success {
+                    restored.push(index);
+                }
+                result
+            };
+
+            results.push(result);
+        }
+
+        if !restored.is_empty() {
+            let mut index = 0;
+            manifest.entries.retain(|_| {
+                let keep = !restored.contains(&index);
+                index += 1;
+                keep
+            });
+
+            if let Err(e) = manifest.save(&self.secure_dir) {
+                results.push(RestoreResult {
+                    encrypted_path: Manifest::path(&self.secure_dir).to_string_lossy().to_string(),
+                    restored_path: String::new(),
+                    success: false,
+                    message: e,
+                });
+            }
+        }
+
+        results
+    }
+
+    /// Move back the file a stub left by `protect` points to, and delete
+    /// the stub
+    pub fn restore_stub(stub_path: &Path, policy: ConflictPolicy) -> RestoreResult {
+        let failure = |message: String| RestoreResult {
+            encrypted_path: String::new(),
+            restored_path: stub_path.to_string_lossy().to_string(),
+            success: false,
+            message,
+        };
+
+        let original = match Stub::original_for(stub_path) {
+            Some(original) => original,
+            None => return failure(format!("Not a stub, expected a .{} file", STUB_EXTENSION)),
+        };
+        let stub = match Stub::load(stub_path) {
+            Ok(stub) => stub,
+            Err(e) => return failure(e),
+        };
+
+        let restorer = Self::new(PathBuf::from(&stub.secure_dir));
+        let protected = PathBuf::from(&stub.protected_path);
+        let mut manifest = restorer.manifest.clone();
+        let index = match manifest
+            .entries
+            .iter()
+            .rposition(|e| e.action == ProtectAction::Moved && e.location(&restorer.secure_dir).as_deref() == Some(protected.as_path()))
+        {
+            Some(index) => index,
+            None => return failure(format!("{} is not in the manifest of {}", stub.protected_path, stub.secure_dir)),
+        };
+        if !protected.exists() {
+            return failure(format!("{} no longer exists", stub.protected_path));
+        }
+
+        let dest = match resolve_conflict(&original, policy) {
+            Some(dest) => dest,
+            None => return failure("Original location is occupied (skipped)".to_string()),
+        };
+
+        let mut result = restorer.move_back(&manifest.entries[index], &protected, &dest);
+        if result.success {
+            manifest.entries.remove(index);
+            let saved = manifest.save(&restorer.secure_dir);
+            // Usually already gone with the stub of the recorded original path
+            let removed = if stub_path.exists() {
+                fs::remove_file(stub_path).map_err(|e| format!("stub not removed: {}", e))
+            } else {
+                Ok(())
+            };
+            result.message = with_warning(&with_warning(&result.message, saved), removed);
+        }
+        result
+    }
+
+    /// Move a protected file back to `dest` with its metadata, dropping the
+    /// stub left at its original location
+    fn move_back(&self, entry: &ManifestEntry, protected: &Path, dest: &Path) -> RestoreResult {
+        let moved = dest
+            .parent()
+            .map(fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .and_then(|_| fs::copy(protected, dest))
+            .and_then(|_| fs::remove_file(protected));
+
+        match moved {
+            Ok(_) => {
+                remove_empty_parents(&self.secure_dir, protected);
+                Stub::remove(Path::new(&entry.original_path));
+                let applied = entry.metadata.as_ref().map(|m| m.apply(dest)).unwrap_or(Ok(()));
+                RestoreResult {
+                    encrypted_path: protected.to_string_lossy().to_string(),
+                    restored_path: dest.to_string_lossy().to_string(),
+                    success: true,
+                    message: with_warning("File restored", applied),
+                }
+            }
+            Err(e) => RestoreResult {
+                encrypted_path: protected.to_string_lossy().to_string(),
+                restored_path: dest.to_string_lossy().to_string(),
+                success: false,
+                message: format!("Failed to restore file: {}", e),
+            },
+        }
+    }
+
+    /// Give a decrypted file the permissions, owner and modification time
+    /// its original had, when the manifest recorded them
+    fn restore_metadata(&self, enc_path: &Path, dest_path: &Path) -> Result<(), String> {
+        match self.manifest.find_by_protected(&self.secure_dir, enc_path).and_then(|e| e.metadata.as_ref()) {
+            Some(metadata) => metadata.apply(dest_path),
+            None => Ok(()),
+        }
+    }
+
+}
+
+/// Remove the directories of the secure directory emptied by a restore
+pub(crate) fn remove_empty_parents(secure_dir: &Path, protected: &Path) {
+    let mut dir = protected.parent();
+    while let Some(path) = dir {
+        // Fails on the first directory that still has files
+        if !path.starts_with(secure_dir) || path == secure_dir || fs::remove_dir(path).is_err() {
+            break;
+        }
+        dir = path.parent();
+    }
+}
+
+fn with_warning(message: &str, outcome: Result<(), String>) -> String {
+    match outcome {
+        Ok(()) => message.to_string(),
+        Err(e) => format!("{} (warning: {})", message, e),
+    }
+}
+
+/// Collect the `.enc` files of a directory tree
+fn collect_encrypted(dir: &Path, files: &mut Vec<PathBuf>) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.is_dir() {
+                collect_encrypted(&path, files);
+            } else if path.is_file() && path.extension().map(|e| e == "enc").unwrap_or(false) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+/// Where to restore a file whose original path is `dest`, or `None` when
+/// it is occupied and the policy skips it
+fn resolve_conflict(dest: &Path, policy: ConflictPolicy) -> Option<PathBuf> {
+    if !dest.exists() {
+        return Some(dest.to_path_buf());
+    }
+    match policy {
+        ConflictPolicy::Skip => None,
+        ConflictPolicy::Overwrite => Some(dest.to_path_buf()),
+        ConflictPolicy::Rename => Some(restored_name(dest)),
+    }
+}
+
+/// Alternative destination used by `ConflictPolicy::Rename`
+fn restored_name(dest: &Path) -> PathBuf {
+    let file_name = dest.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut counter = 0;
+    loop {
+        let suffix = if counter == 0 { String::new() } else { format!(".{}", counter) };
+        let candidate = dest.with_file_name(format!("{}.restored{}", file_name, suffix));
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Decrypt `nonce || ciphertext` data produced by the protector
+pub fn decrypt_bytes(data: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_LEN {
+        return Err("Encrypted file is truncated".to_string());
+    }
+
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+
+    let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        .map_err(|_| "Decryption failed (wrong key or corrupted file)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::{generate_key, FileProtector, ProtectOption};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encrypt_then_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let key = generate_key();
+        let result = FileProtector::new(secure_dir.clone()).protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+        assert!(result.success);
+        assert!(!env_file.exists());
+
+        let results = FileRestorer::new(secure_dir).restore_directory(&DecryptionKey::Raw(key), temp_dir.path(), false, false);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].success, "{}", results[0].message);
+        assert_eq!(fs::read_to_string(&env_file).unwrap(), "API_KEY=abc\n");
+    }
+
+    #[test]
+    fn test_large_file_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let dump = temp_dir.path().join("dump.sql");
+        let content: Vec<u8> = (0..stream::CHUNK_SIZE * 2 + 500).map(|i| (i % 253) as u8).collect();
+        fs::write(&dump, &content).unwrap();
+
+        let key = generate_key();
+        let result = FileProtector::new(secure_dir.clone()).protect_file(&dump, &ProtectOption::Encrypt, Some(&key));
+        assert!(result.success, "{}", result.message);
+        assert!(fs::read(&result.protected_path).unwrap().starts_with(stream::MAGIC));
+
+        let result = FileRestorer::new(secure_dir.clone()).decrypt_file(
+            Path::new(&result.protected_path),
+            &DecryptionKey::Raw(key),
+            &secure_dir,
+            true,
+            false,
+        );
+        assert!(result.success, "{}", result.message);
+        assert_eq!(fs::read(&dump).unwrap(), content);
+    }
+
+    #[test]
+    fn test_single_message_files_still_decrypt() {
+        // Format written before chunked encryption: nonce || ciphertext
+        let key = generate_key();
+        let nonce = [9u8; NONCE_LEN];
+        let ciphertext = Aes256Gcm::new_from_slice(&key)
+            .unwrap()
+            .encrypt(Nonce::from_slice(&nonce), b"API_KEY=abc\n".as_slice())
+            .unwrap();
+        let data = [nonce.as_slice(), &ciphertext].concat();
+
+        assert_eq!(DecryptionKey::Raw(key).decrypt(&data).unwrap(), b"API_KEY=abc\n");
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let key = generate_key();
+        FileProtector::new(secure_dir.clone()).protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+
+        let results = FileRestorer::new(secure_dir).restore_directory(&DecryptionKey::Raw(generate_key()), temp_dir.path(), false, false);
+        assert!(!results[0].success);
+        assert!(!env_file.exists());
+    }
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let salt = protector::generate_salt();
+        let key = protector::derive_key("hunter2", &salt).unwrap();
+        FileProtector::new(secure_dir.clone())
+            .with_salt(salt)
+            .protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+
+        let restorer = FileRestorer::new(secure_dir);
+        let results = restorer.restore_directory(&DecryptionKey::Raw(key), temp_dir.path(), false, false);
+        assert!(results[0].message.contains("--passphrase"));
+
+        let wrong = DecryptionKey::Passphrase("hunter3".to_string());
+        assert!(!restorer.restore_directory(&wrong, temp_dir.path(), false, false)[0].success);
+
+        let right = DecryptionKey::Passphrase("hunter2".to_string());
+        let results = restorer.restore_directory(&right, temp_dir.path(), false, false);
+        assert!(results[0].success, "{}", results[0].message);
+        assert_eq!(fs::read_to_string(&env_file).unwrap(), "API_KEY=abc\n");
+    }
+
+    #[test]
+    fn test_restore_to_original_uses_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let nested = temp_dir.path().join("services").join("api");
+        fs::create_dir_all(&nested).unwrap();
+        let env_file = nested.join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let key = generate_key();
+        FileProtector::new(secure_dir.clone()).protect_file(&env_file, &ProtectOption::Encrypt, Some(&key));
+
+        let results = FileRestorer::new(secure_dir).restore_directory(&DecryptionKey::Raw(key), temp_dir.path(), true, false);
+        assert!(results[0].success, "{}", results[0].message);
+        assert_eq!(fs::read_to_string(&env_file).unwrap(), "API_KEY=abc\n");
+        assert!(!temp_dir.path().join(".env").exists());
+    }
+
+    #[test]
+    fn test_nested_files_restore_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let nested = temp_dir.path().join("services").join("api");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join(".env"), "API_KEY=abc\n").unwrap();
+        fs::write(temp_dir.path().join(".env"), "ROOT=1\n").unwrap();
+
+        let protector = FileProtector::new(secure_dir.clone());
+        protector.protect_directory(temp_dir.path(), &ProtectOption::Move, None);
+        assert!(secure_dir.join("services/api/.env").exists());
+
+        let results = FileRestorer::new(secure_dir.clone()).unprotect_moved(ConflictPolicy::Skip, false);
+        assert!(results.iter().all(|r| r.success));
+        assert_eq!(fs::read_to_string(nested.join(".env")).unwrap(), "API_KEY=abc\n");
+        assert_eq!(fs::read_to_string(temp_dir.path().join(".env")).unwrap(), "ROOT=1\n");
+        assert!(!secure_dir.join("services").exists());
+
+        let key = generate_key();
+        protector.protect_directory(temp_dir.path(), &ProtectOption::Encrypt, Some(&key));
+        let restored = temp_dir.path().join("restored");
+        let results = FileRestorer::new(secure_dir).restore_directory(&DecryptionKey::Raw(key), &restored, false, false);
+        assert_eq!(results.len(), 2);
+        assert_eq!(fs::read_to_string(restored.join("services/api/.env")).unwrap(), "API_KEY=abc\n");
+        assert_eq!(fs::read_to_string(restored.join(".env")).unwrap(), "ROOT=1\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_metadata_survives_protect_and_restore() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let key_file = temp_dir.path().join("deploy.key");
+        let modified = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let prepare = || {
+            fs::write(&key_file, "KEY\n").unwrap();
+            fs::set_permissions(&key_file, fs::Permissions::from_mode(0o640)).unwrap();
+            fs::File::options().write(true).open(&key_file).unwrap().set_modified(modified).unwrap();
+        };
+
+        prepare();
+        let protector = FileProtector::new(secure_dir.clone());
+        protector.protect_file(&key_file, &ProtectOption::Move, None);
+        assert_eq!(mode(&secure_dir), 0o700);
+        assert_eq!(mode(&secure_dir.join("deploy.key")), 0o600);
+        assert_eq!(mode(&secure_dir.join("manifest.json")), 0o600);
+
+        FileRestorer::new(secure_dir.clone()).unprotect_moved(ConflictPolicy::Skip, false);
+        assert_eq!(mode(&key_file), 0o640);
+        assert_eq!(fs::metadata(&key_file).unwrap().modified().unwrap(), modified);
+
+        prepare();
+        let key = generate_key();
+        protector.protect_file(&key_file, &ProtectOption::Encrypt, Some(&key));
+        assert_eq!(mode(&secure_dir.join("deploy.key.enc")), 0o600);
+
+        let results = FileRestorer::new(secure_dir).restore_directory(&DecryptionKey::Raw(key), temp_dir.path(), true, false);
+        assert!(results[0].success, "{}", results[0].message);
+        assert_eq!(mode(&key_file), 0o640);
+        assert_eq!(fs::metadata(&key_file).unwrap().modified().unwrap(), modified);
+    }
+
+    #[test]
+    fn test_restore_stub() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::create_dir_all(temp_dir.path().join("api")).unwrap();
+        let env_file = temp_dir.path().join("api/.env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let result = FileProtector::new(secure_dir.clone()).protect_file(&env_file, &ProtectOption::Move, None);
+        assert!(result.success, "{}", result.message);
+        let stub = temp_dir.path().join("api/.env.enveil");
+        assert!(stub.exists());
+
+        let result = FileRestorer::restore_stub(&stub, ConflictPolicy::Skip);
+        assert!(result.success, "{}", result.message);
+        assert_eq!(fs::read_to_string(&env_file).unwrap(), "API_KEY=abc\n");
+        assert!(!stub.exists());
+        assert!(!secure_dir.join("api").exists());
+        assert!(Manifest::load(&secure_dir).unwrap().entries.is_empty());
+
+        assert!(!FileRestorer::restore_stub(&env_file, ConflictPolicy::Skip).success);
+    }
+
+    #[test]
+    fn test_unprotect_moved_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        let key_file = temp_dir.path().join("server.key");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+        fs::write(&key_file, "KEY\n").unwrap();
+
+        let protector = FileProtector::new(secure_dir.clone());
+        protector.protect_file(&env_file, &ProtectOption::Move, None);
+        protector.protect_file(&key_file, &ProtectOption::Move, None);
+
+        // Dry run touches nothing
+        let preview = FileRestorer::new(secure_dir.clone()).unprotect_moved(ConflictPolicy::Skip, true);
+        assert_eq!(preview.len(), 2);
+        assert!(!env_file.exists());
+        assert!(temp_dir.path().join(".env.enveil").exists());
+
+        // A recreated file is a conflict and is skipped
+        fs::write(&key_file, "NEW\n").unwrap();
+        let results = FileRestorer::new(secure_dir.clone()).unprotect_moved(ConflictPolicy::Skip, false);
+        assert_eq!(results.iter().filter(|r| r.success).count(), 1);
+        assert_eq!(fs::read_to_string(&env_file).unwrap(), "API_KEY=abc\n");
+        assert_eq!(fs::read_to_string(&key_file).unwrap(), "NEW\n");
+        assert!(!temp_dir.path().join(".env.enveil").exists());
+
+        // Only the skipped file is left in the manifest
+        let manifest = Manifest::load(&secure_dir).unwrap();
+        assert_eq!(manifest.entries.len(), 1);
+
+        let results = FileRestorer::new(secure_dir).unprotect_moved(ConflictPolicy::Rename, false);
+        assert!(results[0].success);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("server.key.restored")).unwrap(), "KEY\n");
+    }
+}
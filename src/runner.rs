@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::protector::ProtectAction;
+
+/// Where the .env file of a run is read from
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvSource {
+    /// Encrypted in the secure directory
+    Encrypted(PathBuf),
+    /// Moved to the secure directory
+    Moved(PathBuf),
+    /// Stored in Vault
+    Vaulted(ManifestEntry),
+    /// Still in the project, possibly with values encrypted inline
+    Plain(PathBuf),
+}
+
+/// Find the .env file `file_name` of a project: its latest protected copy
+/// recorded in the manifest, else the file itself
+pub fn locate(project_path: &Path, secure_dir: &Path, file_name: &str) -> Result<EnvSource, String> {
+    let project = fs::canonicalize(project_path)
+        .map_err(|e| format!("Failed to read {}: {}", project_path.display(), e))?;
+    let original = project.join(file_name);
+
+    let manifest = Manifest::load(secure_dir)?;
+    let entry = manifest
+        .entries
+        .iter()
+        .rev()
+        .find(|entry| Path::new(&entry.original_path) == original);
+
+    match entry {
+        Some(entry) if entry.action == ProtectAction::Vaulted => Ok(EnvSource::Vaulted(entry.clone())),
+        Some(entry) => {
+            let location = entry
+                .location(secure_dir)
+                .filter(|location| location.is_file())
+                .ok_or_else(|| format!("The protected copy of {} is missing from {}", file_name, secure_dir.display()))?;
+            match entry.action {
+                ProtectAction::Encrypted => Ok(EnvSource::Encrypted(location)),
+                _ => Ok(EnvSource::Moved(location)),
+            }
+        }
+        None if original.is_file() => Ok(EnvSource::Plain(original)),
+        None => Err(format!(
+            "{} is neither protected in {} nor present in {}",
+            file_name,
+            secure_dir.display(),
+            project.display()
+        )),
+    }
+}
+
+/// Run a command with extra environment variables, returning its exit code.
+/// The variables only exist in the environment of the child process.
+pub fn run(command: &[String], vars: &[(String, String)]) -> Result<i32, String> {
+    let (program, args) = command.split_first().ok_or("No command to run")?;
+    let status = Command::new(program)
+        .args(args)
+        .envs(vars.iter().map(|(name, value)| (name, value)))
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        // Like a shell: 128 + the signal that killed the command
+        if let Some(signal) = status.signal() {
+            return Ok(128 + signal);
+        }
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::{FileProtector, ProtectOption};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_locate() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::write(temp_dir.path().join(".env"), "A=1\n").unwrap();
+        fs::write(temp_dir.path().join(".env.production"), "A=2\n").unwrap();
+
+        let key = crate::protector::generate_key();
+        let result = FileProtector::new(secure_dir.clone())
+            .protect_file(&temp_dir.path().join(".env.production"), &ProtectOption::Encrypt, Some(&key));
+        assert!(result.success);
+
+        assert!(matches!(locate(temp_dir.path(), &secure_dir, ".env").unwrap(), EnvSource::Plain(_)));
+        match locate(temp_dir.path(), &secure_dir, ".env.production").unwrap() {
+            EnvSource::Encrypted(path) => assert_eq!(path, secure_dir.join(".env.production.enc")),
+            other => panic!("unexpected source {:?}", other),
+        }
+        assert!(locate(temp_dir.path(), &secure_dir, ".env.staging").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_passes_variables_and_exit_code() {
+        let vars = vec![("ENVEIL_TEST_VALUE".to_string(), "42".to_string())];
+        let command = |script: &str| vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+
+        assert_eq!(run(&command("test \"$ENVEIL_TEST_VALUE\" = 42"), &vars).unwrap(), 0);
+        assert_eq!(run(&command("exit 3"), &vars).unwrap(), 3);
+        assert!(run(&["enveil-missing-command".to_string()], &vars).is_err());
+    }
+}
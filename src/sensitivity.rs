@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::init::CONFIG_FILE;
+use crate::stub::Stub;
+
+/// Risk levels accepted in `[severity]`; `none` drops a file type
+pub const RISK_LEVELS: &[&str] = &["high", "medium", "low", "none"];
+
+/// File type of `.env` files, whatever their suffix (`.env.production`, `prod.env`)
+pub const ENV_FILE: &str = ".env";
+
+/// File type reported for Dockerfiles
+pub const DOCKERFILE: &str = "Dockerfile";
+
+/// File type of the committed templates of sensitive files (`.env.example`):
+/// scanned for real secrets, but never protected
+pub const TEMPLATE: &str = "template";
+
+/// Suffixes of templates: `.env.example`, `.env.sample`, `config.php.dist`
+pub const TEMPLATE_SUFFIXES: &[&str] = &[".example", ".sample", ".dist"];
+
+/// Directory of Enveil's own artifacts in a project
+const ENVEIL_DIR: &str = ".enveil";
+
+/// Directories never walked, even with `--hidden` (`.enveil` holds the
+/// baseline, cache and honeytoken registry)
+pub const SKIP_DIRS: &[&str] = &[".git", ENVEIL_DIR, "node_modules", "target", "dist", "build", "vendor"];
+
+/// Hidden directories walked by default: CI pipelines and editor settings
+/// often hold credentials
+pub const HIDDEN_DIRS: &[&str] = &[".github", ".gitlab", ".circleci", ".buildkite", ".devcontainer", ".vscode"];
+
+/// Risk level of the file types scan reports and protect picks up, keyed by
+/// extension, by file name, or by the type of files matched by a name
+/// pattern (`.env`, `Dockerfile`, `.tfstate`)
+const DEFAULT_RISKS: &[(&str, &str)] = &[
+    // Secrets & Config
+    (".env", "high"),
+    (TEMPLATE, "low"),
+    (".tfvars", "high"),
+    (".tfstate", "high"),
+    (".json", "medium"),
+    (".yaml", "medium"),
+    (".yml", "medium"),
+    (".toml", "medium"),
+    (".ini", "low"),
+    (".conf", "low"),
+    (".config", "low"),
+    ("Dockerfile", "medium"),
+    // Keys & Credentials
+    (".pem", "high"),
+    (".key", "high"),
+    (".p12", "high"),
+    (".pfx", "high"),
+    (".pub", "low"),
+    (".crt", "low"),
+    (".cer", "low"),
+    // Sensitive
+    (".sql", "medium"),
+    (".db", "medium"),
+    (".sqlite", "low"),
+    (".sqlite3", "low"),
+    (".log", "low"),
+    (".bak", "low"),
+    (".backup", "low"),
+    (".old", "low"),
+    // Credentials recognized by file name
+    ("id_rsa", "high"),
+    ("id_ed25519", "high"),
+    ("id_dsa", "high"),
+    ("id_ecdsa", "high"),
+    ("known_hosts", "low"),
+    ("authorized_keys", "low"),
+    ("npmrc", "high"),
+    (".npmrc", "high"),
+    ("pip.conf", "high"),
+    (".netrc", "high"),
+    (".git-credentials", "high"),
+    ("service-account.json", "high"),
+    ("credentials.json", "high"),
+    ("secrets.yaml", "high"),
+    ("secrets.yml", "high"),
+];
+
+/// A sensitive file: its type and how risky it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    /// Extension, file name or pattern type (e.g. `.pem`, `id_rsa`, `.env`)
+    pub file_type: String,
+    pub risk_level: String,
+}
+
+impl Classification {
+    /// Whether the file is the template of a sensitive file
+    pub fn is_template(&self) -> bool {
+        self.file_type == TEMPLATE
+    }
+}
+
+/// File type to risk level mapping shared by scan and protect: the built-in
+/// table with the `[severity]` overrides of the config
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskTable {
+    levels: HashMap<String, String>,
+}
+
+impl Default for RiskTable {
+    fn default() -> Self {
+        Self {
+            levels: DEFAULT_RISKS.iter().map(|(file_type, level)| (file_type.to_string(), level.to_string())).collect(),
+        }
+    }
+}
+
+impl RiskTable {
+    /// The built-in table with overrides (e.g. `".log" = "high"`). New file
+    /// types are added (`".kdbx" = "high"`) and `none` removes one.
+    pub fn new(overrides: &HashMap<String, String>) -> Self {
+        let mut table = Self::default();
+        for (file_type, level) in overrides {
+            match level.as_str() {
+                "none" => table.levels.remove(file_type),
+                _ => table.levels.insert(file_type.clone(), level.clone()),
+            };
+        }
+        table
+    }
+
+    /// The table of a project configuration, with its extra extensions
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.severity).with_extensions(&config.scan.extra_extensions)
+    }
+
+    /// Add extensions at low risk, unless the table already has them
+    pub fn with_extensions(mut self, extensions: &[String]) -> Self {
+        for extension in extensions {
+            self.levels.entry(extension.clone()).or_insert_with(|| "low".to_string());
+        }
+        self
+    }
+
+    /// Risk level of a file type, `None` when it is not a risky type
+    pub fn level(&self, file_type: &str) -> Option<&str> {
+        self.levels.get(file_type).map(String::as_str)
+    }
+
+    /// Classify a file by its name, then its extension. `None` when it is not
+    /// sensitive. This is what scan, protect and the hooks all go by.
+    pub fn classify(&self, path: &Path) -> Option<Classification> {
+        // Stubs left by `protect` hold no secret
+        if Stub::is_stub(path) {
+            return None;
+        }
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        // Enveil's own config and `.enveil/` artifacts are not secrets to protect
+        if file_name == CONFIG_FILE || path.components().any(|c| c.as_os_str() == ENVEIL_DIR) {
+            return None;
+        }
+        // `*.dist` files, and `.example` or `.sample` copies of sensitive files
+        let original = TEMPLATE_SUFFIXES
+            .iter()
+            .find_map(|suffix| file_name.strip_suffix(suffix))
+            .filter(|original| !original.is_empty());
+        let template = original.is_some_and(|original| {
+            file_name.ends_with(".dist") || self.classify(&path.with_file_name(original)).is_some()
+        });
+
+        let file_type = match pattern_type(file_name) {
+            _ if template => TEMPLATE.to_string(),
+            Some(file_type) => file_type.to_string(),
+            None if self.levels.contains_key(file_name) => file_name.to_string(),
+            None => path.extension().and_then(|e| e.to_str()).map(|e| format!(".{}", e)).unwrap_or_default(),
+        };
+        let risk_level = self.level(&file_type)?.to_string();
+        Some(Classification { file_type, risk_level })
+    }
+
+    /// Whether `protect` picks up a file: sensitive files, but not templates
+    pub fn needs_protection(&self, path: &Path) -> bool {
+        self.classify(path).is_some_and(|classification| !classification.is_template())
+    }
+}
+
+/// Whether a walk skips a directory by name: dependency and build output
+/// directories, the secure directory, and hidden ones outside `HIDDEN_DIRS`
+/// unless `hidden`
+pub fn is_skipped_dir(dir_name: &str, secure_dir: &Path, hidden: bool) -> bool {
+    SKIP_DIRS.contains(&dir_name)
+        || secure_dir.file_name() == Some(OsStr::new(dir_name))
+        || (dir_name.starts_with('.') && !hidden && !HIDDEN_DIRS.contains(&dir_name))
+}
+
+/// File type of files recognized by a name pattern rather than extension
+fn pattern_type(file_name: &str) -> Option<&'static str> {
+    // `.env`, `.env.production`, `prod.env`
+    if file_name == ENV_FILE || file_name.starts_with(".env.") || file_name.ends_with(ENV_FILE) {
+        return Some(ENV_FILE);
+    }
+
+    // `Dockerfile`, `Dockerfile.dev`, `api.dockerfile`
+    if file_name == DOCKERFILE
+        || file_name.starts_with("Dockerfile.")
+        || file_name.to_lowercase().ends_with(".dockerfile")
+    {
+        return Some(DOCKERFILE);
+    }
+
+    // `terraform.tfstate`, `terraform.tfstate.backup`
+    if file_name.ends_with(".tfstate") || file_name.contains(".tfstate.") {
+        return Some(".tfstate");
+    }
+
+    None
+}
+
+/// Check the `[severity]` overrides of a config
+pub fn validate(overrides: &HashMap<String, String>) -> Result<(), String> {
+    for (file_type, level) in overrides {
+        if !RISK_LEVELS.contains(&level.as_str()) {
+            return Err(format!(
+                "Invalid severity for {}: {} (expected high, medium, low or none)",
+                file_type, level
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::FileProtector;
+    use crate::scanner::Scanner;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    /// Files of every kind the table knows, and some it does not
+    const FILES: &[&str] = &[
+        ".env",
+        ".env.production",
+        "prod.env",
+        "id_rsa",
+        ".npmrc",
+        "credentials.json",
+        "config.json",
+        "server.pem",
+        "app.log",
+        "data.sqlite3",
+        "settings.old",
+        "Dockerfile",
+        "terraform.tfstate",
+        "passwords.kdbx",
+        ".env.example",
+        "config.yml.sample",
+        "config.php.dist",
+        "notes.example",
+        "readme.txt",
+        "main.rs",
+        "app.py.enveil",
+        ".enveil.toml",
+        ".environment",
+    ];
+
+    #[test]
+    fn test_classify() {
+        let table = RiskTable::default();
+        let classify = |path: &str| table.classify(Path::new(path)).map(|c| (c.file_type, c.risk_level));
+        assert_eq!(classify("/project/.env.production"), Some((".env".to_string(), "high".to_string())));
+        assert_eq!(classify("/project/id_rsa"), Some(("id_rsa".to_string(), "high".to_string())));
+        assert_eq!(classify("/project/credentials.json"), Some(("credentials.json".to_string(), "high".to_string())));
+        assert_eq!(classify("/project/config.json"), Some((".json".to_string(), "medium".to_string())));
+        assert_eq!(classify("/project/api.dockerfile"), Some((DOCKERFILE.to_string(), "medium".to_string())));
+        assert_eq!(classify("/project/readme.txt"), None);
+        assert_eq!(classify("/project/.env.enveil"), None);
+        // Enveil's own files are neither `.env` files nor protected
+        assert_eq!(classify("/project/.enveil.toml"), None);
+        assert_eq!(classify("/project/.enveil/baseline.json"), None);
+        assert_eq!(classify("/project/.environment"), None);
+        assert!(!table.needs_protection(Path::new("/project/.enveil.toml")));
+
+        let template = Some((TEMPLATE.to_string(), "low".to_string()));
+        assert_eq!(classify("/project/.env.example"), template);
+        assert_eq!(classify("/project/.env.sample"), template);
+        assert_eq!(classify("/project/credentials.json.example"), template);
+        assert_eq!(classify("/project/config.php.dist"), template);
+        assert_eq!(classify("/project/notes.example"), None);
+        assert!(!table.needs_protection(Path::new("/project/.env.example")));
+        assert!(table.needs_protection(Path::new("/project/.env")));
+    }
+
+    /// Scan, protect and the pre-commit hook must agree on what is sensitive
+    #[test]
+    fn test_scan_protect_and_hooks_agree() {
+        let temp_dir = TempDir::new().unwrap();
+        for file in FILES {
+            fs::write(temp_dir.path().join(file), "placeholder\n").unwrap();
+        }
+        let overrides = [(".kdbx".to_string(), "high".to_string()), (".old".to_string(), "none".to_string())].into_iter().collect();
+        let relative = |paths: Vec<String>| -> BTreeSet<String> {
+            paths.iter().map(|p| Path::new(p).file_name().unwrap().to_string_lossy().to_string()).collect()
+        };
+
+        let scanner = Scanner::builder().severity(".kdbx", "high").severity(".old", "none").build().unwrap();
+        let scanned = relative(scanner.scan(temp_dir.path()).unwrap().files.into_iter().map(|f| f.path).collect());
+
+        let protector = FileProtector::new(temp_dir.path().join("enveil_secure")).with_risks(RiskTable::new(&overrides));
+        let protected = relative(
+            protector.find_sensitive_files(temp_dir.path()).iter().map(|p| p.to_string_lossy().to_string()).collect(),
+        );
+
+        let git = |args: &[&str]| assert!(Command::new("git").args(args).current_dir(temp_dir.path()).output().unwrap().status.success());
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        let staged = relative(scanner.scan_staged(temp_dir.path()).unwrap().files.into_iter().map(|f| f.path).collect());
+
+        let expected: BTreeSet<String> = FILES
+            .iter()
+            .filter(|file| !["readme.txt", "main.rs", "app.py.enveil", "settings.old", "notes.example", ".enveil.toml", ".environment"].contains(file))
+            .map(|file| file.to_string())
+            .collect();
+        let templates = [".env.example", "config.yml.sample", "config.php.dist"];
+        assert_eq!(scanned, expected);
+        assert_eq!(staged, expected);
+        // Templates are reported, but left in place
+        assert_eq!(protected, expected.iter().filter(|file| !templates.contains(&file.as_str())).cloned().collect());
+    }
+
+    /// Scan and protect walk the same directories, and never the secure one
+    #[test]
+    fn test_scan_and_protect_skip_the_same_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        for file in [".github/ci.json", ".idea/workspace.json", "node_modules/pkg/config.json", "vault/cfg/id_rsa", "src/config.json"] {
+            let path = temp_dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "placeholder\n").unwrap();
+        }
+        let relative = |paths: Vec<String>| -> BTreeSet<String> {
+            paths
+                .iter()
+                .map(|p| Path::new(p).strip_prefix(temp_dir.path()).unwrap_or(Path::new(p)).to_string_lossy().to_string())
+                .collect()
+        };
+
+        let scanner = Scanner::builder().secure_dir("vault").build().unwrap();
+        let scanned = relative(scanner.scan(temp_dir.path()).unwrap().files.into_iter().map(|f| f.path).collect());
+        let protector = FileProtector::new(temp_dir.path().join("vault"));
+        let protected = relative(
+            protector.find_sensitive_files(temp_dir.path()).iter().map(|p| p.to_string_lossy().to_string()).collect(),
+        );
+
+        let expected: BTreeSet<String> = [".github/ci.json", "src/config.json"].iter().map(|file| file.to_string()).collect();
+        assert_eq!(scanned, expected);
+        assert_eq!(protected, expected);
+    }
+
+    #[test]
+    fn test_is_skipped_dir() {
+        let secure_dir = Path::new("/project/vault");
+        assert!(is_skipped_dir("node_modules", secure_dir, false));
+        assert!(is_skipped_dir("vault", secure_dir, false));
+        assert!(!is_skipped_dir("enveil_secure", secure_dir, false));
+        assert!(is_skipped_dir(".idea", secure_dir, false));
+        assert!(!is_skipped_dir(".github", secure_dir, false));
+        assert!(!is_skipped_dir(".devcontainer", secure_dir, false));
+        assert!(!is_skipped_dir("src", secure_dir, false));
+        assert!(!is_skipped_dir(".idea", secure_dir, true));
+        assert!(is_skipped_dir(".git", secure_dir, true));
+        assert!(is_skipped_dir(".enveil", secure_dir, true));
+    }
+
+    #[test]
+    fn test_default_levels() {
+        let table = RiskTable::default();
+        assert_eq!(table.level(".env"), Some("high"));
+        assert_eq!(table.level(".tfstate"), Some("high"));
+        assert_eq!(table.level(".json"), Some("medium"));
+        assert_eq!(table.level(".log"), Some("low"));
+        assert_eq!(table.level(".txt"), None);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides: HashMap<String, String> = [(".log", "high"), (".kdbx", "high"), (".bak", "none")]
+            .iter()
+            .map(|(file_type, level)| (file_type.to_string(), level.to_string()))
+            .collect();
+        validate(&overrides).unwrap();
+
+        let table = RiskTable::new(&overrides).with_extensions(&[".properties".to_string(), ".log".to_string()]);
+        assert_eq!(table.level(".log"), Some("high"));
+        assert_eq!(table.level(".kdbx"), Some("high"));
+        assert_eq!(table.level(".bak"), None);
+        assert_eq!(table.level(".properties"), Some("low"));
+
+        let invalid: HashMap<String, String> = [(".log".to_string(), "severe".to_string())].into_iter().collect();
+        assert!(validate(&invalid).unwrap_err().contains("Invalid severity for .log"));
+    }
+}
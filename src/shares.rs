@@ -0,0 +1,117 @@
+use base64::Engine;
+use sharks::{Share, Sharks};
+use std::fmt;
+use std::str::FromStr;
+
+/// Prefix of encoded key shares
+pub const SHARE_PREFIX: &str = "enveil-share-";
+
+/// How many of how many shares a key is split into (`3-of-5`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    /// Shares needed to rebuild the key
+    pub required: u8,
+    /// Shares handed out
+    pub total: u8,
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid split {} (expected e.g. 3-of-5)", s);
+        let (required, total) = s.split_once("-of-").ok_or_else(invalid)?;
+        let required: u8 = required.trim().parse().map_err(|_| invalid())?;
+        let total: u8 = total.trim().parse().map_err(|_| invalid())?;
+
+        if required < 2 || required > total {
+            return Err(format!("Invalid split {}: need 2 or more shares, at most as many as handed out", s));
+        }
+        Ok(Threshold { required, total })
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-of-{}", self.required, self.total)
+    }
+}
+
+/// Split a key into Shamir shares, any `threshold.required` of which
+/// rebuild it. Each share is printable: `enveil-share-<required>-<base64>`.
+pub fn split(key: &[u8; 32], threshold: Threshold) -> Vec<String> {
+    Sharks(threshold.required)
+        .dealer(key)
+        .take(threshold.total as usize)
+        .map(|share| {
+            let bytes = Vec::from(&share);
+            format!(
+                "{}{}-{}",
+                SHARE_PREFIX,
+                threshold.required,
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+            )
+        })
+        .collect()
+}
+
+/// Rebuild a key from its shares
+pub fn combine(shares: &[String]) -> Result<[u8; 32], String> {
+    let mut required = None;
+    let mut decoded = Vec::new();
+
+    for (index, encoded) in shares.iter().enumerate() {
+        let invalid = || format!("Invalid share #{}", index + 1);
+        let (needed, data) = encoded
+            .trim()
+            .strip_prefix(SHARE_PREFIX)
+            .and_then(|rest| rest.split_once('-'))
+            .ok_or_else(invalid)?;
+        let needed: u8 = needed.parse().map_err(|_| invalid())?;
+        if *required.get_or_insert(needed) != needed {
+            return Err("Shares come from different splits".to_string());
+        }
+
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(data)
+            .map_err(|_| invalid())?;
+        decoded.push(Share::try_from(bytes.as_slice()).map_err(|_| invalid())?);
+    }
+
+    let required = required.ok_or("No shares given")?;
+    if decoded.len() < required as usize {
+        return Err(format!("{} share(s) given, {} needed", decoded.len(), required));
+    }
+
+    let key = Sharks(required)
+        .recover(&decoded)
+        .map_err(|e| format!("Failed to rebuild the key: {}", e))?;
+    <[u8; 32]>::try_from(key).map_err(|_| "Shares do not rebuild an encryption key".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector;
+
+    #[test]
+    fn test_threshold_parse() {
+        assert_eq!("3-of-5".parse::<Threshold>().unwrap(), Threshold { required: 3, total: 5 });
+        assert!("1-of-5".parse::<Threshold>().is_err());
+        assert!("4-of-3".parse::<Threshold>().is_err());
+        assert!("three".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn test_split_and_combine() {
+        let key = protector::generate_key();
+        let shares = split(&key, "3-of-5".parse().unwrap());
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|s| s.starts_with("enveil-share-3-")));
+
+        assert_eq!(combine(&shares[..3]).unwrap(), key);
+        assert_eq!(combine(&[shares[4].clone(), shares[1].clone(), shares[2].clone()]).unwrap(), key);
+        assert!(combine(&shares[..2]).unwrap_err().contains("3 needed"));
+        assert!(combine(&["enveil-share-3-!!".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::protector;
+use crate::recipient::WrappedKey;
+
+/// Magic bytes starting an encrypted file, followed by the format version
+pub const MAGIC: &[u8; 7] = b"ENVEIL1";
+
+/// Version of the header written by `encrypt`
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Magic bytes of the chunked files written before the authenticated
+/// header, after the passphrase header if any
+pub const LEGACY_STREAM_MAGIC: &[u8; 8] = b"ENVEILS1";
+
+/// Plaintext bytes per frame
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Largest frame accepted when decrypting, so a corrupted header cannot
+/// make decryption allocate without bound
+const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest serialized header accepted when decrypting
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+/// Largest Argon2 memory cost accepted from a header (1 GiB)
+const MAX_KDF_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Random part of the frame nonces, stored in the header
+pub const PREFIX_LEN: usize = 7;
+
+/// Length of the GCM tag closing each frame
+const TAG_LEN: usize = 16;
+
+/// Argon2id parameters a passphrase key was derived with
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KdfParams {
+    pub algorithm: String,
+    /// Base64 salt
+    pub salt: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// The parameters `protector::derive_key` uses, with a salt
+    pub fn argon2id(salt: &[u8]) -> Self {
+        Self {
+            algorithm: "argon2id".to_string(),
+            salt: base64::engine::general_purpose::STANDARD.encode(salt),
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+
+    /// Derive the key from a passphrase
+    pub fn derive(&self, passphrase: &str) -> Result<[u8; 32], String> {
+        if self.algorithm != "argon2id" {
+            return Err(format!("Unsupported key derivation: {}", self.algorithm));
+        }
+        if self.memory_kib > MAX_KDF_MEMORY_KIB {
+            return Err(format!("Key derivation memory cost too high: {} KiB", self.memory_kib));
+        }
+
+        let salt = base64::engine::general_purpose::STANDARD
+            .decode(&self.salt)
+            .map_err(|e| format!("Invalid salt in header: {}", e))?;
+        protector::derive_key_with(passphrase, &salt, self.memory_kib, self.iterations, self.parallelism)
+    }
+}
+
+/// Header of an encrypted file. Its bytes are the associated data of every
+/// frame, so a changed header makes decryption fail.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileHeader {
+    /// Key derivation of passphrase keys, `None` for raw keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<KdfParams>,
+    /// Name of the original file
+    pub file_name: String,
+    /// Plaintext bytes per frame
+    pub chunk_size: usize,
+    /// Base64 random part of the frame nonces
+    pub nonce_prefix: String,
+    /// Hex HMAC-SHA256 of the plaintext under the key, checked after
+    /// decryption (keyed, so it says nothing about the content)
+    pub checksum: String,
+    /// File key wrapped for each recipient, empty for shared keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<WrappedKey>,
+}
+
+impl FileHeader {
+    /// Header for a new file, with a fresh nonce prefix
+    pub fn new(file_name: &str, kdf: Option<KdfParams>, checksum: String) -> Self {
+        let mut prefix = [0u8; PREFIX_LEN];
+        rand::thread_rng().fill(&mut prefix);
+
+        Self {
+            kdf,
+            file_name: file_name.to_string(),
+            chunk_size: CHUNK_SIZE,
+            nonce_prefix: base64::engine::general_purpose::STANDARD.encode(prefix),
+            checksum,
+            recipients: Vec::new(),
+        }
+    }
+
+    /// `MAGIC || version || length (u32 BE) || JSON header`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let json = serde_json::to_vec(self).map_err(|e| format!("Failed to serialize header: {}", e))?;
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&(json.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&json);
+        Ok(bytes)
+    }
+
+    /// Read and validate the header at the start of `reader`, returning it
+    /// with its raw bytes
+    pub fn read<R: Read>(reader: &mut R) -> Result<(Self, Vec<u8>), String> {
+        let mut fixed = [0u8; 12];
+        reader.read_exact(&mut fixed).map_err(|_| "Encrypted file is truncated".to_string())?;
+        if &fixed[..MAGIC.len()] != MAGIC.as_slice() {
+            return Err("Not an Enveil encrypted file".to_string());
+        }
+        let version = fixed[MAGIC.len()];
+        if version != FORMAT_VERSION {
+            return Err(format!("Unsupported encrypted file version {} (upgrade enveil)", version));
+        }
+
+        let len = u32::from_be_bytes([fixed[8], fixed[9], fixed[10], fixed[11]]) as usize;
+        if len > MAX_HEADER_LEN {
+            return Err("Invalid header length (corrupted file)".to_string());
+        }
+        let mut json = vec![0u8; len];
+        reader.read_exact(&mut json).map_err(|_| "Encrypted file is truncated".to_string())?;
+
+        let header: Self = serde_json::from_slice(&json).map_err(|e| format!("Invalid header: {}", e))?;
+        if header.chunk_size == 0 || header.chunk_size > MAX_CHUNK_SIZE {
+            return Err(format!("Invalid chunk size {} (corrupted file)", header.chunk_size));
+        }
+        header.prefix()?;
+
+        let mut raw = fixed.to_vec();
+        raw.extend_from_slice(&json);
+        Ok((header, raw))
+    }
+
+    fn prefix(&self) -> Result<[u8; PREFIX_LEN], String> {
+        base64::engine::general_purpose::STANDARD
+            .decode(&self.nonce_prefix)
+            .ok()
+            .and_then(|prefix| prefix.try_into().ok())
+            .ok_or_else(|| "Invalid nonce prefix in header".to_string())
+    }
+}
+
+/// Keyed checksum of everything `reader` yields, as stored in the header
+pub fn checksum<R: Read>(key: &[u8; 32], mut reader: R) -> Result<String, String> {
+    let mut mac = new_mac(key)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let len = read_full(&mut reader, &mut buf).map_err(read_error)?;
+        mac.update(&buf[..len]);
+        if len < buf.len() {
+            break;
+        }
+    }
+    Ok(mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Encrypt `reader` into `writer`: the header, then AES-256-GCM frames of
+/// `chunk_size` bytes, holding one frame in memory at a time.
+///
+/// Frame `i` is sealed with the nonce `prefix || i (u32 BE) || last`, where
+/// `last` is 1 on the final frame only, so frames cannot be reordered,
+/// dropped or truncated without decryption failing.
+pub fn encrypt<R: Read, W: Write>(reader: R, mut writer: W, key: &[u8; 32], header: &FileHeader) -> Result<(), String> {
+    let cipher = new_cipher(key)?;
+    let prefix = header.prefix()?;
+    let aad = header.to_bytes()?;
+    writer.write_all(&aad).map_err(write_error)?;
+
+    let mut reader = BufReader::new(reader);
+    let mut chunk = vec![0u8; header.chunk_size];
+    let mut counter: u32 = 0;
+    loop {
+        let len = read_full(&mut reader, &mut chunk).map_err(read_error)?;
+        let last = len < chunk.len() || at_end(&mut reader)?;
+
+        let payload = Payload { msg: &chunk[..len], aad: &aad };
+        let sealed = cipher
+            .encrypt(Nonce::from_slice(&frame_nonce(&prefix, counter, last)), payload)
+            .map_err(|e| format!("Encryption failed: {}", e))?;
+        writer.write_all(&sealed).map_err(write_error)?;
+
+        if last {
+            break;
+        }
+        counter = counter.checked_add(1).ok_or("File too large to encrypt")?;
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+/// Decrypt the frames following a header read by `FileHeader::read`, then
+/// verify the checksum
+pub fn decrypt<R: Read, W: Write>(reader: R, writer: W, key: &[u8; 32], header: &FileHeader, aad: &[u8]) -> Result<(), String> {
+    let mut mac = new_mac(key)?;
+    open_frames(reader, writer, key, &header.prefix()?, header.chunk_size, aad, |plaintext| mac.update(plaintext))?;
+
+    let expected = decode_hex(&header.checksum).ok_or("Invalid checksum in header")?;
+    mac.verify_slice(&expected)
+        .map_err(|_| "Checksum mismatch (corrupted file)".to_string())
+}
+
+/// Decrypt a chunked file written before the authenticated header,
+/// starting after `LEGACY_STREAM_MAGIC`
+pub fn decrypt_legacy<R: Read, W: Write>(mut reader: R, writer: W, key: &[u8; 32]) -> Result<(), String> {
+    let mut header = [0u8; 4 + PREFIX_LEN];
+    if read_full(&mut reader, &mut header).map_err(read_error)? < header.len() {
+        return Err("Encrypted file is truncated".to_string());
+    }
+    let chunk_size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(format!("Invalid chunk size {} (corrupted file)", chunk_size));
+    }
+    let mut prefix = [0u8; PREFIX_LEN];
+    prefix.copy_from_slice(&header[4..]);
+
+    open_frames(reader, writer, key, &prefix, chunk_size, &[], |_| {})
+}
+
+fn open_frames<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    key: &[u8; 32],
+    prefix: &[u8; PREFIX_LEN],
+    chunk_size: usize,
+    aad: &[u8],
+    mut on_plaintext: impl FnMut(&[u8]),
+) -> Result<(), String> {
+    let cipher = new_cipher(key)?;
+    let mut reader = BufReader::new(reader);
+    let mut frame = vec![0u8; chunk_size + TAG_LEN];
+    let mut counter: u32 = 0;
+    loop {
+        let len = read_full(&mut reader, &mut frame).map_err(read_error)?;
+        let last = len < frame.len() || at_end(&mut reader)?;
+
+        let payload = Payload { msg: &frame[..len], aad };
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&frame_nonce(prefix, counter, last)), payload)
+            .map_err(|_| "Decryption failed (wrong key or corrupted file)".to_string())?;
+        on_plaintext(&plaintext);
+        writer
+            .write_all(&plaintext)
+            .map_err(|e| format!("Failed to write decrypted file: {}", e))?;
+
+        if last {
+            break;
+        }
+        counter = counter.checked_add(1).ok_or("Encrypted file has too many frames")?;
+    }
+
+    writer.flush().map_err(|e| format!("Failed to write decrypted file: {}", e))
+}
+
+fn new_cipher(key: &[u8; 32]) -> Result<Aes256Gcm, String> {
+    Aes256Gcm::new_from_slice(key).map_err(|e| format!("Failed to create cipher: {}", e))
+}
+
+fn new_mac(key: &[u8; 32]) -> Result<Hmac<Sha256>, String> {
+    <Hmac<Sha256> as Mac>::new_from_slice(key).map_err(|e| format!("Failed to create checksum: {}", e))
+}
+
+fn frame_nonce(prefix: &[u8; PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/// Fill `buf` as far as the input allows, returning the bytes read
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn at_end<R: Read>(reader: &mut BufReader<R>) -> Result<bool, String> {
+    reader.fill_buf().map(|buf| buf.is_empty()).map_err(read_error)
+}
+
+fn read_error(e: std::io::Error) -> String {
+    format!("Failed to read file: {}", e)
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Failed to write encrypted file: {}", e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [3u8; 32];
+
+    fn seal(plaintext: &[u8]) -> Vec<u8> {
+        let header = FileHeader::new(".env", None, checksum(&KEY, plaintext).unwrap());
+        let mut encrypted = Vec::new();
+        encrypt(plaintext, &mut encrypted, &KEY, &header).unwrap();
+        encrypted
+    }
+
+    fn open(mut encrypted: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, String> {
+        let (header, aad) = FileHeader::read(&mut encrypted)?;
+        let mut decrypted = Vec::new();
+        decrypt(encrypted, &mut decrypted, key, &header, &aad)?;
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn test_round_trip_across_frames() {
+        let plaintext: Vec<u8> = (0..CHUNK_SIZE * 2 + 123).map(|i| (i % 251) as u8).collect();
+        let encrypted = seal(&plaintext);
+        assert!(encrypted.starts_with(MAGIC));
+        assert_eq!(encrypted[MAGIC.len()], FORMAT_VERSION);
+        assert_eq!(open(&encrypted, &KEY).unwrap(), plaintext);
+
+        // Exactly one frame, then an empty file
+        assert_eq!(open(&seal(&plaintext[..CHUNK_SIZE]), &KEY).unwrap(), &plaintext[..CHUNK_SIZE]);
+        assert!(open(&seal(b""), &KEY).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let encrypted = seal(b"API_KEY=abc\n");
+        let (header, aad) = FileHeader::read(&mut encrypted.as_slice()).unwrap();
+        assert_eq!(header.file_name, ".env");
+
+        // Renaming the file in the header breaks every frame
+        let mut renamed = header.clone();
+        renamed.file_name = ".evil".to_string();
+        let mut tampered = renamed.to_bytes().unwrap();
+        tampered.extend_from_slice(&encrypted[aad.len()..]);
+        assert!(open(&tampered, &KEY).is_err());
+
+        let mut future = encrypted.clone();
+        future[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert!(open(&future, &KEY).unwrap_err().contains("Unsupported encrypted file version"));
+
+        assert!(open(&encrypted, &[4u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_truncated_or_reordered_frames_fail() {
+        let encrypted = seal(&vec![7u8; CHUNK_SIZE * 2 + 10]);
+        let (_, aad) = FileHeader::read(&mut encrypted.as_slice()).unwrap();
+        let (header, body) = encrypted.split_at(aad.len());
+        let frame = CHUNK_SIZE + TAG_LEN;
+
+        // Dropping the final frame leaves a frame not marked as last
+        let truncated = [header, &body[..2 * frame]].concat();
+        assert!(open(&truncated, &KEY).is_err());
+
+        let swapped = [header, &body[frame..2 * frame], &body[..frame], &body[2 * frame..]].concat();
+        assert!(open(&swapped, &KEY).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Key names (normalized, compared by suffix) whose values are secrets
+const SENSITIVE_KEYS: &[&str] = &[
+    "password",
+    "passwd",
+    "pwd",
+    "secret",
+    "secret_key",
+    "token",
+    "api_key",
+    "apikey",
+    "private_key",
+    "access_key",
+    "credentials",
+];
+
+/// Parent keys whose every value is a secret (Kubernetes `stringData`)
+const SENSITIVE_SECTIONS: &[&str] = &["stringData"];
+
+/// A string value of a parsed config file with its key path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaf {
+    /// Dotted path from the document root, e.g. `database.password`
+    pub key_path: String,
+    /// Last key of the path
+    pub key: String,
+    pub value: String,
+    /// Whether the key or its section marks the value as a secret
+    pub sensitive: bool,
+    /// Decoded text of a base64 value in a Kubernetes Secret `data` map
+    pub decoded: Option<String>,
+}
+
+/// Whether a file is JSON (or Terraform state), YAML or TOML by its
+/// extension, and so is parsed rather than scanned as flat text
+pub fn is_structured(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ["json", "tfstate", "toml", "yaml", "yml"].contains(&e.to_lowercase().as_str()))
+}
+
+/// Parse a JSON, YAML or TOML file into its string leaves.
+///
+/// Returns `None` for other file types and for content that does not parse,
+/// in which case the file should be scanned as flat text.
+pub fn parse(path: &Path, content: &str) -> Option<Vec<Leaf>> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    let documents: Vec<Value> = match extension.as_str() {
+        // Terraform state is JSON
+        "json" | "tfstate" => vec![serde_json::from_str(content).ok()?],
+        "toml" => vec![toml::from_str(content).ok()?],
+        "yaml" | "yml" => serde_yaml::Deserializer::from_str(content)
+            .map(Value::deserialize)
+            .collect::<Result<_, _>>()
+            .ok()?,
+        _ => return None,
+    };
+
+    let mut leaves = Vec::new();
+    for document in &documents {
+        let start = leaves.len();
+        walk(document, "", false, &mut leaves);
+
+        if is_kubernetes_secret(document) {
+            for leaf in &mut leaves[start..] {
+                decode_secret_data(leaf);
+            }
+        }
+    }
+    Some(leaves)
+}
+
+/// Whether a document is a Kubernetes `kind: Secret` manifest
+fn is_kubernetes_secret(document: &Value) -> bool {
+    document.get("kind").and_then(Value::as_str) == Some("Secret")
+        && document.get("apiVersion").is_some()
+}
+
+/// Base64-decode a value of the Secret `data` map, which always holds secrets
+fn decode_secret_data(leaf: &mut Leaf) {
+    if leaf.key_path != format!("data.{}", leaf.key) {
+        return;
+    }
+
+    leaf.sensitive = true;
+    leaf.decoded = base64::engine::general_purpose::STANDARD
+        .decode(leaf.value.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok());
+}
+
+fn walk(value: &Value, path: &str, in_section: bool, leaves: &mut Vec<Leaf>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let child_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                let section = in_section || SENSITIVE_SECTIONS.contains(&key.as_str());
+
+                if let Value::String(text) = child {
+                    leaves.push(Leaf {
+                        key_path: child_path,
+                        key: key.clone(),
+                        value: text.clone(),
+                        sensitive: in_section || is_sensitive_key(key),
+                        decoded: None,
+                    });
+                } else {
+                    walk(child, &child_path, section, leaves);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                match item.as_str().and_then(split_assignment) {
+                    // `NAME=value` items, as in docker-compose `environment` lists
+                    Some((name, text)) => leaves.push(Leaf {
+                        key_path: format!("{}.{}", path, name),
+                        key: name.to_string(),
+                        value: text.to_string(),
+                        sensitive: in_section || is_sensitive_key(name),
+                        decoded: None,
+                    }),
+                    None => walk(item, &format!("{}[{}]", path, i), in_section, leaves),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Split a `NAME=value` string whose name is an identifier
+fn split_assignment(item: &str) -> Option<(&str, &str)> {
+    let (name, value) = item.split_once('=')?;
+    let mut chars = name.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    identifier.then_some((name, value))
+}
+
+/// Whether a key name designates a secret value
+pub fn is_sensitive_key(key: &str) -> bool {
+    let normalized = key.to_lowercase().replace('-', "_");
+    SENSITIVE_KEYS.iter().any(|k| normalized.ends_with(k))
+}
+
+/// Whether a value is empty or a template placeholder rather than a secret
+pub fn is_placeholder(value: &str) -> bool {
+    value.trim().len() < 4 || is_template(value)
+}
+
+/// Whether a value is a `${VAR}`, `{{ var }}` or `<value>` placeholder
+pub fn is_template(value: &str) -> bool {
+    let value = value.trim();
+
+    (value.starts_with("${") && value.ends_with('}'))
+        || (value.starts_with("{{") && value.ends_with("}}"))
+        || (value.starts_with('<') && value.ends_with('>'))
+}
+
+/// Find the 0-based line where a leaf is written, searching from `from` first
+pub fn locate(lines: &[&str], leaf: &Leaf, from: usize) -> usize {
+    let first_value_line = leaf.value.lines().next().unwrap_or("");
+
+    let has_key = |line: &&str| line.contains(leaf.key.as_str());
+    let has_value = |line: &&str| !first_value_line.is_empty() && line.contains(first_value_line);
+
+    let search = |pred: &dyn Fn(&&str) -> bool| {
+        lines.iter().skip(from).position(pred).map(|i| i + from)
+            .or_else(|| lines.iter().position(pred))
+    };
+
+    search(&|line| has_key(line) && has_value(line))
+        .or_else(|| search(&has_key))
+        .or_else(|| search(&has_value))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf<'a>(leaves: &'a [Leaf], key_path: &str) -> &'a Leaf {
+        leaves.iter().find(|l| l.key_path == key_path).unwrap()
+    }
+
+    #[test]
+    fn test_parse_nested_json() {
+        let content = r#"{"database": {"host": "db", "password": "hunter22"}, "users": [{"api_key": "k"}]}"#;
+        let leaves = parse(Path::new("config.json"), content).unwrap();
+
+        assert!(leaf(&leaves, "database.password").sensitive);
+        assert!(!leaf(&leaves, "database.host").sensitive);
+        assert!(leaf(&leaves, "users[0].api_key").sensitive);
+    }
+
+    #[test]
+    fn test_parse_yaml_documents_and_string_data() {
+        let content = "kind: ConfigMap\ndata:\n  mode: prod\n---\nkind: Secret\nstringData:\n  DB_URL: postgres://app\n";
+        let leaves = parse(Path::new("deploy.yaml"), content).unwrap();
+
+        assert!(!leaf(&leaves, "data.mode").sensitive);
+        assert!(leaf(&leaves, "stringData.DB_URL").sensitive);
+    }
+
+    #[test]
+    fn test_kubernetes_secret_data_decoded() {
+        let content = "\
+apiVersion: v1
+kind: Secret
+metadata:
+  name: app
+data:
+  username: YWRtaW4=
+  blob: '!!not base64'
+";
+        let leaves = parse(Path::new("secret.yaml"), content).unwrap();
+
+        let username = leaf(&leaves, "data.username");
+        assert!(username.sensitive);
+        assert_eq!(username.decoded.as_deref(), Some("admin"));
+        assert!(leaf(&leaves, "data.blob").decoded.is_none());
+        assert!(leaf(&leaves, "metadata.name").decoded.is_none());
+
+        let config_map = content.replace("kind: Secret", "kind: ConfigMap");
+        let leaves = parse(Path::new("cm.yaml"), &config_map).unwrap();
+        assert!(!leaf(&leaves, "data.username").sensitive);
+    }
+
+    #[test]
+    fn test_compose_environment_list() {
+        let content = "\
+services:
+  db:
+    image: postgres
+    environment:
+      - POSTGRES_USER=app
+      - POSTGRES_PASSWORD=hunter22
+    command: [\"postgres\", \"-c\", \"log=all\"]
+";
+        let leaves = parse(Path::new("docker-compose.yml"), content).unwrap();
+
+        assert!(leaf(&leaves, "services.db.environment.POSTGRES_PASSWORD").sensitive);
+        assert_eq!(leaf(&leaves, "services.db.environment.POSTGRES_PASSWORD").value, "hunter22");
+        assert!(!leaf(&leaves, "services.db.environment.POSTGRES_USER").sensitive);
+    }
+
+    #[test]
+    fn test_parse_toml_and_fallback() {
+        let leaves = parse(Path::new("app.toml"), "[auth]\nclient_secret = \"s3cr3t!!\"\n").unwrap();
+        assert!(leaf(&leaves, "auth.client_secret").sensitive);
+
+        assert!(parse(Path::new("tsconfig.json"), "{ // comment\n}").is_none());
+        assert!(parse(Path::new("app.py"), "x = 1").is_none());
+    }
+
+    #[test]
+    fn test_sensitive_keys_and_placeholders() {
+        assert!(is_sensitive_key("DB_PASSWORD"));
+        assert!(is_sensitive_key("client-secret"));
+        assert!(!is_sensitive_key("password_min_length"));
+
+        assert!(is_placeholder("${DB_PASSWORD}"));
+        assert!(is_placeholder("{{ .Values.token }}"));
+        assert!(is_placeholder(""));
+        assert!(!is_placeholder("hunter22"));
+    }
+
+    #[test]
+    fn test_locate_repeated_keys() {
+        let content = "a:\n  password: one1\nb:\n  password: two2\n";
+        let lines: Vec<&str> = content.lines().collect();
+        let leaves = parse(Path::new("x.yml"), content).unwrap();
+
+        assert_eq!(locate(&lines, &leaves[0], 0), 1);
+        assert_eq!(locate(&lines, &leaves[1], 2), 3);
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension appended to the original file name of a stub
+pub const STUB_EXTENSION: &str = "enveil";
+
+/// Pointer left at the original location of a moved file (`.env` becomes
+/// `.env.enveil`), so whoever opens the project knows where it went
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stub {
+    /// Absolute path of the protected file
+    pub protected_path: String,
+    /// Absolute path of the secure directory holding it
+    pub secure_dir: String,
+}
+
+impl Stub {
+    pub fn new(protected_path: &Path, secure_dir: &Path) -> Self {
+        // Absolute paths keep the stub valid from any working directory
+        let absolute = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        Self {
+            protected_path: absolute(protected_path).to_string_lossy().to_string(),
+            secure_dir: absolute(secure_dir).to_string_lossy().to_string(),
+        }
+    }
+
+    /// Path of the stub for an original file
+    pub fn path_for(original: &Path) -> PathBuf {
+        let mut name = original.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", STUB_EXTENSION));
+        original.with_file_name(name)
+    }
+
+    /// Original file a stub stands for
+    pub fn original_for(stub_path: &Path) -> Option<PathBuf> {
+        if !Self::is_stub(stub_path) {
+            return None;
+        }
+        Some(stub_path.with_extension(""))
+    }
+
+    /// Whether a path is named like a stub
+    pub fn is_stub(path: &Path) -> bool {
+        path.extension().is_some_and(|e| e == STUB_EXTENSION)
+    }
+
+    /// Write the stub next to where `original` was, returning its path
+    pub fn write(&self, original: &Path) -> Result<PathBuf, String> {
+        let path = Self::path_for(original);
+        let fields = toml::to_string(self).map_err(|e| format!("Failed to serialize stub: {}", e))?;
+        let name = original.file_name().unwrap_or_default().to_string_lossy();
+        let content = format!(
+            "# {} was protected by Enveil and moved to the secure directory.\n\
+             # Put it back with:\n\
+             #   enveil restore {}\n\n{}",
+            name,
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            fields
+        );
+
+        fs::write(&path, content).map_err(|e| format!("Failed to write stub {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    /// Read a stub file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read stub {}: {}", path.display(), e))?;
+
+        toml::from_str(&content).map_err(|e| format!("Invalid stub {}: {}", path.display(), e))
+    }
+
+    /// Delete the stub of an original file, if there is a valid one
+    pub fn remove(original: &Path) {
+        let path = Self::path_for(original);
+        if Self::load(&path).is_ok() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let original = temp_dir.path().join(".env");
+        let stub = Stub::new(&temp_dir.path().join("enveil_secure/.env"), &temp_dir.path().join("enveil_secure"));
+
+        let path = stub.write(&original).unwrap();
+        assert_eq!(path, temp_dir.path().join(".env.enveil"));
+        assert!(fs::read_to_string(&path).unwrap().contains("enveil restore .env.enveil"));
+        assert_eq!(Stub::load(&path).unwrap(), stub);
+        assert_eq!(Stub::original_for(&path), Some(original.clone()));
+        assert_eq!(Stub::original_for(&original), None);
+
+        Stub::remove(&original);
+        assert!(!path.exists());
+    }
+}
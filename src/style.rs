@@ -0,0 +1,278 @@
+use owo_colors::OwoColorize;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// When output is colored
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    /// On terminals, unless `NO_COLOR` is set or `TERM` is `dumb`
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!("Invalid color choice: {} (expected auto, always or never)", s)),
+        }
+    }
+}
+
+/// Stream a line is printed to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Tone {
+    Red,
+    Yellow,
+    Green,
+    Cyan,
+}
+
+/// An emoji starting a line of output: its ASCII replacement (empty to
+/// drop it), its color and whether the color covers the whole line
+struct Marker {
+    emoji: &'static str,
+    ascii: &'static str,
+    tone: Option<Tone>,
+    whole_line: bool,
+}
+
+const fn marker(emoji: &'static str, ascii: &'static str, tone: Option<Tone>, whole_line: bool) -> Marker {
+    Marker { emoji, ascii, tone, whole_line }
+}
+
+const MARKERS: &[Marker] = &[
+    marker("❌", "[x]", Some(Tone::Red), true),
+    marker("🚨", "[!!]", Some(Tone::Red), true),
+    marker("⚠️", "[!]", Some(Tone::Yellow), true),
+    marker("⚠", "[!]", Some(Tone::Yellow), true),
+    marker("✅", "[ok]", Some(Tone::Green), false),
+    marker("ℹ️", "[i]", Some(Tone::Cyan), false),
+    // Risk levels, followed by a [HIGH] style tag that carries the color
+    marker("🔴", "", None, false),
+    marker("🟡", "", None, false),
+    marker("🟢", "", None, false),
+    marker("📁", "", None, false),
+    marker("📋", "", None, false),
+    marker("📄", "", None, false),
+    marker("📥", "", None, false),
+    marker("🔑", "", None, false),
+    marker("🔐", "", None, false),
+    marker("🔒", "", None, false),
+    marker("🔓", "", None, false),
+    marker("🔍", "", None, false),
+    marker("🔗", "", None, false),
+    marker("🔁", "", None, false),
+    marker("↩️", "", None, false),
+    marker("🙈", "", None, false),
+    marker("🧹", "", None, false),
+    marker("💬", "", None, false),
+    marker("🏢", "", None, false),
+    marker("🩺", "", None, false),
+    marker("👀", "", None, false),
+    marker("🚀", "", None, false),
+    marker("📈", "", None, false),
+    marker("⏳", "", None, false),
+    marker("📣", "", None, false),
+];
+
+const RISK_TAGS: &[(&str, Tone)] = &[
+    ("[CRITICAL]", Tone::Red),
+    ("[HIGH]", Tone::Red),
+    ("[MEDIUM]", Tone::Yellow),
+    ("[LOW]", Tone::Green),
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Settings {
+    color: ColorChoice,
+    ascii: bool,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Set how output is decorated, once at startup (`--color`, `--ascii`)
+pub fn init(color: ColorChoice, ascii: bool) {
+    let _ = SETTINGS.set(Settings { color, ascii });
+}
+
+fn settings() -> Settings {
+    SETTINGS.get().copied().unwrap_or_default()
+}
+
+/// Whether output is plain ASCII, without emoji
+pub fn ascii() -> bool {
+    settings().ascii
+}
+
+/// Whether lines printed to a stream get ANSI colors
+pub fn colors(stream: Stream) -> bool {
+    match settings().color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+            let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+            let terminal = match stream {
+                Stream::Stdout => std::io::stdout().is_terminal(),
+                Stream::Stderr => std::io::stderr().is_terminal(),
+            };
+            terminal && !no_color && !dumb
+        }
+    }
+}
+
+/// Print a message line to `stdout` or `stderr`, decorated following the
+/// settings: `emit!(stderr, "❌ Error: {}", e)`. Data (decrypted files,
+/// templates) is written with `print!` instead.
+#[macro_export]
+macro_rules! emit {
+    (stdout) => { std::println!() };
+    (stderr) => { std::eprintln!() };
+    (stdout, $($arg:tt)*) => {
+        std::println!("{}", $crate::style::decorate(&format!($($arg)*), $crate::style::Stream::Stdout))
+    };
+    (stderr, $($arg:tt)*) => {
+        std::eprintln!("{}", $crate::style::decorate(&format!($($arg)*), $crate::style::Stream::Stderr))
+    };
+}
+
+/// Decorate output for a stream following the settings: color the lines
+/// starting with a status emoji and, with `--ascii`, replace or drop the
+/// emoji. Lines not starting with one (e.g. JSON) are left untouched.
+pub fn decorate(text: &str, stream: Stream) -> String {
+    decorate_with(text, colors(stream), ascii())
+}
+
+fn decorate_with(text: &str, colors: bool, ascii: bool) -> String {
+    if !colors && !ascii {
+        return text.to_string();
+    }
+    text.split_inclusive('\n').map(|line| decorate_line(line, colors, ascii)).collect()
+}
+
+fn decorate_line(line: &str, colors: bool, ascii: bool) -> String {
+    let body = line.trim_start_matches(' ');
+    let indent = &line[..line.len() - body.len()];
+    let marker = match MARKERS.iter().find(|marker| body.starts_with(marker.emoji)) {
+        Some(marker) => marker,
+        None => return line.to_string(),
+    };
+
+    let rest = &body[marker.emoji.len()..];
+    let (symbol, rest) = match ascii {
+        true if marker.ascii.is_empty() => ("", rest.trim_start_matches(' ')),
+        true => (marker.ascii, rest),
+        false => (marker.emoji, rest),
+    };
+    let rest = match ascii {
+        true => strip_emoji(rest),
+        false => rest.to_string(),
+    };
+
+    let tone = match marker.tone {
+        Some(tone) if colors => tone,
+        _ if colors => return format!("{}{}{}", indent, symbol, paint_risk_tags(&rest)),
+        _ => return format!("{}{}{}", indent, symbol, rest),
+    };
+    match marker.whole_line {
+        true => {
+            let (content, newline) = split_newline(&rest);
+            format!("{}{}{}", indent, paint(&format!("{}{}", symbol, content), tone), newline)
+        }
+        false => format!("{}{}{}", indent, paint(symbol, tone), rest),
+    }
+}
+
+/// Color the risk tags (`[HIGH]`) of a line
+fn paint_risk_tags(line: &str) -> String {
+    let mut line = line.to_string();
+    for (tag, tone) in RISK_TAGS {
+        if line.contains(tag) {
+            line = line.replace(tag, &paint(tag, *tone));
+        }
+    }
+    line
+}
+
+fn paint(text: &str, tone: Tone) -> String {
+    match tone {
+        Tone::Red => text.red().to_string(),
+        Tone::Yellow => text.yellow().to_string(),
+        Tone::Green => text.green().to_string(),
+        Tone::Cyan => text.cyan().to_string(),
+    }
+}
+
+fn split_newline(line: &str) -> (&str, &str) {
+    match line.strip_suffix('\n') {
+        Some(content) => (content, "\n"),
+        None => (line, ""),
+    }
+}
+
+/// Drop the emoji left in a line
+fn strip_emoji(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_emoji(c) {
+            // Drop the emoji with its variation selector and one space after it
+            while chars.next_if(|next| *next == '\u{FE0F}').is_some() {}
+            chars.next_if_eq(&' ');
+            continue;
+        }
+        stripped.push(c);
+    }
+    stripped
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0xFE0F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        assert_eq!("Always".parse::<ColorChoice>().unwrap(), ColorChoice::Always);
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn test_ascii() {
+        let text = "\n📁 Enveil Scan Report\n\n🔴 [HIGH] .env\n❌ Error: no key\n⚠️  Shared key\n   ✅ a.txt -> b\n{\"line\": \"🔑 x\"}\n";
+        assert_eq!(
+            decorate_with(text, false, true),
+            "\nEnveil Scan Report\n\n[HIGH] .env\n[x] Error: no key\n[!]  Shared key\n   [ok] a.txt -> b\n{\"line\": \"🔑 x\"}\n"
+        );
+        assert_eq!(decorate_with("🔑 3 secrets found 🔒 sealed", false, true), "3 secrets found sealed");
+        assert_eq!(decorate_with(text, false, false), text);
+    }
+
+    #[test]
+    fn test_colors() {
+        let colored = decorate_with("❌ Error: no key\n", true, false);
+        assert!(colored.starts_with("\u{1b}[31m❌ Error: no key"));
+        assert!(colored.ends_with("\u{1b}[39m\n"));
+
+        let colored = decorate_with("🔴 [HIGH] .env", true, true);
+        assert_eq!(colored, format!("{} .env", "[HIGH]".red()));
+        assert_eq!(decorate_with("plain line\n", true, false), "plain line\n");
+    }
+}
@@ -0,0 +1,371 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::manifest::{FileMetadata, Manifest, ManifestEntry};
+use crate::protector::{self, ProtectAction, ProtectResult};
+use crate::restorer::RestoreResult;
+
+/// Address of the Vault server
+pub const ADDR_ENV: &str = "VAULT_ADDR";
+/// Token authenticating with Vault
+pub const TOKEN_ENV: &str = "VAULT_TOKEN";
+/// AppRole credentials, used when no token is set
+pub const ROLE_ID_ENV: &str = "VAULT_ROLE_ID";
+pub const SECRET_ID_ENV: &str = "VAULT_SECRET_ID";
+/// Vault Enterprise namespace
+pub const NAMESPACE_ENV: &str = "VAULT_NAMESPACE";
+
+/// KV version 2 secrets engine used unless another mount is given
+pub const DEFAULT_MOUNT: &str = "secret";
+
+/// Prefix of the protected path recorded for files pushed to Vault
+const PATH_PREFIX: &str = "vault:";
+
+const USER_AGENT: &str = concat!("enveil/", env!("CARGO_PKG_VERSION"));
+
+/// Where protected files are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// The secure directory next to the project
+    Local,
+    /// A Vault KV version 2 secrets engine
+    Vault,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "local" => Ok(Backend::Local),
+            "vault" => Ok(Backend::Vault),
+            _ => Err(format!("Unknown backend: {} (expected local or vault)", s)),
+        }
+    }
+}
+
+/// File stored as a Vault secret
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VaultSecret {
+    pub file_name: String,
+    /// Base64 content of the file
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+struct ReadResponse {
+    data: ReadData,
+}
+
+#[derive(Deserialize)]
+struct ReadData {
+    data: VaultSecret,
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    auth: LoginAuth,
+}
+
+#[derive(Deserialize)]
+struct LoginAuth {
+    client_token: String,
+}
+
+/// Client of the Vault HTTP API
+pub struct Vault {
+    addr: String,
+    token: String,
+    namespace: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl Vault {
+    /// Client of the server at `addr`, authenticated with a token
+    pub fn new(addr: &str, token: String) -> Self {
+        Self {
+            addr: addr.trim_end_matches('/').to_string(),
+            token,
+            namespace: None,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Send requests to a Vault Enterprise namespace
+    pub fn with_namespace(mut self, namespace: Option<String>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Client configured from `VAULT_ADDR`, and `VAULT_TOKEN` or an AppRole
+    /// login with `VAULT_ROLE_ID` and `VAULT_SECRET_ID`
+    pub fn from_env() -> Result<Self, String> {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let addr = env(ADDR_ENV).ok_or_else(|| format!("{} is not set", ADDR_ENV))?;
+        let client = Self::new(&addr, String::new()).with_namespace(env(NAMESPACE_ENV));
+
+        match (env(TOKEN_ENV), env(ROLE_ID_ENV), env(SECRET_ID_ENV)) {
+            (Some(token), _, _) => Ok(Self { token, ..client }),
+            (None, Some(role_id), Some(secret_id)) => client.approle_login(&role_id, &secret_id),
+            _ => Err(format!(
+                "No Vault credentials: set {}, or {} and {}",
+                TOKEN_ENV, ROLE_ID_ENV, SECRET_ID_ENV
+            )),
+        }
+    }
+
+    /// Log in with AppRole credentials, returning a client using the token obtained
+    pub fn approle_login(self, role_id: &str, secret_id: &str) -> Result<Self, String> {
+        let path = "/v1/auth/approle/login";
+        let response: LoginResponse = self
+            .request("POST", path)
+            .send_json(serde_json::json!({ "role_id": role_id, "secret_id": secret_id }))
+            .map_err(|e| api_error(path, e))?
+            .into_json()
+            .map_err(|e| format!("Invalid login response from Vault: {}", e))?;
+
+        Ok(Self { token: response.auth.client_token, ..self })
+    }
+
+    /// Write a secret to `path` of a KV version 2 mount
+    pub fn write(&self, mount: &str, path: &str, secret: &VaultSecret) -> Result<(), String> {
+        let path = format!("/v1/{}/data/{}", mount, path);
+        self.request("POST", &path)
+            .send_json(serde_json::json!({ "data": secret }))
+            .map_err(|e| api_error(&path, e))?;
+        Ok(())
+    }
+
+    /// Read the secret at `path` of a KV version 2 mount
+    pub fn read(&self, mount: &str, path: &str) -> Result<VaultSecret, String> {
+        let path = format!("/v1/{}/data/{}", mount, path);
+        let response: ReadResponse = self
+            .request("GET", &path)
+            .call()
+            .map_err(|e| api_error(&path, e))?
+            .into_json()
+            .map_err(|e| format!("Invalid secret at {}: {}", path, e))?;
+
+        Ok(response.data.data)
+    }
+
+    /// Push files to `<mount>/<prefix>/<path relative to root>`, recording
+    /// each in the manifest of the secure directory. An original is only
+    /// removed once its content is stored and recorded.
+    pub fn protect_files(&self, files: &[PathBuf], root: &Path, secure_dir: &Path, mount: &str, prefix: &str) -> Vec<ProtectResult> {
+        files
+            .iter()
+            .map(|file| {
+                let relative = protector::relative_to(root, file).to_string_lossy().replace('\\', "/");
+                let path = format!("{}/{}", prefix.trim_matches('/'), relative);
+                let protected_path = format!("{}{}/{}", PATH_PREFIX, mount, path);
+                let result = |success: bool, message: String| ProtectResult {
+                    original_path: file.to_string_lossy().to_string(),
+                    protected_path: protected_path.clone(),
+                    action: ProtectAction::Vaulted,
+                    success,
+                    message,
+                };
+
+                let pushed = fs::read(file)
+                    .map_err(|e| format!("Failed to read file: {}", e))
+                    .and_then(|content| {
+                        let secret = VaultSecret {
+                            file_name: file.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                            content: base64::engine::general_purpose::STANDARD.encode(content),
+                        };
+                        self.write(mount, &path, &secret)
+                    })
+                    .and_then(|_| {
+                        let original = fs::canonicalize(file).unwrap_or_else(|_| file.clone());
+                        let mut entry = ManifestEntry::new(&original, Path::new(&protected_path), ProtectAction::Vaulted);
+                        entry.metadata = FileMetadata::capture(file).ok();
+                        fs::create_dir_all(secure_dir)
+                            .map_err(|e| format!("Failed to create secure directory: {}", e))?;
+                        protector::restrict_permissions(secure_dir, protector::SECURE_DIR_MODE)?;
+                        Manifest::record(secure_dir, entry)
+                    });
+
+                match pushed.and_then(|_| fs::remove_file(file).map_err(|e| format!("Stored in Vault, but original not removed: {}", e))) {
+                    Ok(()) => result(true, "File stored in Vault".to_string()),
+                    Err(e) => result(false, e),
+                }
+            })
+            .collect()
+    }
+
+    /// Content of a file recorded as stored in Vault
+    pub fn pull(&self, entry: &ManifestEntry) -> Result<Vec<u8>, String> {
+        let (mount, path) = entry
+            .protected_path
+            .strip_prefix(PATH_PREFIX)
+            .and_then(|location| location.split_once('/'))
+            .ok_or_else(|| format!("Invalid Vault location {}", entry.protected_path))?;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(self.read(mount, path)?.content)
+            .map_err(|e| format!("Invalid content in Vault: {}", e))
+    }
+
+    /// Pull every file recorded as stored in Vault back to its original
+    /// location. Existing files are skipped unless `force` is set. The
+    /// secrets stay in Vault.
+    pub fn restore_files(&self, secure_dir: &Path, force: bool) -> Result<Vec<RestoreResult>, String> {
+        let mut manifest = Manifest::load(secure_dir)?;
+        let mut results = Vec::new();
+        let mut restored = Vec::new();
+
+        for (index, entry) in manifest.entries.iter().enumerate() {
+            if entry.action != ProtectAction::Vaulted {
+                continue;
+            }
+            let original = Path::new(&entry.original_path);
+            let result = |success: bool, message: String| RestoreResult {
+                encrypted_path: entry.protected_path.clone(),
+                restored_path: entry.original_path.clone(),
+                success,
+                message,
+            };
+
+            if original.exists() && !force {
+                results.push(result(false, "Original location is occupied (use --force)".to_string()));
+                continue;
+            }
+
+            let pulled = self.pull(entry).and_then(|content| {
+                if let Some(parent) = original.parent() {
+                    fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+                }
+                fs::write(original, content).map_err(|e| format!("Failed to write file: {}", e))
+            });
+
+            match pulled {
+                Ok(()) => {
+                    let message = match entry.metadata.as_ref().map(|m| m.apply(original)) {
+                        Some(Err(e)) => format!("Restored from Vault (warning: {})", e),
+                        _ => "Restored from Vault".to_string(),
+                    };
+                    restored.push(index);
+                    results.push(result(true, message));
+                }
+                Err(e) => results.push(result(false, e)),
+            }
+        }
+
+        for index in restored.into_iter().rev() {
+            manifest.entries.remove(index);
+        }
+        manifest.save(secure_dir)?;
+        Ok(results)
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self
+            .agent
+            .request(method, &format!("{}{}", self.addr, path))
+            .set("User-Agent", USER_AGENT);
+        let request = match &self.namespace {
+            Some(namespace) => request.set("X-Vault-Namespace", namespace),
+            None => request,
+        };
+        if self.token.is_empty() {
+            request
+        } else {
+            request.set("X-Vault-Token", &self.token)
+        }
+    }
+}
+
+/// Default path prefix of a project's files: `enveil/<project directory name>`
+pub fn default_prefix(project_path: &Path) -> String {
+    let name = fs::canonicalize(project_path)
+        .ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "project".to_string());
+    format!("enveil/{}", name)
+}
+
+/// Describe a failed API request, with the errors Vault returned
+fn api_error(path: &str, error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(status, response) => {
+            let errors = response
+                .into_json::<serde_json::Value>()
+                .ok()
+                .and_then(|body| body["errors"].as_array().cloned())
+                .map(|errors| errors.iter().filter_map(|e| e.as_str()).collect::<Vec<_>>().join(", "))
+                .unwrap_or_default();
+            format!("Vault returned {} for {}: {}", status, path, errors)
+        }
+        e => format!("Vault request failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_mock::serve;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_protect_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let content = base64::engine::general_purpose::STANDARD.encode("API_KEY=abc\n");
+        let (addr, server) = serve(vec![
+            (200, "{}".to_string()),
+            (200, format!(r#"{{"data": {{"data": {{"file_name": ".env", "content": "{}"}}}}}}"#, content)),
+        ]);
+        let vault = Vault::new(&addr, "s.token".to_string());
+
+        let results = vault.protect_files(std::slice::from_ref(&env_file), temp_dir.path(), &secure_dir, "secret", "enveil/app");
+        assert!(results[0].success, "{}", results[0].message);
+        assert_eq!(results[0].protected_path, "vault:secret/enveil/app/.env");
+        assert!(!env_file.exists());
+
+        let results = vault.restore_files(&secure_dir, false).unwrap();
+        assert!(results[0].success, "{}", results[0].message);
+        assert_eq!(fs::read_to_string(&env_file).unwrap(), "API_KEY=abc\n");
+        assert!(Manifest::load(&secure_dir).unwrap().entries.is_empty());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /v1/secret/data/enveil/app/.env "));
+        assert!(requests[0].to_lowercase().contains("x-vault-token: s.token"));
+        assert!(requests[0].contains(&content));
+        assert!(requests[1].starts_with("GET /v1/secret/data/enveil/app/.env "));
+    }
+
+    #[test]
+    fn test_failed_push_keeps_original() {
+        let temp_dir = TempDir::new().unwrap();
+        let env_file = temp_dir.path().join(".env");
+        fs::write(&env_file, "API_KEY=abc\n").unwrap();
+
+        let (addr, server) = serve(vec![(403, r#"{"errors": ["permission denied"]}"#.to_string())]);
+        let vault = Vault::new(&addr, "s.token".to_string());
+        let results = vault.protect_files(std::slice::from_ref(&env_file), temp_dir.path(), &temp_dir.path().join("enveil_secure"), "secret", "enveil/app");
+        server.join().unwrap();
+
+        assert!(!results[0].success);
+        assert!(results[0].message.contains("403"));
+        assert!(results[0].message.contains("permission denied"));
+        assert!(env_file.exists());
+    }
+
+    #[test]
+    fn test_approle_login() {
+        let (addr, server) = serve(vec![(200, r#"{"auth": {"client_token": "s.approle"}}"#.to_string())]);
+        let vault = Vault::new(&addr, String::new()).approle_login("role", "secret").unwrap();
+        let requests = server.join().unwrap();
+
+        assert_eq!(vault.token, "s.approle");
+        assert!(requests[0].starts_with("POST /v1/auth/approle/login "));
+        assert!(requests[0].contains(r#""role_id":"role""#));
+    }
+}
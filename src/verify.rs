@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::manifest::{self, Manifest};
+use crate::protector::ProtectAction;
+use crate::restorer::DecryptionKey;
+
+/// Outcome of checking one protected file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum VerifyStatus {
+    /// Digest matches, and the file decrypts when a key was given
+    Ok,
+    /// Recorded before digests were stored: only decryption was checked, if anything
+    Unchecked,
+    /// The protected file is gone
+    Missing,
+    /// Content differs from what protect wrote (tampering, truncation, bit rot)
+    Modified,
+    /// Digest matches but the file does not decrypt with the key
+    Undecryptable(String),
+}
+
+/// Check result of a protected file
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyResult {
+    pub protected_path: String,
+    pub original_path: String,
+    pub status: VerifyStatus,
+}
+
+impl VerifyResult {
+    /// Whether the file can still be restored as far as the checks go
+    pub fn is_healthy(&self) -> bool {
+        matches!(self.status, VerifyStatus::Ok | VerifyStatus::Unchecked)
+    }
+}
+
+/// Check every file recorded in the manifest of a secure directory against
+/// its stored digest, and decrypt encrypted files (without writing the
+/// plaintext anywhere) when a key is given
+pub fn verify(secure_dir: &Path, key: Option<&DecryptionKey>) -> Result<Vec<VerifyResult>, String> {
+    let manifest = Manifest::load(secure_dir)?;
+    let mut results = Vec::new();
+
+    // Files stored in Vault are not in the secure directory
+    for entry in manifest.entries.iter().filter(|e| e.action != ProtectAction::Vaulted) {
+        let location = match entry.location(secure_dir) {
+            Some(location) => location,
+            None => continue,
+        };
+
+        let status = if !location.is_file() {
+            VerifyStatus::Missing
+        } else if entry.digest.is_some() && manifest::file_digest(&location).ok() != entry.digest {
+            VerifyStatus::Modified
+        } else {
+            let decrypted = match (key, &entry.action) {
+                (Some(key), ProtectAction::Encrypted) => Some(
+                    fs::File::open(&location)
+                        .map_err(|e| format!("Failed to read file: {}", e))
+                        .and_then(|input| key.decrypt_stream(io::BufReader::new(input), io::sink())),
+                ),
+                _ => None,
+            };
+
+            match decrypted {
+                Some(Err(e)) => VerifyStatus::Undecryptable(e),
+                Some(Ok(())) => VerifyStatus::Ok,
+                None if entry.digest.is_none() => VerifyStatus::Unchecked,
+                None => VerifyStatus::Ok,
+            }
+        };
+
+        results.push(VerifyResult {
+            protected_path: location.to_string_lossy().to_string(),
+            original_path: entry.original_path.clone(),
+            status,
+        });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protector::{self, FileProtector, ProtectOption};
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detects_tampering_and_missing_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let secure_dir = temp_dir.path().join("enveil_secure");
+        fs::write(temp_dir.path().join(".env"), "API_KEY=abc\n").unwrap();
+        fs::write(temp_dir.path().join("id_rsa"), "key\n").unwrap();
+        fs::write(temp_dir.path().join("secrets.yaml"), "token: abc\n").unwrap();
+        let key = protector::generate_key();
+
+        let protector = FileProtector::new(secure_dir.clone());
+        let results = protector.protect_directory(temp_dir.path(), &ProtectOption::Encrypt, Some(&key));
+        assert!(results.iter().all(|r| r.success));
+
+        let results = verify(&secure_dir, Some(&DecryptionKey::Raw(key))).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.status == VerifyStatus::Ok));
+
+        // Bit rot in one file, another deleted
+        let mut file = OpenOptions::new().append(true).open(secure_dir.join(".env.enc")).unwrap();
+        file.write_all(b"x").unwrap();
+        fs::remove_file(secure_dir.join("id_rsa.enc")).unwrap();
+
+        let results = verify(&secure_dir, None).unwrap();
+        let status = |name: &str| &results.iter().find(|r| r.protected_path.ends_with(name)).unwrap().status;
+        assert_eq!(status(".env.enc"), &VerifyStatus::Modified);
+        assert_eq!(status("id_rsa.enc"), &VerifyStatus::Missing);
+        assert_eq!(status("secrets.yaml.enc"), &VerifyStatus::Ok);
+
+        let wrong = DecryptionKey::Raw(protector::generate_key());
+        let results = verify(&secure_dir, Some(&wrong)).unwrap();
+        let last = results.iter().find(|r| r.protected_path.ends_with("secrets.yaml.enc")).unwrap();
+        assert!(matches!(last.status, VerifyStatus::Undecryptable(_)));
+    }
+}
@@ -2,8 +2,6 @@
 
 #[cfg(test)]
 mod git_hooks_tests {
-    use std::fs;
-    use std::path::Path;
     use tempfile::TempDir;
     
     #[test]
@@ -11,7 +9,7 @@ mod git_hooks_tests {
         let temp_dir = TempDir::new().unwrap();
         // Create a git repo
         std::process::Command::new("git")
-            .args(&["init"])
+            .args(["init"])
             .current_dir(temp_dir.path())
             .output()
             .expect("Failed to init git");